@@ -4,10 +4,15 @@ mod project;
 
 use crate::error::UpdateError;
 use crate::licenses::License;
-use crate::project::{Page, Project, Selector};
-use log::{error, info};
+use crate::project::{
+    ArchiveOptions, DocumentMetadata, DownsampleOptions, ExpectedChanges, ExportPreview,
+    OrderingOperation, Page, PageImageOptions, PageRangeError, PageSize, PreflightReport, Project,
+    Redaction, SanitizeOptions, SearchResult, Selector, SeparatorOptions, SigningOptions, Split,
+    TextOptions, TextPageTemplate, Tile, TileOptions,
+};
+use log::{error, info, warn};
 use project::SourceFile;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::sync::{mpsc, Mutex};
 use tauri::ipc::Channel;
@@ -15,7 +20,7 @@ use tauri::menu::*;
 use tauri::menu::{MenuBuilder, SubmenuBuilder};
 use tauri::Manager;
 use tauri::{AppHandle, Emitter};
-use tauri_plugin_dialog::{DialogExt, FilePath, MessageDialogButtons};
+use tauri_plugin_dialog::{DialogExt, FilePath, MessageDialogButtons, MessageDialogKind};
 use tauri_plugin_updater::{Error, UpdaterExt};
 
 /// TO RELEASE
@@ -77,7 +82,11 @@ impl UpdatePrompt {
     }
 }
 
-async fn add_files(app: AppHandle, paths: Vec<PathBuf>) -> Result<(), String> {
+async fn add_files(
+    app: AppHandle,
+    paths: Vec<PathBuf>,
+    separators: Option<SeparatorOptions>,
+) -> Result<(), String> {
     if paths.is_empty() {
         return Ok(());
     };
@@ -92,8 +101,13 @@ async fn add_files(app: AppHandle, paths: Vec<PathBuf>) -> Result<(), String> {
     for (index, path) in paths.into_iter().enumerate() {
         let (sender, receiver) = mpsc::channel();
 
+        let separators = separators.clone();
         let import_task = tauri::async_runtime::spawn_blocking(move || {
-            SourceFile::open(&path, Some(sender.clone()))
+            let mut file = SourceFile::open(&path, Some(sender.clone()))?;
+            if let Some(options) = &separators {
+                file.detect_separators(options)?;
+            }
+            Ok::<_, anyhow::Error>(file)
         });
 
         let receiver_app = app.clone();
@@ -138,7 +152,13 @@ const IMAGE_SUPPORTED_EXTENSIONS: &[&str] = &[
     "bmp", "ico", "hdr", "exr", "pbm", "pam", "ppm", "pgm", "pnm", "ff", "qoi",
 ];
 
-async fn open_files(app_handle: &AppHandle) -> Result<(), String> {
+/// Names of the files of a split export, when the user didn't choose any.
+const DEFAULT_PART_TEMPLATE: &str = "{source}-{index}";
+
+async fn open_files(
+    app_handle: &AppHandle,
+    separators: Option<SeparatorOptions>,
+) -> Result<(), String> {
     let picked_paths = app_handle
         .dialog()
         .file()
@@ -158,7 +178,7 @@ async fn open_files(app_handle: &AppHandle) -> Result<(), String> {
         return Ok(());
     };
 
-    let result = add_files(app_handle.clone(), picked_paths).await;
+    let result = add_files(app_handle.clone(), picked_paths, separators).await;
     if let Err(e) = &result {
         notify_error(&app_handle, &e);
     }
@@ -167,8 +187,8 @@ async fn open_files(app_handle: &AppHandle) -> Result<(), String> {
 }
 
 #[tauri::command]
-async fn open_files_command(app_handle: AppHandle) {
-    let result = open_files(&app_handle).await;
+async fn open_files_command(app_handle: AppHandle, separators: Option<SeparatorOptions>) {
+    let result = open_files(&app_handle, separators).await;
 
     if let Err(e) = &result {
         notify_error(&app_handle, &e);
@@ -189,16 +209,74 @@ fn load_project_command(
     Ok(state.clone())
 }
 
-async fn export(app_handle: &AppHandle, ordering: Vec<Selector>) -> Result<(), String> {
+/// Options of an export besides its pages. Everything is off by default.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct ExportOptions {
+    /// Checks the saved file against the selected pages.
+    verify: bool,
+    signing: Option<SigningOptions>,
+    metadata: Option<DocumentMetadata>,
+    sanitize: Option<SanitizeOptions>,
+    downsample: Option<DownsampleOptions>,
+    split: Option<Split>,
+    /// Names the parts of a split export, see `DEFAULT_PART_TEMPLATE`.
+    template: Option<String>,
+}
+
+async fn export(
+    app_handle: &AppHandle,
+    ordering: Vec<Selector>,
+    options: ExportOptions,
+) -> Result<(), String> {
     let app_handle = app_handle.clone();
+    let ExportOptions {
+        verify,
+        signing,
+        metadata,
+        sanitize,
+        downsample,
+        split,
+        template,
+    } = options;
 
     let _ = tauri::async_runtime::spawn_blocking(move || {
-        let path = app_handle
-            .dialog()
-            .file()
-            .set_file_name("project.pdf")
-            .add_filter("PDF", &["pdf"])
-            .blocking_save_file();
+        let signed_sources = {
+            let state = app_handle.state::<Mutex<AppState>>();
+            let Ok(unlocked_state) = state.lock() else {
+                return notify_error(&app_handle, "Couldn't lock the application state");
+            };
+            unlocked_state.project.signed_sources(&ordering)
+        };
+
+        if !signed_sources.is_empty() {
+            let confirm = app_handle
+                .dialog()
+                .message(format!(
+                    "These documents are digitally signed, their signatures won't be valid in the exported file:\n\n{}",
+                    signed_sources.join("\n")
+                ))
+                .title("Export")
+                .kind(MessageDialogKind::Warning)
+                .buttons(MessageDialogButtons::OkCancel)
+                .blocking_show();
+
+            if !confirm {
+                return;
+            }
+        }
+
+        // Splits named from a template are written to a folder rather than next to a file.
+        let path = if split.as_ref().is_some_and(Split::uses_template) {
+            app_handle.dialog().file().blocking_pick_folder()
+        } else {
+            app_handle
+                .dialog()
+                .file()
+                .set_file_name("project.pdf")
+                .add_filter("PDF", &["pdf"])
+                .blocking_save_file()
+        };
 
         let path = match path {
             Some(FilePath::Path(p)) => p,
@@ -213,18 +291,67 @@ async fn export(app_handle: &AppHandle, ordering: Vec<Selector>) -> Result<(), S
             return notify_error(&app_handle, "Couldn't lock the application state");
         };
 
-        let Ok(mut document) = unlocked_state.project.export(&ordering).or_else(|e| {
-            notify_error(
-                &app_handle,
-                format!("An error occurred while exporting the file: {}", e).as_str(),
-            );
-            let _ = app_handle.emit("rancher://did-not-export", ());
-            Err(())
-        }) else {
+        let metadata = metadata.unwrap_or_else(|| unlocked_state.project.default_metadata());
+
+        let finish = |mut document: lopdf::Document| -> anyhow::Result<_> {
+            let sanitized = sanitize
+                .as_ref()
+                .map(|options| project::sanitize(&mut document, options));
+            let downsampled = downsample
+                .as_ref()
+                .map(|options| project::downsample(&mut document, options));
+
+            let signed = signing
+                .as_ref()
+                .map(|options| options.for_document(&document));
+            let bytes = match &signed {
+                Some(options) => project::sign(document, options)?,
+                None => {
+                    let mut bytes = Vec::new();
+                    document.save_to(&mut bytes)?;
+                    bytes
+                }
+            };
+
+            Ok((bytes, (sanitized, downsampled, signed)))
+        };
+
+        let Ok((parts, report)) = unlocked_state
+            .project
+            .export_parts(&ordering, &metadata, split.as_ref(), finish)
+            .or_else(|e| {
+                notify_error(
+                    &app_handle,
+                    format!("An error occurred while exporting the file: {}", e).as_str(),
+                );
+                let _ = app_handle.emit("rancher://did-not-export", ());
+                Err(())
+            })
+        else {
             return;
         };
 
-        let Ok(_) = document.save(path).or_else(|e| {
+        let paths = if split.as_ref().is_some_and(Split::uses_template) {
+            unlocked_state.project.part_paths(
+                &path,
+                template.as_deref().unwrap_or(DEFAULT_PART_TEMPLATE),
+                &ordering,
+                &parts,
+            )
+        } else if parts.len() == 1 {
+            vec![path.clone()]
+        } else {
+            (1..=parts.len())
+                .map(|number| project::part_path(&path, number))
+                .collect()
+        };
+
+        let saved = parts
+            .iter()
+            .zip(&paths)
+            .try_for_each(|(part, path)| std::fs::write(path, part.bytes()));
+
+        let Ok(_) = saved.or_else(|e| {
             notify_error(
                 &app_handle,
                 format!("An error occurred while saving the file: {}", e).as_str(),
@@ -235,6 +362,114 @@ async fn export(app_handle: &AppHandle, ordering: Vec<Selector>) -> Result<(), S
             return;
         };
 
+        if verify {
+            for (part, path) in parts.iter().zip(&paths) {
+                let (sanitized, downsampled, signed) = part.output();
+                let changes = ExpectedChanges::new(
+                    sanitized.as_ref(),
+                    downsampled.as_ref(),
+                    signed.as_ref(),
+                );
+                match unlocked_state
+                    .project
+                    .verify_export(path, &ordering[part.pages()], changes)
+                {
+                    Ok(verification) if verification.is_valid() => {
+                        info!("Exported file verified: {}", path.display());
+                    }
+                    Ok(verification) => {
+                        warn!("Exported file failed verification: {:?}", verification);
+                        app_handle
+                            .dialog()
+                            .message(format!(
+                                "The exported file doesn't match the selected pages.\n\n{}",
+                                verification
+                            ))
+                            .title("Export verification")
+                            .kind(MessageDialogKind::Warning)
+                            .show(|_| {});
+                    }
+                    Err(e) => notify_error(
+                        &app_handle,
+                        format!("An error occurred while verifying the file: {}", e).as_str(),
+                    ),
+                }
+            }
+        }
+
+        let oversized = parts
+            .iter()
+            .filter(|part| part.is_oversized())
+            .map(|part| match part.pages() {
+                pages if pages.len() == 1 => format!("Page {}", pages.start + 1),
+                pages => format!("Pages {} to {}", pages.start + 1, pages.end),
+            })
+            .collect::<Vec<_>>();
+
+        if !oversized.is_empty() {
+            warn!("Export parts over the maximum size: {:?}", oversized);
+            app_handle
+                .dialog()
+                .message(format!(
+                    "These pages are over the maximum size on their own, their files are larger than requested:\n\n{}",
+                    oversized.join("\n")
+                ))
+                .title("Export")
+                .kind(MessageDialogKind::Warning)
+                .show(|_| {});
+        }
+
+        let sanitized = parts
+            .iter()
+            .filter_map(|part| part.output().0.clone())
+            .reduce(|mut total, sanitized| {
+                total += sanitized;
+                total
+            });
+        let downsampled = parts
+            .iter()
+            .filter_map(|part| part.output().1.clone())
+            .reduce(|mut total, downsampled| {
+                total += downsampled;
+                total
+            });
+
+        if !report.is_empty() {
+            warn!("Export dropped features: {:?}", report);
+            app_handle
+                .dialog()
+                .message(format!(
+                    "Some features of the original documents couldn't be kept in the exported file.\n\n{}",
+                    report
+                ))
+                .title("Export")
+                .kind(MessageDialogKind::Warning)
+                .show(|_| {});
+        }
+
+        if let Some(sanitized) = sanitized.filter(|sanitized| !sanitized.is_empty()) {
+            info!("Sanitized export: {:?}", sanitized);
+            app_handle
+                .dialog()
+                .message(format!(
+                    "The following were removed from the exported file:\n\n{}",
+                    sanitized
+                ))
+                .title("Sanitize")
+                .kind(MessageDialogKind::Info)
+                .show(|_| {});
+        }
+
+        if let Some(downsampled) = downsampled.filter(|downsampled| !downsampled.is_empty()) {
+            info!("Downsampled export: {:?}", downsampled);
+            app_handle
+                .dialog()
+                .message(format!("{}.", downsampled))
+                .title("Downsample")
+                .kind(MessageDialogKind::Info)
+                .show(|_| {});
+        }
+
         let _ = app_handle.emit("rancher://did-export", ());
     })
     .await;
@@ -248,14 +483,193 @@ fn notify_error(app_handle: &AppHandle, error: &str) {
 }
 
 #[tauri::command]
-async fn export_command(app_handle: AppHandle, ordering: Vec<Selector>) -> Result<(), String> {
-    let result = export(&app_handle, ordering).await;
+async fn export_command(
+    app_handle: AppHandle,
+    ordering: Vec<Selector>,
+    options: Option<ExportOptions>,
+) -> Result<(), String> {
+    let result = export(&app_handle, ordering, options.unwrap_or_default()).await;
     if let Err(e) = &result {
         notify_error(&app_handle, e);
     };
     result
 }
 
+/// Exports every selected page to its own image file in a folder, named after `template`.
+#[tauri::command]
+async fn export_images_command(
+    app_handle: AppHandle,
+    ordering: Vec<Selector>,
+    options: PageImageOptions,
+    template: Option<String>,
+) -> Result<(), String> {
+    let _ = tauri::async_runtime::spawn_blocking(move || {
+        let path = match app_handle.dialog().file().blocking_pick_folder() {
+            Some(FilePath::Path(p)) => p,
+            _ => return,
+        };
+
+        let _ = app_handle.emit("rancher://will-export", ());
+
+        let state = app_handle.state::<Mutex<AppState>>();
+        let Ok(unlocked_state) = state.lock() else {
+            let _ = app_handle.emit("rancher://did-not-export", ());
+            return notify_error(&app_handle, "Couldn't lock the application state");
+        };
+
+        let paths = unlocked_state.project.page_image_paths(
+            &path,
+            template.as_deref().unwrap_or(DEFAULT_PART_TEMPLATE),
+            &ordering,
+            &options,
+        );
+
+        let write = |index: usize, bytes: Vec<u8>| -> anyhow::Result<()> {
+            std::fs::write(&paths[index], bytes)?;
+            Ok(())
+        };
+
+        let result = unlocked_state
+            .project
+            .export_page_images(&ordering, &options, write);
+
+        if let Err(e) = result {
+            notify_error(
+                &app_handle,
+                format!("An error occurred while exporting the images: {}", e).as_str(),
+            );
+            let _ = app_handle.emit("rancher://did-not-export", ());
+            return;
+        }
+
+        let _ = app_handle.emit("rancher://did-export", ());
+    })
+    .await;
+
+    Ok(())
+}
+
+/// Exports the selected pages to a single multi-page TIFF or CBZ file.
+#[tauri::command]
+async fn export_archive_command(
+    app_handle: AppHandle,
+    ordering: Vec<Selector>,
+    metadata: Option<DocumentMetadata>,
+    options: ArchiveOptions,
+) -> Result<(), String> {
+    let _ = tauri::async_runtime::spawn_blocking(move || {
+        let extension = options.format().extension();
+        let path = app_handle
+            .dialog()
+            .file()
+            .set_file_name(format!("project.{}", extension))
+            .add_filter(extension.to_uppercase(), &[extension])
+            .blocking_save_file();
+
+        let path = match path {
+            Some(FilePath::Path(p)) => p,
+            _ => return,
+        };
+
+        let _ = app_handle.emit("rancher://will-export", ());
+
+        let state = app_handle.state::<Mutex<AppState>>();
+        let Ok(unlocked_state) = state.lock() else {
+            let _ = app_handle.emit("rancher://did-not-export", ());
+            return notify_error(&app_handle, "Couldn't lock the application state");
+        };
+
+        let metadata = metadata.unwrap_or_else(|| unlocked_state.project.default_metadata());
+
+        let result = unlocked_state
+            .project
+            .export_archive(&ordering, &metadata, &options)
+            .and_then(|bytes| Ok(std::fs::write(&path, bytes)?));
+
+        if let Err(e) = result {
+            notify_error(
+                &app_handle,
+                format!("An error occurred while exporting the file: {}", e).as_str(),
+            );
+            let _ = app_handle.emit("rancher://did-not-export", ());
+            return;
+        }
+
+        let _ = app_handle.emit("rancher://did-export", ());
+    })
+    .await;
+
+    Ok(())
+}
+
+/// Exports the text of the selected pages to a plain text or Markdown file.
+#[tauri::command]
+async fn export_text_command(
+    app_handle: AppHandle,
+    ordering: Vec<Selector>,
+    options: TextOptions,
+) -> Result<(), String> {
+    let _ = tauri::async_runtime::spawn_blocking(move || {
+        let extension = options.format().extension();
+        let path = app_handle
+            .dialog()
+            .file()
+            .set_file_name(format!("project.{}", extension))
+            .add_filter(extension.to_uppercase(), &[extension])
+            .blocking_save_file();
+
+        let path = match path {
+            Some(FilePath::Path(p)) => p,
+            _ => return,
+        };
+
+        let _ = app_handle.emit("rancher://will-export", ());
+
+        let state = app_handle.state::<Mutex<AppState>>();
+        let Ok(unlocked_state) = state.lock() else {
+            let _ = app_handle.emit("rancher://did-not-export", ());
+            return notify_error(&app_handle, "Couldn't lock the application state");
+        };
+
+        let result = unlocked_state
+            .project
+            .export_text(&ordering, &options)
+            .and_then(|text| Ok(std::fs::write(&path, text)?));
+
+        if let Err(e) = result {
+            notify_error(
+                &app_handle,
+                format!("An error occurred while extracting the text: {}", e).as_str(),
+            );
+            let _ = app_handle.emit("rancher://did-not-export", ());
+            return;
+        }
+
+        let _ = app_handle.emit("rancher://did-export", ());
+    })
+    .await;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn pick_certificate_command(app_handle: AppHandle) -> Option<PathBuf> {
+    tauri::async_runtime::spawn_blocking(move || {
+        match app_handle
+            .dialog()
+            .file()
+            .add_filter("Certificates", &["p12", "pfx"])
+            .blocking_pick_file()
+        {
+            Some(FilePath::Path(path)) => Some(path),
+            _ => None,
+        }
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
 async fn clear_project(app_handle: AppHandle) {
     let cloned_handle = app_handle.clone();
     let confirm = tauri::async_runtime::spawn_blocking(move || {
@@ -313,6 +727,361 @@ async fn preview_command(app_handle: AppHandle, ordering: Selector) -> Result<Pa
     Ok(page)
 }
 
+#[tauri::command]
+async fn default_metadata_command(app_handle: AppHandle) -> Result<DocumentMetadata, ()> {
+    let state = app_handle.state::<Mutex<AppState>>();
+    let Ok(unlocked_state) = state.lock() else {
+        notify_error(&app_handle, "Couldn't lock the application state");
+        return Err(());
+    };
+
+    Ok(unlocked_state.project.default_metadata())
+}
+
+#[tauri::command]
+async fn preflight_command(
+    app_handle: AppHandle,
+    ordering: Vec<Selector>,
+) -> Result<PreflightReport, ()> {
+    let state = app_handle.state::<Mutex<AppState>>();
+    let Ok(unlocked_state) = state.lock() else {
+        notify_error(&app_handle, "Couldn't lock the application state");
+        return Err(());
+    };
+
+    Ok(unlocked_state.project.preflight(&ordering))
+}
+
+#[tauri::command]
+async fn preview_export_command(
+    app_handle: AppHandle,
+    ordering: Vec<Selector>,
+) -> Result<ExportPreview, ()> {
+    let state = app_handle.state::<Mutex<AppState>>();
+    let Ok(unlocked_state) = state.lock() else {
+        notify_error(&app_handle, "Couldn't lock the application state");
+        return Err(());
+    };
+
+    unlocked_state
+        .project
+        .preview_export(&ordering)
+        .map_err(|e| {
+            notify_error(
+                &app_handle,
+                format!("An error occurred while previewing the export: {}", e).as_str(),
+            );
+        })
+}
+
+#[tauri::command]
+async fn render_tile_command(
+    app_handle: AppHandle,
+    ordering: Selector,
+    options: TileOptions,
+) -> Result<Tile, ()> {
+    let state = app_handle.state::<Mutex<AppState>>();
+    let Ok(unlocked_state) = state.lock() else {
+        notify_error(&app_handle, "Couldn't lock the application state");
+        return Err(());
+    };
+
+    unlocked_state
+        .project
+        .render_tile(ordering, &options)
+        .map_err(|e| {
+            notify_error(
+                &app_handle,
+                format!("An error occurred while rendering the page: {}", e).as_str(),
+            );
+        })
+}
+
+#[tauri::command]
+async fn add_redactions_command(
+    app_handle: AppHandle,
+    source_file_index: usize,
+    redactions: Vec<Redaction>,
+) -> Result<(), ()> {
+    let state = app_handle.state::<Mutex<AppState>>();
+    let Ok(mut unlocked_state) = state.lock() else {
+        notify_error(&app_handle, "Couldn't lock the application state");
+        return Err(());
+    };
+
+    unlocked_state
+        .project
+        .add_redactions(source_file_index, redactions)
+        .map_err(|e| {
+            notify_error(
+                &app_handle,
+                format!("An error occurred while marking the redactions: {}", e).as_str(),
+            );
+        })
+}
+
+#[tauri::command]
+async fn clear_redactions_command(
+    app_handle: AppHandle,
+    source_file_index: usize,
+    page_index: Option<usize>,
+) -> Result<(), ()> {
+    let state = app_handle.state::<Mutex<AppState>>();
+    let Ok(mut unlocked_state) = state.lock() else {
+        notify_error(&app_handle, "Couldn't lock the application state");
+        return Err(());
+    };
+
+    unlocked_state
+        .project
+        .clear_redactions(source_file_index, page_index)
+        .map_err(|e| {
+            notify_error(
+                &app_handle,
+                format!("An error occurred while clearing the redactions: {}", e).as_str(),
+            );
+        })
+}
+
+#[tauri::command]
+async fn redact_text_command(
+    app_handle: AppHandle,
+    source_file_index: usize,
+    query: String,
+    color: Option<[f32; 3]>,
+) -> Result<Vec<Redaction>, ()> {
+    let state = app_handle.state::<Mutex<AppState>>();
+    let Ok(mut unlocked_state) = state.lock() else {
+        notify_error(&app_handle, "Couldn't lock the application state");
+        return Err(());
+    };
+
+    unlocked_state
+        .project
+        .redact_text(source_file_index, &query, color.unwrap_or_default())
+        .map_err(|e| {
+            notify_error(
+                &app_handle,
+                format!("An error occurred while searching the text: {}", e).as_str(),
+            );
+        })
+}
+
+/// Every page of a source file but its separator sheets, grouped by the separators found when
+/// importing it.
+#[tauri::command]
+async fn separator_selectors_command(
+    app_handle: AppHandle,
+    source_file_index: usize,
+) -> Result<Vec<Selector>, ()> {
+    let state = app_handle.state::<Mutex<AppState>>();
+    let Ok(unlocked_state) = state.lock() else {
+        notify_error(&app_handle, "Couldn't lock the application state");
+        return Err(());
+    };
+
+    unlocked_state
+        .project
+        .separator_selectors(source_file_index)
+        .map_err(|e| notify_error(&app_handle, e.to_string().as_str()))
+}
+
+/// The ordering with `operation` applied to it, such as interleaving two sources.
+#[tauri::command]
+async fn apply_ordering_command(
+    app_handle: AppHandle,
+    ordering: Vec<Selector>,
+    operation: OrderingOperation,
+) -> Result<Vec<Selector>, ()> {
+    let state = app_handle.state::<Mutex<AppState>>();
+    let Ok(unlocked_state) = state.lock() else {
+        notify_error(&app_handle, "Couldn't lock the application state");
+        return Err(());
+    };
+
+    unlocked_state
+        .project
+        .apply_ordering(&ordering, &operation)
+        .map_err(|e| notify_error(&app_handle, e.to_string().as_str()))
+}
+
+/// The ordering with a blank page inserted at `position`. The blank page may be a new source
+/// file, so the project needs reloading.
+#[tauri::command]
+async fn insert_blank_page_command(
+    app_handle: AppHandle,
+    ordering: Vec<Selector>,
+    position: usize,
+    size: PageSize,
+) -> Result<Vec<Selector>, ()> {
+    let state = app_handle.state::<Mutex<AppState>>();
+    let Ok(mut unlocked_state) = state.lock() else {
+        notify_error(&app_handle, "Couldn't lock the application state");
+        return Err(());
+    };
+
+    unlocked_state
+        .project
+        .insert_blank_page(&ordering, position, &size)
+        .map_err(|e| {
+            notify_error(
+                &app_handle,
+                format!("An error occurred while inserting the blank page: {}", e).as_str(),
+            );
+        })
+}
+
+/// The ordering with a page generated from `template` inserted at `position`. The page is a new
+/// source file, so the project needs reloading.
+#[tauri::command]
+async fn insert_text_page_command(
+    app_handle: AppHandle,
+    ordering: Vec<Selector>,
+    position: usize,
+    template: TextPageTemplate,
+) -> Result<Vec<Selector>, ()> {
+    let state = app_handle.state::<Mutex<AppState>>();
+    let Ok(mut unlocked_state) = state.lock() else {
+        notify_error(&app_handle, "Couldn't lock the application state");
+        return Err(());
+    };
+
+    unlocked_state
+        .project
+        .insert_text_page(&ordering, position, &template)
+        .map_err(|e| {
+            notify_error(
+                &app_handle,
+                format!("An error occurred while generating the page: {}", e).as_str(),
+            );
+        })
+}
+
+/// The ordering with blank pages inserted so every source starts on an odd page.
+#[tauri::command]
+async fn pad_sources_to_odd_pages_command(
+    app_handle: AppHandle,
+    ordering: Vec<Selector>,
+) -> Result<Vec<Selector>, ()> {
+    let state = app_handle.state::<Mutex<AppState>>();
+    let Ok(mut unlocked_state) = state.lock() else {
+        notify_error(&app_handle, "Couldn't lock the application state");
+        return Err(());
+    };
+
+    unlocked_state
+        .project
+        .pad_sources_to_odd_pages(&ordering)
+        .map_err(|e| {
+            notify_error(
+                &app_handle,
+                format!("An error occurred while inserting the blank pages: {}", e).as_str(),
+            );
+        })
+}
+
+/// Pages of a source file picked by a page range expression. Errors in the expression are
+/// returned for the UI to point at, other errors are notified.
+#[tauri::command]
+async fn page_range_selectors_command(
+    app_handle: AppHandle,
+    source_file_index: usize,
+    expression: String,
+) -> Result<Vec<Selector>, Option<PageRangeError>> {
+    let state = app_handle.state::<Mutex<AppState>>();
+    let Ok(unlocked_state) = state.lock() else {
+        notify_error(&app_handle, "Couldn't lock the application state");
+        return Err(None);
+    };
+
+    unlocked_state
+        .project
+        .page_range_selectors(source_file_index, &expression)
+        .map_err(|e| match e.downcast::<PageRangeError>() {
+            Ok(error) => Some(error),
+            Err(e) => {
+                notify_error(&app_handle, e.to_string().as_str());
+                None
+            }
+        })
+}
+
+/// Pages of every source file with the text of `query`, to highlight and select them.
+#[tauri::command]
+async fn search_command(app_handle: AppHandle, query: String) -> Result<Vec<SearchResult>, ()> {
+    let state = app_handle.state::<Mutex<AppState>>();
+    let Ok(unlocked_state) = state.lock() else {
+        notify_error(&app_handle, "Couldn't lock the application state");
+        return Err(());
+    };
+
+    Ok(unlocked_state.project.search(&query))
+}
+
+/// Every page of a source file, grouped by the bookmarks down to `depth`, for the project to
+/// use as sections.
+#[tauri::command]
+async fn outline_selectors_command(
+    app_handle: AppHandle,
+    source_file_index: usize,
+    depth: Option<usize>,
+) -> Result<Vec<Selector>, ()> {
+    let state = app_handle.state::<Mutex<AppState>>();
+    let Ok(unlocked_state) = state.lock() else {
+        notify_error(&app_handle, "Couldn't lock the application state");
+        return Err(());
+    };
+
+    unlocked_state
+        .project
+        .outline_selectors(source_file_index, depth.unwrap_or(1))
+        .map_err(|e| {
+            notify_error(
+                &app_handle,
+                format!("An error occurred while reading the bookmarks: {}", e).as_str(),
+            );
+        })
+}
+
+/// Exports a source file to one file per bookmark down to `depth`, named after the bookmarks.
+#[tauri::command]
+async fn export_outline_command(
+    app_handle: AppHandle,
+    source_file_index: usize,
+    depth: Option<usize>,
+) -> Result<(), String> {
+    let selectors = {
+        let state = app_handle.state::<Mutex<AppState>>();
+        let unlocked_state = state
+            .lock()
+            .map_err(|_| "Couldn't lock the application state".to_string())?;
+
+        unlocked_state
+            .project
+            .outline_selectors(source_file_index, depth.unwrap_or(1))
+            .map_err(|e| format!("An error occurred while reading the bookmarks: {}", e))
+    };
+
+    let result = match selectors {
+        Ok(selectors) if selectors.iter().all(|selector| selector.group().is_none()) => {
+            Err("The document doesn't have any bookmarks".to_string())
+        }
+        Ok(selectors) => {
+            let options = ExportOptions {
+                split: Some(Split::Group),
+                template: Some("{group}".to_string()),
+                ..ExportOptions::default()
+            };
+            export(&app_handle, selectors, options).await
+        }
+        Err(e) => Err(e),
+    };
+    if let Err(e) = &result {
+        notify_error(&app_handle, e);
+    };
+    result
+}
+
 #[tauri::command]
 async fn perform_update_app(app: AppHandle, on_event: Channel<UpdateEvent>) -> Result<(), Error> {
     info!("Performing update...");
@@ -386,7 +1155,7 @@ pub fn run() {
                     let paths = paths.clone();
 
                     tauri::async_runtime::spawn(async move {
-                        let result = add_files(app_handle.clone(), paths).await;
+                        let result = add_files(app_handle.clone(), paths, None).await;
 
                         if let Err(e) = &result {
                             notify_error(&app_handle, e);
@@ -402,7 +1171,7 @@ pub fn run() {
             if id == "open-file" {
                 let app_handle = app.clone();
                 tauri::async_runtime::spawn(async move {
-                    let result = open_files(&app_handle).await;
+                    let result = open_files(&app_handle, None).await;
 
                     if let Err(e) = &result {
                         return notify_error(&app_handle, &e);
@@ -459,9 +1228,29 @@ pub fn run() {
             open_files_command,
             load_project_command,
             export_command,
+            export_images_command,
+            export_archive_command,
+            export_text_command,
+            pick_certificate_command,
             clear_project_command,
             licenses_command,
             preview_command,
+            render_tile_command,
+            add_redactions_command,
+            clear_redactions_command,
+            redact_text_command,
+            apply_ordering_command,
+            insert_blank_page_command,
+            insert_text_page_command,
+            pad_sources_to_odd_pages_command,
+            page_range_selectors_command,
+            search_command,
+            outline_selectors_command,
+            separator_selectors_command,
+            export_outline_command,
+            preview_export_command,
+            preflight_command,
+            default_metadata_command,
             check_update_app,
             perform_update_app,
         ])
@@ -4,15 +4,60 @@ use pdfium_render::prelude::*;
 use rand::distr::Alphanumeric;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::env::consts::{ARCH, OS};
 use std::ffi::OsString;
 use std::fmt::Debug;
-use std::fs::File;
 use std::io::Cursor;
 use std::path::PathBuf;
 use std::sync::mpsc;
 
+mod archive;
+mod blank;
+mod downsample;
+mod metadata;
+mod ordering;
+mod outline;
+mod page_images;
+mod page_range;
+mod preflight;
+mod rasterize;
+mod redact;
+mod report;
+mod sanitize;
+mod search;
+mod separator;
+mod sign;
+mod signature;
+mod split;
+mod text;
+mod text_page;
+mod verify;
+
+pub use archive::ArchiveOptions;
+pub use blank::PageSize;
+pub use downsample::{downsample, DownsampleOptions};
+pub use metadata::DocumentMetadata;
+pub use ordering::OrderingOperation;
+pub use page_images::PageImageOptions;
+pub use page_range::PageRangeError;
+pub use preflight::PreflightReport;
+pub use rasterize::{ColorMode, RasterizeOptions};
+pub use redact::Redaction;
+pub use report::ExportReport;
+pub use sanitize::{sanitize, SanitizeOptions};
+pub use search::SearchResult;
+pub use separator::{Separator, SeparatorOptions};
+pub use sign::{sign, SigningOptions};
+pub use signature::Signature;
+pub use split::{part_path, Split};
+pub use text::TextOptions;
+pub use text_page::TextPageTemplate;
+pub use verify::ExpectedChanges;
+
+use search::PageText;
+use text_page::TextPage;
+
 #[derive(Debug, Clone, Serialize)]
 pub struct Project {
     source_files: Vec<SourceFile>,
@@ -60,10 +105,119 @@ impl Project {
 
         img.write_to(&mut bytes, image::ImageFormat::Jpeg)?;
 
-        Ok(Page::new(bytes.into_inner(), img.dimensions()))
+        let page_id = source_file
+            .document
+            .get_pages()
+            .into_values()
+            .nth(selector.page_index);
+        let metadata = PageMetadata::read(&page, &source_file.document, page_id);
+
+        Ok(Page::new(bytes.into_inner(), img.dimensions(), metadata))
+    }
+
+    /// Renders a page, or a region of it, at an arbitrary resolution. Used by the focused view to
+    /// zoom into a page without being limited by the fixed preview size.
+    pub fn render_tile(&self, selector: Selector, options: &TileOptions) -> Result<Tile> {
+        let source_file = self
+            .source_files
+            .get(selector.source_file_index)
+            .ok_or_else(|| anyhow!("Invalid source_file_index: {}", selector.source_file_index))?;
+
+        let pdfium = pdfium()?;
+        let bytes = source_file.to_bytes()?;
+        let document = pdfium.load_pdf_from_byte_slice(&bytes, None)?;
+
+        let page = document
+            .pages()
+            .iter()
+            .nth(selector.page_index)
+            .ok_or_else(|| anyhow!("Invalid page_index: {}", selector.page_index))?;
+
+        let (page_width, page_height) = (page.width().value, page.height().value);
+        let clip = match options.clip {
+            Some(clip) => clip
+                .clamp(page_width, page_height)
+                .ok_or_else(|| anyhow!("Clip rectangle is outside of the page"))?,
+            None => ClipRect {
+                x: 0.0,
+                y: 0.0,
+                width: page_width,
+                height: page_height,
+            },
+        };
+
+        let scale = options.scale();
+        let (x, y, width, height) = clip.to_pixels(scale);
+        check_render_size(width, height)?;
+
+        let render_config = PdfRenderConfig::new()
+            .scale_page_by_factor(scale)
+            .render_annotations(options.annotations);
+
+        let img = if options.form_fields && document.form().is_some() {
+            // Pdfium cannot render form data and clip at the same time, so the whole page is
+            // rendered and the requested region cropped out of it.
+            check_render_size(
+                (page_width * scale).round() as u32,
+                (page_height * scale).round() as u32,
+            )?;
+            page.render_with_config(&render_config.render_form_data(true))?
+                .as_image()
+                .crop_imm(x, y, width, height)
+        } else {
+            // Only the region is rendered, by moving its corner to the origin of the bitmap.
+            let render_config = render_config
+                .set_fixed_size(width as Pixels, height as Pixels)
+                .translate(
+                    PdfPoints::new(-(x as f32) / scale),
+                    PdfPoints::new(-(y as f32) / scale),
+                )?;
+            page.render_with_config(&render_config)?.as_image()
+        };
+
+        let dimensions = (img.width(), img.height());
+        let mut bytes = Cursor::new(Vec::new());
+        match options.format {
+            TileFormat::Png => img.write_to(&mut bytes, image::ImageFormat::Png)?,
+            TileFormat::Jpeg => img
+                .into_rgb8()
+                .write_to(&mut bytes, image::ImageFormat::Jpeg)?,
+        };
+
+        Ok(Tile {
+            image: bytes.into_inner(),
+            format: options.format,
+            dimensions,
+            clip,
+        })
     }
 
-    pub fn export(&self, selectors: &Vec<Selector>) -> Result<Document> {
+    /// Exports the selected pages in memory and renders the result, so it can be checked before
+    /// being saved.
+    pub fn preview_export(&self, selectors: &[Selector]) -> Result<ExportPreview> {
+        let (mut document, _) = self.export(selectors)?;
+
+        let mut bytes = Vec::new();
+        document.save_to(&mut bytes)?;
+
+        let pages = load_pdf_pages_from_bytes(&document, &bytes, None)?;
+
+        Ok(ExportPreview {
+            page_count: pages.len(),
+            file_size: bytes.len(),
+            pages,
+        })
+    }
+
+    pub fn export(&self, selectors: &[Selector]) -> Result<(Document, ExportReport)> {
+        self.export_with_metadata(selectors, &self.default_metadata())
+    }
+
+    pub fn export_with_metadata(
+        &self,
+        selectors: &[Selector],
+        metadata: &DocumentMetadata,
+    ) -> Result<(Document, ExportReport)> {
         // Basic validations to avoid panics
         if self.source_files.is_empty() {
             return Err(anyhow!("No source files to export"));
@@ -87,6 +241,8 @@ impl Project {
             }
         }
 
+        let report = ExportReport::new(self, selectors);
+
         // Load documents
         let documents = self
             .source_files
@@ -189,6 +345,7 @@ impl Project {
                 source_file_index: source_file_id,
                 page_index,
                 rotation,
+                ..
             } = selector;
             let (object_id, object) = &source_pages[*source_file_id][*page_index];
             if let Ok(dictionary) = object.as_dict() {
@@ -250,6 +407,19 @@ impl Project {
         // Reorder all new Document objects
         document.renumber_objects();
 
+        // Redact the copies of the pages, the source files are left untouched.
+        let page_ids = document.get_pages().into_values().collect::<Vec<_>>();
+        let mut redacted_pages = BTreeSet::new();
+        for (selector, page_id) in selectors.iter().zip(page_ids) {
+            let redactions =
+                self.source_files[selector.source_file_index].page_redactions(selector.page_index);
+            if !redactions.is_empty() && redacted_pages.insert(page_id) {
+                redact::redact_page(&mut document, page_id, &redactions)?;
+            }
+        }
+
+        rasterize::rasterize_pages(&mut document, selectors)?;
+
         // Set any Bookmarks to the First child if they are not set to a page
         document.adjust_zero_pages();
 
@@ -262,6 +432,8 @@ impl Project {
             }
         }
 
+        metadata::write_metadata(&mut document, metadata)?;
+
         document.prune_objects();
         document.compress();
 
@@ -271,7 +443,7 @@ impl Project {
         // if false {
         // document.save("merged.pdf").unwrap();
         // }
-        Ok(document)
+        Ok((document, report))
     }
 }
 
@@ -304,15 +476,28 @@ pub struct Selector {
     source_file_index: usize,
     page_index: usize,
     rotation: Rotation,
+    /// Export the page as a picture of itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rasterize: Option<RasterizeOptions>,
+    /// Name of the group the page belongs to. Consecutive pages of a group are exported to the
+    /// same file when splitting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
 }
 
 impl Selector {
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
     #[cfg(test)]
     fn new(source_file_index: usize, page_index: usize) -> Self {
         Self {
             source_file_index,
             page_index,
             rotation: Rotation::R0,
+            rasterize: None,
+            group: None,
         }
     }
 }
@@ -322,13 +507,16 @@ pub struct Page {
     #[serde(with = "base64")]
     preview_jpg: Vec<u8>,
     dimensions: (u32, u32),
+    #[serde(flatten)]
+    metadata: PageMetadata,
 }
 
 impl Page {
-    fn new(preview_jpg: Vec<u8>, dimensions: (u32, u32)) -> Self {
+    fn new(preview_jpg: Vec<u8>, dimensions: (u32, u32), metadata: PageMetadata) -> Self {
         Self {
             preview_jpg,
             dimensions,
+            metadata,
         }
     }
 
@@ -343,10 +531,253 @@ impl Page {
     }
 }
 
-#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportPreview {
+    pages: Vec<Page>,
+    page_count: usize,
+    /// Size of the exported file, in bytes.
+    file_size: usize,
+}
+
+/// Information about a source page that isn't visible from its thumbnail.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct PageMetadata {
+    /// Width and height of the MediaBox, in points.
+    media_box: (f32, f32),
+    /// Width and height of the CropBox, in points. Defaults to the MediaBox.
+    crop_box: (f32, f32),
+    /// Rotation already applied by the page's `/Rotate` entry, in degrees.
+    rotation: u32,
+    label: Option<String>,
+    annotation_count: usize,
+    form_field_count: usize,
+    has_text: bool,
+    /// The page is only made of images, as produced by most scanners.
+    image_only: bool,
+    /// Approximate number of bytes the page adds to an export, counting shared resources in full.
+    estimated_size: usize,
+}
+
+impl PageMetadata {
+    fn read(page: &PdfPage, document: &Document, page_id: Option<ObjectId>) -> Self {
+        let size = |rect: PdfRect| (rect.width().value, rect.height().value);
+
+        let media_box = page
+            .boundaries()
+            .media()
+            .map(|b| size(b.bounds))
+            .unwrap_or((page.width().value, page.height().value));
+        let crop_box = page
+            .boundaries()
+            .crop()
+            .map(|b| size(b.bounds))
+            .unwrap_or(media_box);
+
+        let (form_field_count, annotation_count) = page
+            .annotations()
+            .iter()
+            .partition::<Vec<_>, _>(|annotation| annotation.as_form_field().is_some());
+
+        let has_text = page.text().map(|text| !text.is_empty()).unwrap_or(false);
+
+        let objects = page.objects();
+        let image_only = !has_text
+            && !objects.is_empty()
+            && objects
+                .iter()
+                .all(|object| object.object_type() == PdfPageObjectType::Image);
+
+        Self {
+            media_box,
+            crop_box,
+            rotation: page
+                .rotation()
+                .map(|r| r.as_degrees() as u32)
+                .unwrap_or_default(),
+            label: page.label().map(str::to_string),
+            annotation_count: annotation_count.len(),
+            form_field_count: form_field_count.len(),
+            has_text,
+            image_only,
+            estimated_size: page_id
+                .map(|id| estimate_page_size(document, id))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Sums the size of every object reachable from the page, except through `/Parent`. Objects
+/// shared between pages (fonts, images) are counted for every page using them.
+fn estimate_page_size(document: &Document, page_id: ObjectId) -> usize {
+    // Rough size of an object's header and dictionary once serialized.
+    const OBJECT_OVERHEAD: usize = 64;
+
+    let mut seen = BTreeSet::new();
+    let mut pending = vec![page_id];
+    let mut total = 0;
+
+    while let Some(id) = pending.pop() {
+        if !seen.insert(id) {
+            continue;
+        }
+        let Ok(object) = document.get_object(id) else {
+            continue;
+        };
+
+        total += OBJECT_OVERHEAD;
+        if let Object::Stream(stream) = object {
+            total += stream.content.len();
+        }
+
+        collect_references(object, &mut pending);
+    }
+
+    total
+}
+
+fn collect_references(object: &Object, references: &mut Vec<ObjectId>) {
+    match object {
+        Object::Reference(id) => references.push(*id),
+        Object::Array(array) => array
+            .iter()
+            .for_each(|object| collect_references(object, references)),
+        Object::Dictionary(dict) | Object::Stream(Stream { dict, .. }) => dict
+            .iter()
+            .filter(|(key, _)| key.as_slice() != b"Parent")
+            .for_each(|(_, object)| collect_references(object, references)),
+        _ => {}
+    }
+}
+
+/// Pdfium bitmaps are allocated in one piece, keep them within a reasonable size.
+const MAX_TILE_RENDER_SIZE: u32 = 16_384;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TileFormat {
+    Png,
+    #[default]
+    Jpeg,
+}
+
+/// A region of a page, in points, with the origin at the top-left corner of the page.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+pub struct ClipRect {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+impl ClipRect {
+    /// The part of the rectangle within a page of `width` by `height` points, if any.
+    fn clamp(self, width: f32, height: f32) -> Option<ClipRect> {
+        let x = self.x.max(0.0);
+        let y = self.y.max(0.0);
+        let clamped = ClipRect {
+            x,
+            y,
+            width: (self.x + self.width).min(width) - x,
+            height: (self.y + self.height).min(height) - y,
+        };
+        (clamped.width > 0.0 && clamped.height > 0.0).then_some(clamped)
+    }
+
+    /// Converts the rectangle to `(x, y, width, height)` pixels, covering every pixel it touches.
+    fn to_pixels(self, scale: f32) -> (u32, u32, u32, u32) {
+        let left = (self.x * scale).floor();
+        let top = (self.y * scale).floor();
+        let right = ((self.x + self.width) * scale).ceil();
+        let bottom = ((self.y + self.height) * scale).ceil();
+        (
+            left as u32,
+            top as u32,
+            (right - left) as u32,
+            (bottom - top) as u32,
+        )
+    }
+}
+
+/// Fails for bitmaps larger than pdfium should allocate.
+fn check_render_size(width: u32, height: u32) -> Result<()> {
+    if width.max(height) > MAX_TILE_RENDER_SIZE {
+        return Err(anyhow!(
+            "Requested resolution is too large: {}x{} pixels",
+            width,
+            height
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TileOptions {
+    /// Resolution in CSS pixels per inch; 72 renders one pixel per point.
+    #[serde(default = "TileOptions::default_dpi")]
+    dpi: f32,
+    #[serde(default = "TileOptions::default_device_pixel_ratio")]
+    device_pixel_ratio: f32,
+    #[serde(default)]
+    clip: Option<ClipRect>,
+    #[serde(default)]
+    format: TileFormat,
+    #[serde(default = "TileOptions::default_true")]
+    annotations: bool,
+    #[serde(default = "TileOptions::default_true")]
+    form_fields: bool,
+}
+
+impl TileOptions {
+    fn default_dpi() -> f32 {
+        72.0
+    }
+
+    fn default_device_pixel_ratio() -> f32 {
+        1.0
+    }
+
+    fn default_true() -> bool {
+        true
+    }
+
+    fn scale(&self) -> f32 {
+        (self.dpi / 72.0) * self.device_pixel_ratio
+    }
+}
+
+impl Default for TileOptions {
+    fn default() -> Self {
+        Self {
+            dpi: Self::default_dpi(),
+            device_pixel_ratio: Self::default_device_pixel_ratio(),
+            clip: None,
+            format: TileFormat::default(),
+            annotations: true,
+            form_fields: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Tile {
+    #[serde(with = "base64")]
+    image: Vec<u8>,
+    format: TileFormat,
+    dimensions: (u32, u32),
+    clip: ClipRect,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
 enum Source {
     PDF(PathBuf),
     Image(PathBuf),
+    /// A single blank page, with its width and height in points.
+    Blank {
+        width: f32,
+        height: f32,
+    },
+    /// A single page generated from text, such as a cover page.
+    Text(TextPage),
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -356,6 +787,15 @@ pub struct SourceFile {
     #[serde(skip_serializing)]
     document: Document,
     pages: Vec<Page>,
+    /// Signatures of the original file, exporting invalidates them.
+    signatures: Vec<Signature>,
+    /// Regions to redact on export.
+    redactions: Vec<Redaction>,
+    /// Separator sheets found when importing a scanned batch.
+    separators: Vec<Separator>,
+    /// Text of each page, for searching.
+    #[serde(skip_serializing)]
+    text: Vec<PageText>,
 }
 
 impl SourceFile {
@@ -366,25 +806,27 @@ impl SourceFile {
             .and_then(|s| s.to_str())
             .map(|s| s.to_lowercase());
 
-        let id: String = rand::rng()
-            .sample_iter(&Alphanumeric)
-            .take(7)
-            .map(char::from)
-            .collect();
+        let id = random_id();
 
         match ext.as_deref() {
             Some("pdf") => {
                 // Current PDF implementation
-                let reader = File::open(path)?;
-                let document = Document::load_from(reader)?;
+                let bytes = std::fs::read(path)?;
+                let document = Document::load_mem(&bytes)?;
                 // random string
                 let pages = load_pdf_pages(&document, sender)?;
+                let signatures = signature::read_signatures(&document, bytes.len());
+                let text = search::index_text(&bytes)?;
 
                 Ok(Self {
                     id,
                     source: Source::PDF(path.clone()),
                     document,
                     pages,
+                    signatures,
+                    redactions: Vec::new(),
+                    separators: Vec::new(),
+                    text,
                 })
             }
             // Image branch (extensions supported by the `image` crate)
@@ -548,6 +990,10 @@ impl SourceFile {
                     source: Source::Image(path.clone()),
                     document: doc,
                     pages,
+                    signatures: Vec::new(),
+                    redactions: Vec::new(),
+                    separators: Vec::new(),
+                    text: Vec::new(),
                 })
             }
             Some(other) => Err(anyhow!("Unsupported file extension: {}", other)),
@@ -555,6 +1001,27 @@ impl SourceFile {
         }
     }
 
+    /// Path of the file the source was opened from, `None` for generated ones.
+    fn path(&self) -> Option<&PathBuf> {
+        match &self.source {
+            Source::PDF(path) => Some(path),
+            Source::Image(path) => Some(path),
+            Source::Blank { .. } | Source::Text(_) => None,
+        }
+    }
+
+    /// Name of the source as shown to the user, its file name for opened files.
+    fn name(&self) -> String {
+        match &self.source {
+            Source::PDF(path) | Source::Image(path) => path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            Source::Blank { .. } => "Blank page".to_string(),
+            Source::Text(page) => page.name(),
+        }
+    }
+
     fn to_bytes(&self) -> Result<Vec<u8>> {
         let mut bytes = Vec::new();
         self.document.clone().save_to(&mut bytes)?;
@@ -562,6 +1029,15 @@ impl SourceFile {
     }
 }
 
+/// A short random identifier for a source file.
+fn random_id() -> String {
+    rand::rng()
+        .sample_iter(&Alphanumeric)
+        .take(7)
+        .map(char::from)
+        .collect()
+}
+
 fn pdfium() -> Result<Pdfium> {
     for path in ["./", "./frameworks/"].iter() {
         let mut prefix = OsString::new();
@@ -582,14 +1058,23 @@ fn pdfium() -> Result<Pdfium> {
 }
 
 fn load_pdf_pages(
-    document: &Document,
+    source: &Document,
     sender: Option<mpsc::Sender<(usize, usize)>>,
 ) -> Result<Vec<Page>> {
-    let pdfium = pdfium()?;
-
     let mut bytes = Vec::new();
-    document.clone().save_to(&mut bytes)?;
-    let document = pdfium.load_pdf_from_byte_slice(&bytes, None)?;
+    source.clone().save_to(&mut bytes)?;
+
+    load_pdf_pages_from_bytes(source, &bytes, sender)
+}
+
+/// Renders the thumbnails of `source`, which must already be serialized into `bytes`.
+fn load_pdf_pages_from_bytes(
+    source: &Document,
+    bytes: &[u8],
+    sender: Option<mpsc::Sender<(usize, usize)>>,
+) -> Result<Vec<Page>> {
+    let pdfium = pdfium()?;
+    let document = pdfium.load_pdf_from_byte_slice(bytes, None)?;
 
     let render_config = PdfRenderConfig::new()
         .set_target_width(300)
@@ -597,6 +1082,8 @@ fn load_pdf_pages(
 
     let mut previews = Vec::new();
 
+    let page_ids = source.get_pages().into_values().collect::<Vec<_>>();
+
     let page_count = document.pages().len() as usize;
     for (index, page) in document.pages().iter().enumerate() {
         let mut bytes = Cursor::new(Vec::new());
@@ -608,7 +1095,9 @@ fn load_pdf_pages(
 
         img.write_to(&mut bytes, image::ImageFormat::Jpeg)?;
 
-        previews.push(Page::new(bytes.into_inner(), img.dimensions()));
+        let metadata = PageMetadata::read(&page, source, page_ids.get(index).copied());
+
+        previews.push(Page::new(bytes.into_inner(), img.dimensions(), metadata));
 
         if let Some(sender) = &sender {
             let _ = sender.send((index + 1, page_count));
@@ -633,16 +1122,61 @@ mod base64 {
 mod test {
     use super::*;
 
+    /// A project of the files at `paths`, for the tests of every module.
+    pub(super) fn project(paths: &[&str]) -> Project {
+        Project {
+            source_files: paths
+                .iter()
+                .map(|path| SourceFile::open(&PathBuf::from(path), None).unwrap())
+                .collect(),
+        }
+    }
+
+    /// The first page of `test/basic.pdf`, exported.
+    pub(super) fn exported_document() -> Document {
+        project(&["test/basic.pdf"])
+            .export(&[Selector::new(0, 0)])
+            .unwrap()
+            .0
+    }
+
     #[test]
     fn test_open() {
         let path = PathBuf::from("test/basic.pdf");
         let source_file = SourceFile::open(&path, None).unwrap();
         assert_eq!(Source::PDF(path), source_file.source);
         assert_eq!(3, source_file.pages.len());
+        assert_eq!(3, source_file.text.len());
         assert_eq!(232, source_file.pages[0].width());
         assert_eq!(300, source_file.pages[0].height());
     }
 
+    #[test]
+    fn test_open_page_metadata() {
+        let path = PathBuf::from("test/basic.pdf");
+        let source_file = SourceFile::open(&path, None).unwrap();
+        let metadata = &source_file.pages[0].metadata;
+        assert_eq!((612.0, 792.0), metadata.media_box);
+        assert_eq!(metadata.media_box, metadata.crop_box);
+        assert_eq!(0, metadata.rotation);
+        assert_eq!(0, metadata.annotation_count);
+        assert_eq!(0, metadata.form_field_count);
+        assert!(metadata.has_text);
+        assert!(!metadata.image_only);
+        assert!(metadata.estimated_size > 0);
+
+        let path = PathBuf::from("test/paysage.pdf");
+        let source_file = SourceFile::open(&path, None).unwrap();
+        assert_eq!((792.0, 612.0), source_file.pages[0].metadata.media_box);
+        assert_eq!(0, source_file.pages[0].metadata.rotation);
+
+        let path = PathBuf::from("test/small-image.jpg");
+        let source_file = SourceFile::open(&path, None).unwrap();
+        let metadata = &source_file.pages[0].metadata;
+        assert!(!metadata.has_text);
+        assert!(metadata.image_only);
+    }
+
     #[test]
     fn test_open_offset() {
         let path = PathBuf::from("test/offset.pdf");
@@ -750,7 +1284,7 @@ mod test {
             Selector::new(2, 2),
         ];
 
-        let document = project.export(&selectors).unwrap();
+        let (document, _) = project.export(&selectors).unwrap();
 
         assert_eq!(3, document.page_iter().count());
 
@@ -775,6 +1309,25 @@ mod test {
         assert_eq!(3, count_streams);
     }
 
+    #[test]
+    fn test_preview_export() {
+        let project = Project {
+            source_files: vec![
+                SourceFile::open(&PathBuf::from("test/basic.pdf"), None).unwrap(),
+                SourceFile::open(&PathBuf::from("test/legal.pdf"), None).unwrap(),
+            ],
+        };
+        let selectors = vec![Selector::new(0, 0), Selector::new(1, 2)];
+
+        let preview = project.preview_export(&selectors).unwrap();
+
+        assert_eq!(2, preview.page_count);
+        assert_eq!(2, preview.pages.len());
+        assert!(preview.file_size > 0);
+        assert_eq!(232, preview.pages[0].width());
+        assert_eq!(182, preview.pages[1].width());
+    }
+
     #[test]
     fn test_export_invalid_source_index_errors() {
         let project = Project {
@@ -808,6 +1361,87 @@ mod test {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_render_tile() {
+        let project = Project {
+            source_files: vec![SourceFile::open(&PathBuf::from("test/basic.pdf"), None).unwrap()],
+        };
+
+        let options = TileOptions {
+            dpi: 144.0,
+            ..TileOptions::default()
+        };
+        let tile = project.render_tile(Selector::new(0, 0), &options).unwrap();
+        assert_eq!((1224, 1584), tile.dimensions);
+
+        let options = TileOptions {
+            device_pixel_ratio: 2.0,
+            clip: Some(ClipRect {
+                x: 100.0,
+                y: 100.0,
+                width: 50.0,
+                height: 25.0,
+            }),
+            format: TileFormat::Png,
+            ..TileOptions::default()
+        };
+        let tile = project.render_tile(Selector::new(0, 0), &options).unwrap();
+        assert_eq!((100, 50), tile.dimensions);
+        assert_eq!(
+            (100, 50),
+            image::load_from_memory(&tile.image)
+                .unwrap()
+                .to_rgb8()
+                .dimensions()
+        );
+
+        // Regions reaching past the page are clamped to it.
+        let options = TileOptions {
+            clip: Some(ClipRect {
+                x: 600.0,
+                y: -8.0,
+                width: 50.0,
+                height: 20.0,
+            }),
+            ..TileOptions::default()
+        };
+        let tile = project.render_tile(Selector::new(0, 0), &options).unwrap();
+        assert_eq!((12, 12), tile.dimensions);
+        assert_eq!(
+            ClipRect {
+                x: 600.0,
+                y: 0.0,
+                width: 12.0,
+                height: 12.0,
+            },
+            tile.clip
+        );
+
+        let options = TileOptions {
+            dpi: 7200.0,
+            ..TileOptions::default()
+        };
+        assert!(project.render_tile(Selector::new(0, 0), &options).is_err());
+    }
+
+    #[test]
+    fn test_clip_rect() {
+        let clip = ClipRect {
+            x: 10.25,
+            y: -5.0,
+            width: 20.0,
+            height: 10.0,
+        };
+
+        let clamped = clip.clamp(25.0, 100.0).unwrap();
+        assert_eq!(
+            (10.25, 0.0, 14.75, 5.0),
+            (clamped.x, clamped.y, clamped.width, clamped.height)
+        );
+        assert_eq!((20, 0, 30, 10), clamped.to_pixels(2.0));
+        assert!(clip.clamp(10.0, 100.0).is_none());
+    }
+
     #[test]
     fn test_rotate() {
         let project = Project {
@@ -819,20 +1453,26 @@ mod test {
                 source_file_index: 0,
                 page_index: 0,
                 rotation: Rotation::R0,
+                rasterize: None,
+                group: None,
             },
             Selector {
                 source_file_index: 0,
                 page_index: 1,
                 rotation: Rotation::R270,
+                rasterize: None,
+                group: None,
             },
             Selector {
                 source_file_index: 0,
                 page_index: 2,
                 rotation: Rotation::R90,
+                rasterize: None,
+                group: None,
             },
         ];
 
-        let document = project.export(&selectors).unwrap();
+        let (document, _) = project.export(&selectors).unwrap();
 
         let pages = document.page_iter().collect::<Vec<_>>();
 
@@ -0,0 +1,511 @@
+use super::metadata::escape_xml;
+use super::page_images::{encode, PageImageFormat};
+use super::rasterize::{default_dpi, default_quality};
+use super::{ColorMode, DocumentMetadata, Project, Selector};
+use anyhow::{anyhow, Result};
+use fax::encoder::Encoder;
+use fax::{Color, VecWriter};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use image::{DynamicImage, GenericImageView};
+use serde::Deserialize;
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Gray level under which a pixel is black in Group 4 images.
+const BLACK_THRESHOLD: u8 = 128;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveFormat {
+    /// A multi-page TIFF, Group 4 compressed in monochrome and Deflate compressed otherwise.
+    #[default]
+    Tiff,
+    /// A comic book archive, a zip of page images with a `ComicInfo.xml`.
+    Cbz,
+}
+
+impl ArchiveFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Tiff => "tiff",
+            ArchiveFormat::Cbz => "cbz",
+        }
+    }
+}
+
+/// How pages are rendered into a single image archive.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ArchiveOptions {
+    #[serde(default)]
+    format: ArchiveFormat,
+    #[serde(default = "default_dpi")]
+    dpi: f32,
+    #[serde(default)]
+    color_mode: ColorMode,
+    /// JPEG quality of the pages of a CBZ, from 1 to 100.
+    #[serde(default = "default_quality")]
+    quality: u8,
+}
+
+impl Default for ArchiveOptions {
+    fn default() -> Self {
+        ArchiveOptions {
+            format: ArchiveFormat::default(),
+            dpi: default_dpi(),
+            color_mode: ColorMode::default(),
+            quality: default_quality(),
+        }
+    }
+}
+
+impl ArchiveOptions {
+    pub fn format(&self) -> ArchiveFormat {
+        self.format
+    }
+}
+
+impl Project {
+    /// Renders the selected pages into a single multi-page TIFF or CBZ file, in the order of the
+    /// selectors.
+    pub fn export_archive(
+        &self,
+        selectors: &[Selector],
+        metadata: &DocumentMetadata,
+        options: &ArchiveOptions,
+    ) -> Result<Vec<u8>> {
+        // Pages are compressed as they are rendered, so only one is ever held uncompressed.
+        match options.format {
+            ArchiveFormat::Tiff => {
+                let mut pages = Vec::new();
+                self.render_export(selectors, options.dpi, options.color_mode, |_, image| {
+                    pages.push(TiffPage::new(&image, options.color_mode)?);
+                    Ok(())
+                })?;
+                write_tiff(&pages, metadata, options.dpi)
+            }
+            ArchiveFormat::Cbz => {
+                let mut pages = Vec::new();
+                self.render_export(selectors, options.dpi, options.color_mode, |_, image| {
+                    pages.push(CbzPage::new(&image, options)?);
+                    Ok(())
+                })?;
+                write_cbz(&pages, metadata)
+            }
+        }
+    }
+}
+
+/// A compressed page image, with the tags describing its pixels.
+struct TiffPage {
+    width: u32,
+    height: u32,
+    /// Bits per sample, for each sample of a pixel.
+    bits_per_sample: Vec<u16>,
+    compression: u16,
+    photometric_interpretation: u16,
+    data: Vec<u8>,
+}
+
+impl TiffPage {
+    fn new(image: &DynamicImage, color_mode: ColorMode) -> Result<Self> {
+        let (width, height) = image.dimensions();
+
+        Ok(match color_mode {
+            ColorMode::Monochrome => TiffPage {
+                width,
+                height,
+                bits_per_sample: vec![1],
+                compression: 4,
+                // Group 4 codes black runs as 1s.
+                photometric_interpretation: 0,
+                data: group4(&image.to_luma8())?,
+            },
+            ColorMode::Grayscale => TiffPage {
+                width,
+                height,
+                bits_per_sample: vec![8],
+                compression: 8,
+                photometric_interpretation: 1,
+                data: deflate(image.to_luma8().as_raw())?,
+            },
+            ColorMode::Color => TiffPage {
+                width,
+                height,
+                bits_per_sample: vec![8; 3],
+                compression: 8,
+                photometric_interpretation: 2,
+                data: deflate(image.to_rgb8().as_raw())?,
+            },
+        })
+    }
+}
+
+/// Value of a TIFF tag.
+enum TagValue {
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(u32, u32),
+}
+
+impl TagValue {
+    /// The field type, the number of values, and their little endian bytes.
+    fn encode(&self) -> (u16, u32, Vec<u8>) {
+        match self {
+            TagValue::Ascii(text) => {
+                let mut bytes = text.replace('\0', "").into_bytes();
+                bytes.push(0);
+                (2, bytes.len() as u32, bytes)
+            }
+            TagValue::Short(values) => (
+                3,
+                values.len() as u32,
+                values
+                    .iter()
+                    .flat_map(|value| value.to_le_bytes())
+                    .collect(),
+            ),
+            TagValue::Long(values) => (
+                4,
+                values.len() as u32,
+                values
+                    .iter()
+                    .flat_map(|value| value.to_le_bytes())
+                    .collect(),
+            ),
+            TagValue::Rational(numerator, denominator) => (
+                5,
+                1,
+                [numerator.to_le_bytes(), denominator.to_le_bytes()].concat(),
+            ),
+        }
+    }
+}
+
+/// Writes a little endian TIFF with an image file directory per page. Each page's data comes
+/// right before its directory.
+fn write_tiff(pages: &[TiffPage], metadata: &DocumentMetadata, dpi: f32) -> Result<Vec<u8>> {
+    let offset = |position: usize| {
+        u32::try_from(position).map_err(|_| anyhow!("The TIFF file would be over 4 GB"))
+    };
+    let resolution = dpi.round().max(1.0) as u32;
+
+    let mut bytes = b"II*\0".to_vec();
+    // Where the offset of the next directory goes, the header's for the first one.
+    let mut next_directory = bytes.len();
+    bytes.extend(0u32.to_le_bytes());
+
+    for (index, page) in pages.iter().enumerate() {
+        let data_offset = offset(bytes.len())?;
+        bytes.extend(&page.data);
+        if bytes.len() % 2 == 1 {
+            bytes.push(0);
+        }
+
+        // Tags must be in ascending order.
+        let mut tags = vec![
+            (254, TagValue::Long(vec![2])),
+            (256, TagValue::Long(vec![page.width])),
+            (257, TagValue::Long(vec![page.height])),
+            (258, TagValue::Short(page.bits_per_sample.clone())),
+            (259, TagValue::Short(vec![page.compression])),
+            (262, TagValue::Short(vec![page.photometric_interpretation])),
+        ];
+        if let Some(title) = metadata.title() {
+            tags.push((269, TagValue::Ascii(title.to_string())));
+        }
+        if let Some(subject) = metadata.subject() {
+            tags.push((270, TagValue::Ascii(subject.to_string())));
+        }
+        tags.extend([
+            (273, TagValue::Long(vec![data_offset])),
+            (
+                277,
+                TagValue::Short(vec![page.bits_per_sample.len() as u16]),
+            ),
+            (278, TagValue::Long(vec![page.height])),
+            (279, TagValue::Long(vec![page.data.len() as u32])),
+            (282, TagValue::Rational(resolution, 1)),
+            (283, TagValue::Rational(resolution, 1)),
+            (296, TagValue::Short(vec![2])),
+            (
+                297,
+                TagValue::Short(vec![
+                    index as u16,
+                    pages.len().min(u16::MAX as usize) as u16,
+                ]),
+            ),
+            (305, TagValue::Ascii(metadata.producer())),
+        ]);
+        if let Some(author) = metadata.author() {
+            tags.push((315, TagValue::Ascii(author.to_string())));
+        }
+
+        let directory_offset = bytes.len();
+        bytes[next_directory..next_directory + 4]
+            .copy_from_slice(&offset(directory_offset)?.to_le_bytes());
+
+        // Values over 4 bytes go after the directory, which ends with the next one's offset.
+        let mut values_offset = directory_offset + 2 + 12 * tags.len() + 4;
+        let mut values = Vec::new();
+
+        bytes.extend((tags.len() as u16).to_le_bytes());
+        for (tag, value) in &tags {
+            let (field_type, count, mut value) = value.encode();
+            bytes.extend(u16::to_le_bytes(*tag));
+            bytes.extend(field_type.to_le_bytes());
+            bytes.extend(count.to_le_bytes());
+
+            if value.len() <= 4 {
+                value.resize(4, 0);
+                bytes.extend(value);
+            } else {
+                bytes.extend(offset(values_offset)?.to_le_bytes());
+                if value.len() % 2 == 1 {
+                    value.push(0);
+                }
+                values_offset += value.len();
+                values.extend(value);
+            }
+        }
+
+        next_directory = bytes.len();
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(values);
+    }
+
+    Ok(bytes)
+}
+
+/// Compresses a page with CCITT Group 4, as fax machines do.
+fn group4(image: &image::GrayImage) -> Result<Vec<u8>> {
+    let width = u16::try_from(image.width())
+        .map_err(|_| anyhow!("The page is too wide for Group 4: {}", image.width()))?;
+
+    let mut encoder = Encoder::new(VecWriter::new());
+    for row in image.rows() {
+        let pels = row.map(|pixel| match pixel.0[0] < BLACK_THRESHOLD {
+            true => Color::Black,
+            false => Color::White,
+        });
+        encoder.encode_line(pels, width)?;
+    }
+    let writer = encoder.finish()?;
+
+    Ok(writer.finish())
+}
+
+fn deflate(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+/// An encoded page image of a CBZ.
+struct CbzPage {
+    width: u32,
+    height: u32,
+    format: PageImageFormat,
+    data: Vec<u8>,
+}
+
+impl CbzPage {
+    fn new(image: &DynamicImage, options: &ArchiveOptions) -> Result<Self> {
+        // JPEG blurs black and white pages, and doesn't make them much smaller.
+        let format = match options.color_mode {
+            ColorMode::Monochrome => PageImageFormat::Png,
+            ColorMode::Color | ColorMode::Grayscale => PageImageFormat::Jpeg,
+        };
+
+        Ok(CbzPage {
+            width: image.width(),
+            height: image.height(),
+            format,
+            data: encode(image, format, options.quality)?,
+        })
+    }
+}
+
+/// Writes a CBZ of the page images, named so they sort in order, with a `ComicInfo.xml` for
+/// readers that show metadata. Images are stored as is since they're already compressed.
+fn write_cbz(pages: &[CbzPage], metadata: &DocumentMetadata) -> Result<Vec<u8>> {
+    let digits = pages.len().to_string().len().max(3);
+    let file_options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (index, page) in pages.iter().enumerate() {
+        let name = format!(
+            "{:0digits$}.{}",
+            index + 1,
+            page.format.extension(),
+            digits = digits
+        );
+        zip.start_file(name, file_options)?;
+        zip.write_all(&page.data)?;
+    }
+
+    zip.start_file("ComicInfo.xml", file_options)?;
+    zip.write_all(comic_info(pages, metadata).as_bytes())?;
+
+    Ok(zip.finish()?.into_inner())
+}
+
+/// The `ComicInfo.xml` of a CBZ, in the format of the ComicRack schema.
+fn comic_info(pages: &[CbzPage], metadata: &DocumentMetadata) -> String {
+    let mut fields = Vec::new();
+    let elements = [
+        ("Title", metadata.title()),
+        ("Writer", metadata.author()),
+        ("Summary", metadata.subject()),
+        ("Tags", metadata.keywords()),
+    ];
+    for (element, value) in elements {
+        if let Some(value) = value {
+            fields.push(format!(
+                "  <{}>{}</{}>",
+                element,
+                escape_xml(value),
+                element
+            ));
+        }
+    }
+    fields.push(format!("  <PageCount>{}</PageCount>", pages.len()));
+
+    let pages = pages
+        .iter()
+        .enumerate()
+        .map(|(index, page)| {
+            format!(
+                "    <Page Image=\"{}\" ImageWidth=\"{}\" ImageHeight=\"{}\" />",
+                index, page.width, page.height
+            )
+        })
+        .collect::<Vec<_>>();
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+        <ComicInfo xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\" \
+        xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n\
+        {}\n  <Pages>\n{}\n  </Pages>\n</ComicInfo>\n",
+        fields.join("\n"),
+        pages.join("\n")
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::project::test::project;
+    use image::GrayImage;
+    use std::io::Read;
+
+    /// Offsets of the image file directories of a TIFF.
+    fn directories(bytes: &[u8]) -> Vec<usize> {
+        let read = |offset: usize| {
+            u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize
+        };
+
+        let mut directories = Vec::new();
+        let mut next = read(4);
+        while next != 0 {
+            directories.push(next);
+            let count = u16::from_le_bytes([bytes[next], bytes[next + 1]]) as usize;
+            next = read(next + 2 + 12 * count);
+        }
+        directories
+    }
+
+    fn gray_page(width: u32, height: u32) -> TiffPage {
+        let image = DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, _| {
+            image::Luma([(x * 10) as u8])
+        }));
+        TiffPage::new(&image, ColorMode::Grayscale).unwrap()
+    }
+
+    #[test]
+    fn test_write_tiff() {
+        let pages = [gray_page(20, 10), gray_page(5, 8), gray_page(7, 7)];
+
+        let bytes = write_tiff(&pages, &DocumentMetadata::default(), 150.0).unwrap();
+
+        assert_eq!(directories(&bytes).len(), 3);
+        let first = image::load_from_memory_with_format(&bytes, image::ImageFormat::Tiff)
+            .unwrap()
+            .into_luma8();
+        assert_eq!(first.dimensions(), (20, 10));
+        assert_eq!(first.get_pixel(3, 5).0, [30]);
+    }
+
+    #[test]
+    fn test_group4() {
+        let image = GrayImage::from_fn(64, 16, |x, y| {
+            image::Luma([if (x / 8 + y / 4) % 2 == 0 { 0 } else { u8::MAX }])
+        });
+
+        let data = group4(&image).unwrap();
+
+        let mut rows = Vec::new();
+        fax::decoder::decode_g4(data.into_iter(), 64, Some(16), |transitions| {
+            rows.push(fax::decoder::pels(transitions, 64).collect::<Vec<_>>());
+        })
+        .unwrap();
+        assert_eq!(rows.len(), 16);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, color) in row.iter().enumerate() {
+                let black = image.get_pixel(x as u32, y as u32).0[0] == 0;
+                assert_eq!(*color == Color::Black, black);
+            }
+        }
+    }
+
+    #[test]
+    fn test_export_tiff() {
+        let project = project(&["test/basic.pdf"]);
+        let selectors = (0..3)
+            .map(|page| Selector::new(0, page))
+            .collect::<Vec<_>>();
+        let options = ArchiveOptions {
+            color_mode: ColorMode::Monochrome,
+            dpi: 72.0,
+            ..Default::default()
+        };
+
+        let bytes = project
+            .export_archive(&selectors, &project.default_metadata(), &options)
+            .unwrap();
+
+        assert_eq!(&bytes[0..4], b"II*\0");
+        assert_eq!(directories(&bytes).len(), 3);
+    }
+
+    #[test]
+    fn test_write_cbz() {
+        let pages = [(30, 40), (40, 30)].map(|(width, height)| {
+            CbzPage::new(
+                &DynamicImage::new_rgb8(width, height),
+                &ArchiveOptions::default(),
+            )
+            .unwrap()
+        });
+        let metadata = serde_json::from_str(r#"{ "title": "Manuals & Guides" }"#).unwrap();
+
+        let bytes = write_cbz(&pages, &metadata).unwrap();
+
+        let mut zip = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(
+            zip.file_names().collect::<Vec<_>>(),
+            vec!["001.jpg", "002.jpg", "ComicInfo.xml"]
+        );
+
+        let mut comic_info = String::new();
+        zip.by_name("ComicInfo.xml")
+            .unwrap()
+            .read_to_string(&mut comic_info)
+            .unwrap();
+        assert!(comic_info.contains("<Title>Manuals &amp; Guides</Title>"));
+        assert!(comic_info.contains("<PageCount>2</PageCount>"));
+        assert!(comic_info.contains("<Page Image=\"1\" ImageWidth=\"40\" ImageHeight=\"30\" />"));
+    }
+}
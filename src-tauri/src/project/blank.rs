@@ -0,0 +1,384 @@
+use super::{load_pdf_pages, random_id, Project, Rotation, Selector, Source, SourceFile};
+use anyhow::{anyhow, Result};
+use lopdf::{Dictionary, Document, Object, Stream};
+use serde::Deserialize;
+
+/// Difference in points under which two page sizes are the same, since sizes converted from
+/// millimeters are rarely round.
+const SIZE_TOLERANCE: f32 = 0.5;
+
+/// Size of an inserted page.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "size", rename_all = "snake_case")]
+pub enum PageSize {
+    /// Width and height in points.
+    Custom { width: f32, height: f32 },
+    /// Same size as the page the inserted one goes with.
+    Neighbor,
+}
+
+impl SourceFile {
+    /// A source made of a single blank page of `width` by `height` points.
+    fn blank(width: f32, height: f32) -> Result<Self> {
+        let mut document = Document::with_version("1.5");
+
+        let content_id = document.add_object(Stream::new(Dictionary::new(), Vec::new()));
+
+        let mut page = Dictionary::new();
+        page.set("Type", "Page");
+        page.set(
+            "MediaBox",
+            vec![
+                Object::Integer(0),
+                Object::Integer(0),
+                Object::Real(width),
+                Object::Real(height),
+            ],
+        );
+        page.set("Resources", Dictionary::new());
+        page.set("Contents", Object::Reference(content_id));
+        let page_id = document.add_object(page);
+
+        let mut pages = Dictionary::new();
+        pages.set("Type", "Pages");
+        pages.set("Kids", vec![Object::Reference(page_id)]);
+        pages.set("Count", 1);
+        let pages_id = document.add_object(pages);
+
+        if let Some(Object::Dictionary(page)) = document.objects.get_mut(&page_id) {
+            page.set("Parent", Object::Reference(pages_id));
+        }
+
+        let mut catalog = Dictionary::new();
+        catalog.set("Type", "Catalog");
+        catalog.set("Pages", Object::Reference(pages_id));
+        let catalog_id = document.add_object(catalog);
+        document.trailer.set("Root", catalog_id);
+
+        let pages = load_pdf_pages(&document, None)?;
+
+        Ok(Self {
+            id: random_id(),
+            source: Source::Blank { width, height },
+            document,
+            pages,
+            signatures: Vec::new(),
+            redactions: Vec::new(),
+            separators: Vec::new(),
+            text: Vec::new(),
+        })
+    }
+
+    fn is_blank(&self) -> bool {
+        matches!(self.source, Source::Blank { .. })
+    }
+}
+
+impl Project {
+    /// The ordering with a blank page inserted at `position`. Blank pages are sources of their
+    /// own, added to the project the first time a size is used.
+    pub fn insert_blank_page(
+        &mut self,
+        ordering: &[Selector],
+        position: usize,
+        size: &PageSize,
+    ) -> Result<Vec<Selector>> {
+        // The blank page goes with the page it follows.
+        let neighbor = neighbor(ordering, position, false)?;
+        let (width, height) = self.resolve_size(size, neighbor)?;
+
+        let selector =
+            self.blank_selector(width, height, neighbor.and_then(|s| s.group.clone()))?;
+
+        let mut ordering = ordering.to_vec();
+        ordering.insert(position, selector);
+        Ok(ordering)
+    }
+
+    /// The ordering with blank pages inserted where a source would start on an even page, so each
+    /// starts on a right-hand page when printed double-sided. Blank pages match the page before
+    /// them.
+    pub fn pad_sources_to_odd_pages(&mut self, ordering: &[Selector]) -> Result<Vec<Selector>> {
+        let sources = ordering
+            .iter()
+            .map(|selector| {
+                let source_file = self.source_files.get(selector.source_file_index)?;
+                (!source_file.is_blank()).then_some(selector.source_file_index)
+            })
+            .collect::<Vec<_>>();
+
+        let mut ordering = ordering.to_vec();
+        // From the end, so positions before the insertions stay valid.
+        for position in padding_positions(&sources).into_iter().rev() {
+            let previous = &ordering[position - 1];
+            let (width, height) = self.displayed_size(previous)?;
+            let selector = self.blank_selector(width, height, previous.group.clone())?;
+            ordering.insert(position, selector);
+        }
+
+        Ok(ordering)
+    }
+
+    /// A selector for a blank page of `width` by `height` points, reusing a blank source of that
+    /// size when the project has one.
+    fn blank_selector(
+        &mut self,
+        width: f32,
+        height: f32,
+        group: Option<String>,
+    ) -> Result<Selector> {
+        let source_file_index = match self.find_blank(width, height) {
+            Some(index) => index,
+            None => {
+                self.source_files.push(SourceFile::blank(width, height)?);
+                self.source_files.len() - 1
+            }
+        };
+
+        Ok(Selector {
+            source_file_index,
+            page_index: 0,
+            rotation: Rotation::R0,
+            rasterize: None,
+            group,
+        })
+    }
+
+    fn find_blank(&self, width: f32, height: f32) -> Option<usize> {
+        self.source_files
+            .iter()
+            .position(|source_file| match source_file.source {
+                Source::Blank {
+                    width: blank_width,
+                    height: blank_height,
+                } => {
+                    (blank_width - width).abs() < SIZE_TOLERANCE
+                        && (blank_height - height).abs() < SIZE_TOLERANCE
+                }
+                _ => false,
+            })
+    }
+
+    /// Width and height in points of an inserted page going with `neighbor`.
+    pub(super) fn resolve_size(
+        &self,
+        size: &PageSize,
+        neighbor: Option<&Selector>,
+    ) -> Result<(f32, f32)> {
+        let (width, height) = match size {
+            PageSize::Custom { width, height } => (*width, *height),
+            PageSize::Neighbor => {
+                let neighbor = neighbor.ok_or_else(|| anyhow!("No page to match the size of"))?;
+                self.displayed_size(neighbor)?
+            }
+        };
+
+        if !(width > 0.0 && height > 0.0) {
+            return Err(anyhow!("Invalid page size: {} × {}", width, height));
+        }
+        Ok((width, height))
+    }
+
+    /// Width and height of a selected page as it is exported, with its crop box and rotation.
+    fn displayed_size(&self, selector: &Selector) -> Result<(f32, f32)> {
+        let page = self
+            .source_files
+            .get(selector.source_file_index)
+            .and_then(|source_file| source_file.pages.get(selector.page_index))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Invalid page {} of source file {}",
+                    selector.page_index,
+                    selector.source_file_index
+                )
+            })?;
+
+        let (width, height) = page.metadata.crop_box;
+        let rotation = selector
+            .rotation
+            .as_rotation()
+            .unwrap_or(page.metadata.rotation);
+
+        Ok(match rotation % 180 {
+            90 => (height, width),
+            _ => (width, height),
+        })
+    }
+}
+
+/// The page a page inserted at `position` goes with: the one after it when `after` is set, the
+/// one before otherwise, or the other one at either end of the ordering.
+pub(super) fn neighbor(
+    ordering: &[Selector],
+    position: usize,
+    after: bool,
+) -> Result<Option<&Selector>> {
+    if position > ordering.len() {
+        return Err(anyhow!(
+            "Invalid position {} for {} pages",
+            position,
+            ordering.len()
+        ));
+    }
+
+    let previous = position.checked_sub(1).map(|index| &ordering[index]);
+    let next = ordering.get(position);
+    Ok(match after {
+        true => next.or(previous),
+        false => previous.or(next),
+    })
+}
+
+/// Positions of the ordering where a blank page goes so every source starts on an odd page.
+/// `sources` has the source of each page of the ordering, `None` for blank pages, which don't
+/// start sources.
+fn padding_positions(sources: &[Option<usize>]) -> Vec<usize> {
+    let mut positions = Vec::new();
+    let mut page_count = 0;
+    let mut previous = None;
+
+    for (position, source) in sources.iter().enumerate() {
+        if let Some(source) = source {
+            // An odd count means the source would start on an even page.
+            if previous.is_some_and(|previous| previous != *source) && page_count % 2 == 1 {
+                positions.push(position);
+                page_count += 1;
+            }
+            previous = Some(*source);
+        }
+        page_count += 1;
+    }
+
+    positions
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::project::{Page, PageMetadata};
+
+    /// A source whose pages are all `width` by `height` points, without thumbnails.
+    fn source_file(source: Source, page_count: usize, size: (f32, f32)) -> SourceFile {
+        let metadata = PageMetadata {
+            media_box: size,
+            crop_box: size,
+            ..Default::default()
+        };
+
+        SourceFile {
+            id: "a".into(),
+            source,
+            document: Document::with_version("1.5"),
+            pages: (0..page_count)
+                .map(|_| Page::new(vec![], (0, 0), metadata.clone()))
+                .collect(),
+            signatures: vec![],
+            redactions: vec![],
+            separators: vec![],
+            text: vec![],
+        }
+    }
+
+    fn project() -> Project {
+        Project {
+            source_files: vec![
+                source_file(Source::PDF("a.pdf".into()), 3, (595.0, 842.0)),
+                source_file(Source::PDF("b.pdf".into()), 2, (612.0, 792.0)),
+                source_file(
+                    Source::Blank {
+                        width: 612.0,
+                        height: 792.0,
+                    },
+                    1,
+                    (612.0, 792.0),
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_padding_positions() {
+        let sources = [Some(0), Some(0), Some(0), Some(1), Some(1), Some(2)];
+        assert_eq!(padding_positions(&sources), vec![3]);
+
+        // Sources already padded don't get more blank pages.
+        let sources = [Some(0), None, Some(1), Some(1), Some(0)];
+        assert_eq!(padding_positions(&sources), Vec::<usize>::new());
+
+        let sources = [Some(0), Some(1), Some(2)];
+        assert_eq!(padding_positions(&sources), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_neighbor() {
+        let ordering = vec![Selector::new(0, 0), Selector::new(1, 0)];
+        let page = |position, after| {
+            neighbor(&ordering, position, after)
+                .unwrap()
+                .map(|selector| selector.source_file_index)
+        };
+
+        assert_eq!(page(1, false), Some(0));
+        assert_eq!(page(1, true), Some(1));
+        assert_eq!(page(0, false), Some(0));
+        assert_eq!(page(2, true), Some(1));
+        assert!(neighbor(&ordering, 3, true).is_err());
+        assert_eq!(neighbor(&[], 0, true).unwrap().map(|_| ()), None);
+    }
+
+    #[test]
+    fn test_displayed_size() {
+        let project = project();
+        let mut selector = Selector::new(0, 1);
+
+        assert_eq!(project.displayed_size(&selector).unwrap(), (595.0, 842.0));
+        selector.rotation = Rotation::R270;
+        assert_eq!(project.displayed_size(&selector).unwrap(), (842.0, 595.0));
+        assert!(project.displayed_size(&Selector::new(0, 3)).is_err());
+    }
+
+    #[test]
+    fn test_find_blank() {
+        let project = project();
+
+        assert_eq!(project.find_blank(612.2, 791.9), Some(2));
+        assert_eq!(project.find_blank(595.0, 842.0), None);
+    }
+
+    #[test]
+    fn test_pad_sources_to_odd_pages() {
+        let mut project = project();
+        let ordering = [(0, 0), (0, 1), (0, 2), (1, 0), (1, 1)]
+            .iter()
+            .map(|(source_file_index, page_index)| Selector::new(*source_file_index, *page_index))
+            .collect::<Vec<_>>();
+
+        let padded = project.pad_sources_to_odd_pages(&ordering).unwrap();
+
+        // The blank page of the project is letter sized, so an A4 one is added.
+        assert_eq!(padded.len(), 6);
+        assert_eq!(padded[3].source_file_index, 3);
+        assert_eq!(
+            project.source_files[3].source,
+            Source::Blank {
+                width: 595.0,
+                height: 842.0
+            }
+        );
+    }
+
+    #[test]
+    fn test_insert_blank_page() {
+        let mut project = project();
+        let ordering = vec![Selector::new(1, 0), Selector::new(1, 1)];
+        let size = PageSize::Neighbor;
+
+        let inserted = project.insert_blank_page(&ordering, 0, &size).unwrap();
+
+        assert_eq!(inserted.len(), 3);
+        assert_eq!(inserted[0].source_file_index, 2);
+        assert_eq!(project.source_files.len(), 3);
+        assert!(project.insert_blank_page(&ordering, 3, &size).is_err());
+        assert!(project.insert_blank_page(&[], 0, &size).is_err());
+    }
+}
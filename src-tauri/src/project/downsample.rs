@@ -0,0 +1,604 @@
+use super::rasterize::{default_dpi, pack_bits};
+use super::redact::{color_components, decoded_content, page_resources, Matrix, MAX_FORM_DEPTH};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, RgbImage};
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::ops::AddAssign;
+
+/// Images are only resampled when this much above their target resolution, resampling close to
+/// it loses quality for little gain.
+const RESAMPLE_THRESHOLD: f32 = 1.5;
+
+/// Share of pixels that must be close to black or white for a grayscale image to be a bilevel
+/// scan, and how close they must be.
+const BILEVEL_RATIO: f32 = 0.99;
+const BILEVEL_TOLERANCE: u8 = 48;
+
+/// How images of an exported document are resampled and recompressed.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DownsampleOptions {
+    /// Highest resolution kept for color images, in dots per inch.
+    #[serde(default = "default_dpi")]
+    color_dpi: f32,
+    #[serde(default = "default_dpi")]
+    grayscale_dpi: f32,
+    #[serde(default = "default_monochrome_dpi")]
+    monochrome_dpi: f32,
+    #[serde(default = "default_jpeg_quality")]
+    jpeg_quality: u8,
+    /// Converts grayscale scans of black and white pages to 1 bit images.
+    #[serde(default)]
+    bilevel: bool,
+}
+
+fn default_monochrome_dpi() -> f32 {
+    300.0
+}
+
+fn default_jpeg_quality() -> u8 {
+    75
+}
+
+impl Default for DownsampleOptions {
+    fn default() -> Self {
+        Self {
+            color_dpi: default_dpi(),
+            grayscale_dpi: default_dpi(),
+            monochrome_dpi: default_monochrome_dpi(),
+            jpeg_quality: default_jpeg_quality(),
+            bilevel: false,
+        }
+    }
+}
+
+/// Images recompressed by [`downsample`], with their encoded size before and after.
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct DownsampleReport {
+    images: usize,
+    before: usize,
+    after: usize,
+}
+
+impl DownsampleReport {
+    pub fn is_empty(&self) -> bool {
+        self.images == 0
+    }
+}
+
+impl AddAssign for DownsampleReport {
+    fn add_assign(&mut self, other: Self) {
+        self.images += other.images;
+        self.before += other.before;
+        self.after += other.after;
+    }
+}
+
+impl Display for DownsampleReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} images recompressed, from {} to {}",
+            self.images,
+            format_size(self.before),
+            format_size(self.after)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImageKind {
+    Color,
+    Grayscale,
+    Monochrome,
+}
+
+/// Resamples the images of a document above the target resolutions, and recompresses them.
+/// Images are only replaced when it makes them smaller, and those the document draws in ways
+/// that can't be measured (annotations, patterns) are left as they are.
+pub fn downsample(document: &mut Document, options: &DownsampleOptions) -> DownsampleReport {
+    let mut report = DownsampleReport::default();
+
+    for (id, extent) in image_extents(document) {
+        let Ok(Object::Stream(image)) = document.get_object(id) else {
+            continue;
+        };
+        let before = image.content.len();
+        let Some(downsampled) = downsample_image(document, image, extent, options) else {
+            continue;
+        };
+        if downsampled.content.len() >= before {
+            continue;
+        }
+
+        report.images += 1;
+        report.before += before;
+        report.after += downsampled.content.len();
+        document.objects.insert(id, Object::Stream(downsampled));
+    }
+
+    report
+}
+
+/// The largest size each image is drawn at on the pages, in points.
+fn image_extents(document: &Document) -> BTreeMap<ObjectId, (f32, f32)> {
+    let mut extents = BTreeMap::new();
+
+    for page_id in document.get_pages().into_values() {
+        let Ok(content) = document.get_page_content(page_id) else {
+            continue;
+        };
+        let resources = page_resources(document, page_id);
+        collect_extents(
+            document,
+            &content,
+            &resources,
+            Matrix::IDENTITY,
+            0,
+            &mut extents,
+        );
+    }
+
+    extents
+}
+
+fn collect_extents(
+    document: &Document,
+    content: &[u8],
+    resources: &Dictionary,
+    mut ctm: Matrix,
+    depth: usize,
+    extents: &mut BTreeMap<ObjectId, (f32, f32)>,
+) {
+    let Ok(content) = Content::decode(content) else {
+        return;
+    };
+
+    let mut saved = Vec::new();
+    for operation in content.operations {
+        match operation.operator.as_str() {
+            "q" => saved.push(ctm),
+            "Q" => ctm = saved.pop().unwrap_or(ctm),
+            "cm" => {
+                if let Some(matrix) = Matrix::from_operands(&operation.operands) {
+                    ctm = matrix.then(&ctm);
+                }
+            }
+            "Do" => {
+                let Some((id, xobject)) = operation
+                    .operands
+                    .first()
+                    .and_then(|name| name.as_name().ok())
+                    .and_then(|name| xobject(document, resources, name))
+                else {
+                    continue;
+                };
+
+                match xobject.dict.get(b"Subtype").and_then(Object::as_name) {
+                    Ok(b"Image") => {
+                        let (width, height) = ctm.scale();
+                        let extent = extents.entry(id).or_insert((0.0, 0.0));
+                        *extent = (extent.0.max(width), extent.1.max(height));
+                    }
+                    Ok(b"Form") if depth < MAX_FORM_DEPTH => {
+                        let matrix = xobject
+                            .dict
+                            .get(b"Matrix")
+                            .and_then(Object::as_array)
+                            .ok()
+                            .and_then(|matrix| Matrix::from_operands(matrix))
+                            .unwrap_or(Matrix::IDENTITY);
+                        let form_resources = xobject
+                            .dict
+                            .get(b"Resources")
+                            .and_then(|resources| document.dereference(resources))
+                            .and_then(|(_, resources)| resources.as_dict())
+                            .unwrap_or(resources);
+                        let Some(form_content) = decoded_content(xobject) else {
+                            continue;
+                        };
+
+                        collect_extents(
+                            document,
+                            &form_content,
+                            form_resources,
+                            matrix.then(&ctm),
+                            depth + 1,
+                            extents,
+                        );
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn xobject<'a>(
+    document: &'a Document,
+    resources: &Dictionary,
+    name: &[u8],
+) -> Option<(ObjectId, &'a Stream)> {
+    let xobjects = resources.get(b"XObject").ok()?;
+    let id = match xobjects {
+        Object::Reference(id) => document.get_dictionary(*id).ok()?.get(name),
+        _ => xobjects.as_dict().ok()?.get(name),
+    }
+    .and_then(Object::as_reference)
+    .ok()?;
+
+    Some((id, document.get_object(id).ok()?.as_stream().ok()?))
+}
+
+/// The image resampled and recompressed, `None` when its encoding isn't supported or there is
+/// nothing to gain.
+fn downsample_image(
+    document: &Document,
+    image: &Stream,
+    (width_points, height_points): (f32, f32),
+    options: &DownsampleOptions,
+) -> Option<Stream> {
+    let dict = &image.dict;
+    let integer = |key: &[u8]| dict.get(key).and_then(Object::as_i64).ok();
+
+    // Masks, decode arrays and color keys all depend on the exact values of the samples.
+    let image_mask = dict
+        .get(b"ImageMask")
+        .and_then(Object::as_bool)
+        .unwrap_or(false);
+    let color_key = dict.get(b"Mask").is_ok_and(|mask| mask.as_array().is_ok());
+    if image_mask || color_key || dict.has(b"Decode") {
+        return None;
+    }
+
+    let width = u32::try_from(integer(b"Width")?).ok()?;
+    let height = u32::try_from(integer(b"Height")?).ok()?;
+    let color_space = dict.get(b"ColorSpace").ok()?;
+    if !is_device_color(document, color_space) {
+        return None;
+    }
+    let components = color_components(document, color_space)?;
+    let bits = integer(b"BitsPerComponent")?;
+
+    let jpeg = image
+        .filters()
+        .is_ok_and(|filters| filters == [b"DCTDecode"]);
+    let pixels = if jpeg {
+        image::load_from_memory_with_format(&image.content, image::ImageFormat::Jpeg).ok()?
+    } else {
+        let data = decoded_content(image)?;
+        match (components, bits) {
+            (1, 1) => DynamicImage::ImageLuma8(unpack_bits(&data, width, height)?),
+            (1, 8) => DynamicImage::ImageLuma8(GrayImage::from_raw(
+                width,
+                height,
+                data.get(..(width * height) as usize)?.to_vec(),
+            )?),
+            (3, 8) => DynamicImage::ImageRgb8(RgbImage::from_raw(
+                width,
+                height,
+                data.get(..(width * height * 3) as usize)?.to_vec(),
+            )?),
+            _ => return None,
+        }
+    };
+    // Decoders convert some color spaces, like CMYK JPEGs to RGB.
+    if pixels.width() != width
+        || pixels.height() != height
+        || pixels.color().channel_count() as usize != components
+    {
+        return None;
+    }
+
+    let kind = if bits == 1 {
+        ImageKind::Monochrome
+    } else if components == 1 {
+        match &pixels {
+            DynamicImage::ImageLuma8(gray) if options.bilevel && is_bilevel(gray) => {
+                ImageKind::Monochrome
+            }
+            _ => ImageKind::Grayscale,
+        }
+    } else {
+        ImageKind::Color
+    };
+    let target_dpi = match kind {
+        ImageKind::Color => options.color_dpi,
+        ImageKind::Grayscale => options.grayscale_dpi,
+        ImageKind::Monochrome => options.monochrome_dpi,
+    };
+
+    // The smallest scale keeping both sides at the target resolution.
+    let scale = (target_dpi * width_points / 72.0 / width as f32)
+        .max(target_dpi * height_points / 72.0 / height as f32);
+    let resample = scale > 0.0 && scale * RESAMPLE_THRESHOLD < 1.0;
+
+    // Encoding a JPEG again at the same size only loses quality.
+    if jpeg && !resample && kind != ImageKind::Monochrome {
+        return None;
+    }
+
+    let pixels = if resample {
+        let new_width = ((width as f32 * scale).round() as u32).max(1);
+        let new_height = ((height as f32 * scale).round() as u32).max(1);
+        pixels.resize_exact(new_width, new_height, FilterType::CatmullRom)
+    } else {
+        pixels
+    };
+
+    let mut dict = dict.clone();
+    dict.set("Width", pixels.width());
+    dict.set("Height", pixels.height());
+    dict.remove(b"DecodeParms");
+
+    match kind {
+        ImageKind::Monochrome => {
+            let gray = pixels.into_luma8();
+            dict.set("BitsPerComponent", 1);
+            dict.remove(b"Filter");
+
+            let mut stream = Stream::new(dict, pack_bits(&gray));
+            stream.compress().ok()?;
+            Some(stream)
+        }
+        ImageKind::Color | ImageKind::Grayscale => {
+            let pixels = match kind {
+                ImageKind::Color => DynamicImage::ImageRgb8(pixels.into_rgb8()),
+                _ => DynamicImage::ImageLuma8(pixels.into_luma8()),
+            };
+            let mut bytes = Vec::new();
+            JpegEncoder::new_with_quality(&mut bytes, options.jpeg_quality)
+                .encode_image(&pixels)
+                .ok()?;
+
+            dict.set("BitsPerComponent", 8);
+            dict.set("Filter", "DCTDecode");
+            Some(Stream::new(dict, bytes))
+        }
+    }
+}
+
+/// Whether samples of the color space are plain gray or RGB intensities, which resampling and
+/// JPEG preserve.
+fn is_device_color(document: &Document, color_space: &Object) -> bool {
+    let Ok((_, color_space)) = document.dereference(color_space) else {
+        return false;
+    };
+
+    match color_space {
+        Object::Name(name) => matches!(
+            name.as_slice(),
+            b"DeviceGray" | b"DeviceRGB" | b"CalGray" | b"CalRGB"
+        ),
+        Object::Array(array) => match array.first().and_then(|kind| kind.as_name().ok()) {
+            Some(b"CalGray" | b"CalRGB") => true,
+            // ICC profiles of CMYK or other color spaces aren't converted.
+            Some(b"ICCBased") => matches!(color_components(document, color_space), Some(1 | 3)),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn is_bilevel(image: &GrayImage) -> bool {
+    let extremes = image
+        .pixels()
+        .filter(|pixel| pixel.0[0] < BILEVEL_TOLERANCE || pixel.0[0] > u8::MAX - BILEVEL_TOLERANCE)
+        .count();
+
+    extremes as f32 >= image.len() as f32 * BILEVEL_RATIO
+}
+
+/// Expands 1 bit samples, rows padded to a byte, to black and white pixels.
+fn unpack_bits(data: &[u8], width: u32, height: u32) -> Option<GrayImage> {
+    let row_bytes = (width as usize).div_ceil(8);
+    if data.len() < row_bytes * height as usize {
+        return None;
+    }
+
+    Some(GrayImage::from_fn(width, height, |x, y| {
+        let byte = data[y as usize * row_bytes + x as usize / 8];
+        let white = byte & (0x80 >> (x % 8)) != 0;
+        image::Luma([if white { u8::MAX } else { 0 }])
+    }))
+}
+
+fn format_size(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["bytes", "KB", "MB", "GB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} bytes", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::project::{Project, Selector, SourceFile};
+    use lopdf::dictionary;
+    use std::path::PathBuf;
+
+    fn page_image(document: &Document) -> &Stream {
+        document
+            .objects
+            .values()
+            .filter_map(|object| object.as_stream().ok())
+            .find(|stream| {
+                stream.dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Image")
+                    && stream.dict.has(b"ColorSpace")
+                    && !stream.dict.has(b"SMask")
+            })
+            .unwrap()
+    }
+
+    /// A one inch square page drawing a grayscale image.
+    fn gray_document(width: u32, height: u32, pixels: Vec<u8>) -> Document {
+        image_document(
+            Document::with_version("1.5"),
+            Stream::new(
+                dictionary! {
+                    "Type" => "XObject",
+                    "Subtype" => "Image",
+                    "Width" => width,
+                    "Height" => height,
+                    "ColorSpace" => "DeviceGray",
+                    "BitsPerComponent" => 8,
+                },
+                pixels,
+            ),
+        )
+    }
+
+    /// Adds a one inch square page drawing `image` to `document`.
+    fn image_document(mut document: Document, image: Stream) -> Document {
+        let image_id = document.add_object(image);
+        let content_id = document.add_object(Stream::new(
+            dictionary! {},
+            b"q 72 0 0 72 0 0 cm /Im0 Do Q".to_vec(),
+        ));
+        let pages_id = document.new_object_id();
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 72.into(), 72.into()],
+            "Contents" => content_id,
+            "Resources" => dictionary! {
+                "XObject" => dictionary! { "Im0" => image_id },
+            },
+        });
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+
+        document
+    }
+
+    #[test]
+    fn test_downsample() {
+        let project = Project {
+            source_files: vec![
+                SourceFile::open(&PathBuf::from("test/large-image.jpg"), None).unwrap(),
+            ],
+        };
+        let (mut document, _) = project.export(&[Selector::new(0, 0)]).unwrap();
+
+        let report = downsample(&mut document, &DownsampleOptions::default());
+
+        assert_eq!(report.images, 1);
+        assert!(report.after < report.before);
+
+        let image = page_image(&document);
+        let width = image.dict.get(b"Width").unwrap().as_i64().unwrap();
+        assert!(width < 3000);
+        assert_eq!(
+            image.dict.get(b"Filter").unwrap().as_name().unwrap(),
+            b"DCTDecode"
+        );
+    }
+
+    #[test]
+    fn test_downsample_below_target() {
+        // 50 dpi, under every target.
+        let mut document = gray_document(50, 50, (0..2500).map(|i| (i % 251) as u8).collect());
+
+        downsample(&mut document, &DownsampleOptions::default());
+
+        let image = page_image(&document);
+        assert_eq!(image.dict.get(b"Width").unwrap().as_i64().unwrap(), 50);
+        assert_eq!(image.dict.get(b"Height").unwrap().as_i64().unwrap(), 50);
+    }
+
+    #[test]
+    fn test_downsample_bilevel() {
+        // Black and white stripes, scanned at 400 dpi.
+        let pixels = (0..400 * 400)
+            .map(|i| if (i / 400) % 20 < 10 { 0 } else { 255 })
+            .collect();
+        let mut document = gray_document(400, 400, pixels);
+
+        let report = downsample(
+            &mut document,
+            &DownsampleOptions {
+                bilevel: true,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(report.images, 1);
+        let image = page_image(&document);
+        assert_eq!(
+            image
+                .dict
+                .get(b"BitsPerComponent")
+                .unwrap()
+                .as_i64()
+                .unwrap(),
+            1
+        );
+        assert_eq!(image.dict.get(b"Width").unwrap().as_i64().unwrap(), 400);
+        assert_eq!(image.decompressed_content().unwrap().len(), 50 * 400);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 bytes");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(200 * 1024 * 1024), "200.0 MB");
+    }
+
+    #[test]
+    fn test_downsample_skips_unsupported_icc_profiles() {
+        let mut jpeg = Vec::new();
+        JpegEncoder::new(&mut jpeg)
+            .encode_image(&RgbImage::from_pixel(600, 600, image::Rgb([200, 40, 40])))
+            .unwrap();
+
+        // A CMYK profile, and a gray one whose JPEG decodes to RGB.
+        for components in [4, 1] {
+            let mut document = Document::with_version("1.5");
+            let profile_id =
+                document.add_object(Stream::new(dictionary! { "N" => components }, Vec::new()));
+            let image = Stream::new(
+                dictionary! {
+                    "Type" => "XObject",
+                    "Subtype" => "Image",
+                    "Width" => 600,
+                    "Height" => 600,
+                    "ColorSpace" => vec!["ICCBased".into(), profile_id.into()],
+                    "BitsPerComponent" => 8,
+                    "Filter" => "DCTDecode",
+                },
+                jpeg.clone(),
+            );
+            let mut document = image_document(document, image);
+
+            let report = downsample(&mut document, &DownsampleOptions::default());
+
+            assert!(report.is_empty());
+        }
+    }
+}
@@ -0,0 +1,333 @@
+use super::{Project, SourceFile};
+use anyhow::Result;
+use lopdf::{decode_text_string, dictionary, text_string, Dictionary, Document, Object, Stream};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Document information written to both the `/Info` dictionary and the XMP metadata of exported
+/// files. Empty fields are left out.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DocumentMetadata {
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    author: Option<String>,
+    #[serde(default)]
+    subject: Option<String>,
+    #[serde(default)]
+    keywords: Option<String>,
+    /// Application that created the original document.
+    #[serde(default)]
+    creator: Option<String>,
+    /// Application that produced the PDF, defaults to PDF Rancher.
+    #[serde(default)]
+    producer: Option<String>,
+}
+
+impl DocumentMetadata {
+    pub(super) fn title(&self) -> Option<&str> {
+        non_empty(&self.title)
+    }
+
+    pub(super) fn author(&self) -> Option<&str> {
+        non_empty(&self.author)
+    }
+
+    pub(super) fn subject(&self) -> Option<&str> {
+        non_empty(&self.subject)
+    }
+
+    pub(super) fn keywords(&self) -> Option<&str> {
+        non_empty(&self.keywords)
+    }
+
+    /// The producer, PDF Rancher when none is set.
+    pub(super) fn producer(&self) -> String {
+        non_empty(&self.producer)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("PDF Rancher {}", env!("CARGO_PKG_VERSION")))
+    }
+}
+
+impl Project {
+    /// Metadata of the first source file, used as the default for exports. The title falls back
+    /// to the file name when the document doesn't have one.
+    pub fn default_metadata(&self) -> DocumentMetadata {
+        let Some(source_file) = self.source_files.first() else {
+            return DocumentMetadata::default();
+        };
+
+        let file_stem = Path::new(&source_file.name())
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned());
+
+        DocumentMetadata {
+            title: source_file.info(b"Title").or(file_stem),
+            author: source_file.info(b"Author"),
+            subject: source_file.info(b"Subject"),
+            keywords: source_file.info(b"Keywords"),
+            creator: source_file.info(b"Creator"),
+            producer: None,
+        }
+    }
+}
+
+impl SourceFile {
+    /// A text entry of the source's `/Info` dictionary, `None` when missing or blank.
+    pub(super) fn info(&self, key: &[u8]) -> Option<String> {
+        let document = &self.document;
+        let info = document
+            .trailer
+            .get(b"Info")
+            .and_then(|info| document.dereference(info))
+            .and_then(|(_, info)| info.as_dict())
+            .ok()?;
+
+        text(info, key)
+    }
+}
+
+fn text(dict: &Dictionary, key: &[u8]) -> Option<String> {
+    dict.get(key)
+        .and_then(decode_text_string)
+        .ok()
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+/// Writes `metadata` to the `/Info` dictionary and the catalog `/Metadata` stream, replacing any
+/// metadata copied from the sources.
+pub(super) fn write_metadata(document: &mut Document, metadata: &DocumentMetadata) -> Result<()> {
+    let timestamp = now()?;
+    let producer = metadata.producer();
+
+    let mut info = dictionary! {
+        "Producer" => text_string(&producer),
+        "CreationDate" => Object::string_literal(pdf_date(timestamp)),
+        "ModDate" => Object::string_literal(pdf_date(timestamp)),
+    };
+    let fields = [
+        ("Title", &metadata.title),
+        ("Author", &metadata.author),
+        ("Subject", &metadata.subject),
+        ("Keywords", &metadata.keywords),
+        ("Creator", &metadata.creator),
+    ];
+    for (key, value) in fields {
+        if let Some(value) = non_empty(value) {
+            info.set(key, text_string(value));
+        }
+    }
+    let info_id = document.add_object(info);
+    document.trailer.set("Info", info_id);
+
+    // XMP must stay readable by tools that don't decode PDF filters.
+    let xmp = Stream::new(
+        dictionary! {
+            "Type" => "Metadata",
+            "Subtype" => "XML",
+        },
+        xmp_packet(metadata, &producer, timestamp).into_bytes(),
+    )
+    .with_compression(false);
+    let xmp_id = document.add_object(xmp);
+
+    let catalog_id = document.trailer.get(b"Root")?.as_reference()?;
+    document
+        .get_dictionary_mut(catalog_id)?
+        .set("Metadata", xmp_id);
+
+    Ok(())
+}
+
+fn xmp_packet(metadata: &DocumentMetadata, producer: &str, timestamp: u64) -> String {
+    let date = xmp_date(timestamp);
+    let mut properties = Vec::new();
+
+    if let Some(title) = non_empty(&metadata.title) {
+        properties.push(format!(
+            "<dc:title><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:title>",
+            escape_xml(title)
+        ));
+    }
+    if let Some(author) = non_empty(&metadata.author) {
+        properties.push(format!(
+            "<dc:creator><rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq></dc:creator>",
+            escape_xml(author)
+        ));
+    }
+    if let Some(subject) = non_empty(&metadata.subject) {
+        properties.push(format!(
+            "<dc:description><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:description>",
+            escape_xml(subject)
+        ));
+    }
+    if let Some(keywords) = non_empty(&metadata.keywords) {
+        properties.push(format!(
+            "<pdf:Keywords>{}</pdf:Keywords>",
+            escape_xml(keywords)
+        ));
+    }
+    if let Some(creator) = non_empty(&metadata.creator) {
+        properties.push(format!(
+            "<xmp:CreatorTool>{}</xmp:CreatorTool>",
+            escape_xml(creator)
+        ));
+    }
+    properties.push(format!(
+        "<pdf:Producer>{}</pdf:Producer>",
+        escape_xml(producer)
+    ));
+    properties.push(format!("<xmp:CreateDate>{}</xmp:CreateDate>", date));
+    properties.push(format!("<xmp:ModifyDate>{}</xmp:ModifyDate>", date));
+    properties.push(format!("<xmp:MetadataDate>{}</xmp:MetadataDate>", date));
+
+    format!(
+        concat!(
+            "<?xpacket begin=\"\u{FEFF}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n",
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n",
+            "<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n",
+            "<rdf:Description rdf:about=\"\"",
+            " xmlns:dc=\"http://purl.org/dc/elements/1.1/\"",
+            " xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\"",
+            " xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\">\n",
+            "{}\n",
+            "</rdf:Description>\n",
+            "</rdf:RDF>\n",
+            "</x:xmpmeta>\n",
+            "<?xpacket end=\"w\"?>"
+        ),
+        properties.join("\n")
+    )
+}
+
+pub(super) fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Current UNIX timestamp, in seconds.
+pub(super) fn now() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+/// Formats a UNIX timestamp as an XMP date in UTC.
+fn xmp_date(timestamp: u64) -> String {
+    let (year, month, day) = civil_from_days((timestamp / 86_400) as i64);
+    let seconds = timestamp % 86_400;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+/// Formats a UNIX timestamp as a PDF date in UTC.
+pub(super) fn pdf_date(timestamp: u64) -> String {
+    let (year, month, day) = civil_from_days((timestamp / 86_400) as i64);
+    let seconds = timestamp % 86_400;
+
+    format!(
+        "D:{:04}{:02}{:02}{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+/// Converts days since 1970-01-01 to a `(year, month, day)` date, from Howard Hinnant's
+/// `civil_from_days`.
+pub(super) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::project::test::project;
+    use crate::project::Selector;
+
+    fn info(document: &Document) -> &Dictionary {
+        let info = document.trailer.get(b"Info").unwrap();
+        document.dereference(info).unwrap().1.as_dict().unwrap()
+    }
+
+    #[test]
+    fn test_default_metadata() {
+        let project = project(&["test/legal.pdf", "test/basic.pdf"]);
+
+        let metadata = project.default_metadata();
+
+        assert_eq!(metadata.title.as_deref(), Some("legal"));
+        assert_eq!(metadata.author, None);
+        assert_eq!(metadata.producer, None);
+    }
+
+    #[test]
+    fn test_export_metadata() {
+        let project = project(&["test/basic.pdf"]);
+        let metadata = DocumentMetadata {
+            title: Some("Quarterly Report".to_string()),
+            author: Some("Zoë <Finance>".to_string()),
+            keywords: Some(" ".to_string()),
+            ..Default::default()
+        };
+
+        let (document, _) = project
+            .export_with_metadata(&[Selector::new(0, 0)], &metadata)
+            .unwrap();
+
+        let info = info(&document);
+        assert_eq!(text(info, b"Title").as_deref(), Some("Quarterly Report"));
+        assert_eq!(text(info, b"Author").as_deref(), Some("Zoë <Finance>"));
+        assert!(info.get(b"Keywords").is_err());
+        assert!(text(info, b"Producer").unwrap().starts_with("PDF Rancher"));
+        assert!(text(info, b"CreationDate").unwrap().starts_with("D:"));
+
+        let catalog = document.catalog().unwrap();
+        let xmp_id = catalog.get(b"Metadata").unwrap().as_reference().unwrap();
+        let xmp = document.get_object(xmp_id).unwrap().as_stream().unwrap();
+        assert!(xmp.dict.get(b"Filter").is_err());
+
+        let xmp = String::from_utf8(xmp.content.clone()).unwrap();
+        assert!(xmp.contains("<rdf:li xml:lang=\"x-default\">Quarterly Report</rdf:li>"));
+        assert!(xmp.contains("<rdf:li>Zoë &lt;Finance&gt;</rdf:li>"));
+        assert!(!xmp.contains("pdf:Keywords"));
+    }
+
+    #[test]
+    fn test_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+        assert_eq!(pdf_date(1_704_110_400), "D:20240101120000Z");
+        assert_eq!(xmp_date(1_704_110_400), "2024-01-01T12:00:00Z");
+    }
+}
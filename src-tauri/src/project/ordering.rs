@@ -0,0 +1,335 @@
+use super::metadata::pdf_date;
+use super::{Project, Selector, SourceFile};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::ops::Range;
+use std::time::UNIX_EPOCH;
+
+/// A change to the order of the project's pages made in one step.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum OrderingOperation {
+    /// Alternates the pages of two sources, where the first one's pages start. Duplex scans
+    /// made as one file of fronts and one of backs, the backs usually being in reverse.
+    Interleave {
+        first: usize,
+        second: usize,
+        #[serde(default)]
+        reverse_second: bool,
+    },
+    /// Reverses the pages at these positions of the ordering.
+    Reverse { pages: Range<usize> },
+    /// Groups the pages by source, in the order of the sources' names, dates or titles.
+    SortSources {
+        by: SortKey,
+        #[serde(default)]
+        descending: bool,
+    },
+    /// Repeats the whole ordering, for printing several collated copies.
+    Collate { copies: usize },
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    /// File name, with numbers in names compared by value.
+    Name,
+    /// Creation date of the document, or modification date of the file when it has none.
+    Date,
+    /// Title of the document, or file name when it has none.
+    Title,
+}
+
+impl Project {
+    /// The ordering once `operation` is applied to it.
+    pub fn apply_ordering(
+        &self,
+        ordering: &[Selector],
+        operation: &OrderingOperation,
+    ) -> Result<Vec<Selector>> {
+        match operation {
+            OrderingOperation::Interleave {
+                first,
+                second,
+                reverse_second,
+            } => interleave(ordering, *first, *second, *reverse_second),
+            OrderingOperation::Reverse { pages } => {
+                if pages.start > pages.end || pages.end > ordering.len() {
+                    return Err(anyhow!(
+                        "Invalid page range {}..{} for {} pages",
+                        pages.start,
+                        pages.end,
+                        ordering.len()
+                    ));
+                }
+                let mut ordering = ordering.to_vec();
+                ordering[pages.clone()].reverse();
+                Ok(ordering)
+            }
+            OrderingOperation::SortSources { by, descending } => {
+                Ok(self.sort_sources(ordering, *by, *descending))
+            }
+            OrderingOperation::Collate { copies } => {
+                if *copies == 0 {
+                    return Err(anyhow!("At least one copy is needed"));
+                }
+                Ok((0..*copies).flat_map(|_| ordering.to_vec()).collect())
+            }
+        }
+    }
+
+    fn sort_sources(&self, ordering: &[Selector], by: SortKey, descending: bool) -> Vec<Selector> {
+        let mut source_file_indices = (0..self.source_files.len()).collect::<Vec<_>>();
+        let keys = self
+            .source_files
+            .iter()
+            .map(|source_file| sort_key(source_file, by))
+            .collect::<Vec<_>>();
+
+        source_file_indices.sort_by(|a, b| {
+            let ordering = match (&keys[*a], &keys[*b]) {
+                (Some(a), Some(b)) => natural_cmp(a, b),
+                // Sources without a date go last either way.
+                (Some(_), None) => return Ordering::Less,
+                (None, Some(_)) => return Ordering::Greater,
+                (None, None) => return Ordering::Equal,
+            };
+            match descending {
+                true => ordering.reverse(),
+                false => ordering,
+            }
+        });
+
+        source_file_indices
+            .into_iter()
+            .flat_map(|source_file_index| {
+                ordering
+                    .iter()
+                    .filter(move |selector| selector.source_file_index == source_file_index)
+                    .cloned()
+            })
+            .collect()
+    }
+}
+
+/// The pages of `first` and `second` alternated, in place of the pages of `first`. Pages of the
+/// longer source are left at the end.
+fn interleave(
+    ordering: &[Selector],
+    first: usize,
+    second: usize,
+    reverse_second: bool,
+) -> Result<Vec<Selector>> {
+    if first == second {
+        return Err(anyhow!("Can't interleave a source with itself"));
+    }
+
+    let pages = |source_file_index: usize| {
+        ordering
+            .iter()
+            .filter(|selector| selector.source_file_index == source_file_index)
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let fronts = pages(first);
+    let mut backs = pages(second);
+    if fronts.is_empty() || backs.is_empty() {
+        return Err(anyhow!("Both sources must have pages in the ordering"));
+    }
+    if reverse_second {
+        backs.reverse();
+    }
+
+    let mut interleaved = Vec::with_capacity(fronts.len() + backs.len());
+    let (mut fronts, mut backs) = (fronts.into_iter(), backs.into_iter());
+    loop {
+        match (fronts.next(), backs.next()) {
+            (None, None) => break,
+            (front, back) => interleaved.extend(front.into_iter().chain(back)),
+        }
+    }
+
+    let start = ordering
+        .iter()
+        .position(|selector| selector.source_file_index == first)
+        .unwrap_or_default();
+    let mut result = ordering
+        .iter()
+        .filter(|selector| ![first, second].contains(&selector.source_file_index))
+        .cloned()
+        .collect::<Vec<_>>();
+    // Pages of other sources before the first page of `first` stay before it.
+    let before = ordering[..start]
+        .iter()
+        .filter(|selector| selector.source_file_index != second)
+        .count();
+    result.splice(before..before, interleaved);
+
+    Ok(result)
+}
+
+fn sort_key(source_file: &SourceFile, by: SortKey) -> Option<String> {
+    match by {
+        SortKey::Name => Some(source_file.name().to_lowercase()),
+        SortKey::Title => Some(
+            source_file
+                .info(b"Title")
+                .unwrap_or_else(|| source_file.name())
+                .to_lowercase(),
+        ),
+        SortKey::Date => source_file
+            .info(b"CreationDate")
+            .and_then(|date| date_digits(&date))
+            .or_else(|| {
+                let modified = std::fs::metadata(source_file.path()?)
+                    .ok()?
+                    .modified()
+                    .ok()?;
+                let timestamp = modified.duration_since(UNIX_EPOCH).ok()?.as_secs();
+                date_digits(&pdf_date(timestamp))
+            }),
+    }
+}
+
+/// The `YYYYMMDDHHmmSS` digits of a PDF date, padded when the date is partial, so dates compare
+/// as strings. Time zones are ignored.
+fn date_digits(date: &str) -> Option<String> {
+    let digits = date
+        .trim_start_matches("D:")
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .take(14)
+        .collect::<String>();
+
+    (digits.len() >= 4).then(|| format!("{:0<14}", digits))
+}
+
+/// Compares strings with their runs of digits compared by value, so `scan2` comes before
+/// `scan10`.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(digit);
+                    }
+                    digits.trim_start_matches('0').to_string()
+                };
+                let (x, y) = (number(&mut a), number(&mut b));
+                let ordering = x.len().cmp(&y.len()).then_with(|| x.cmp(&y));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::project::test::project;
+
+    fn pages(ordering: &[Selector]) -> Vec<(usize, usize)> {
+        ordering
+            .iter()
+            .map(|selector| (selector.source_file_index, selector.page_index))
+            .collect()
+    }
+
+    fn selectors(pages: &[(usize, usize)]) -> Vec<Selector> {
+        pages
+            .iter()
+            .map(|(source_file_index, page_index)| Selector::new(*source_file_index, *page_index))
+            .collect()
+    }
+
+    #[test]
+    fn test_interleave() {
+        let ordering = selectors(&[(2, 0), (0, 0), (0, 1), (0, 2), (1, 0), (1, 1), (1, 2)]);
+
+        assert_eq!(
+            pages(&interleave(&ordering, 0, 1, true).unwrap()),
+            vec![(2, 0), (0, 0), (1, 2), (0, 1), (1, 1), (0, 2), (1, 0)]
+        );
+        assert_eq!(
+            pages(&interleave(&ordering[..6], 0, 1, false).unwrap()),
+            vec![(2, 0), (0, 0), (1, 0), (0, 1), (1, 1), (0, 2)]
+        );
+        assert!(interleave(&ordering, 0, 0, false).is_err());
+        assert!(interleave(&ordering, 0, 3, false).is_err());
+    }
+
+    #[test]
+    fn test_reverse_and_collate() {
+        let project = Project {
+            source_files: vec![],
+        };
+        let ordering = selectors(&[(0, 0), (0, 1), (0, 2), (0, 3)]);
+
+        let reversed = project
+            .apply_ordering(&ordering, &OrderingOperation::Reverse { pages: 1..4 })
+            .unwrap();
+        assert_eq!(pages(&reversed), vec![(0, 0), (0, 3), (0, 2), (0, 1)]);
+        assert!(project
+            .apply_ordering(&ordering, &OrderingOperation::Reverse { pages: 2..5 })
+            .is_err());
+
+        let collated = project
+            .apply_ordering(&ordering[..2], &OrderingOperation::Collate { copies: 3 })
+            .unwrap();
+        assert_eq!(
+            pages(&collated),
+            vec![(0, 0), (0, 1), (0, 0), (0, 1), (0, 0), (0, 1)]
+        );
+        assert!(project
+            .apply_ordering(&ordering, &OrderingOperation::Collate { copies: 0 })
+            .is_err());
+    }
+
+    #[test]
+    fn test_sort_sources() {
+        let project = project(&["test/basic.pdf", "test/paysage.pdf"]);
+        let ordering = selectors(&[(0, 2), (1, 0), (0, 0)]);
+        let sort = |by, descending| {
+            let operation = OrderingOperation::SortSources { by, descending };
+            pages(&project.apply_ordering(&ordering, &operation).unwrap())
+        };
+
+        assert_eq!(sort(SortKey::Name, false), vec![(0, 2), (0, 0), (1, 0)]);
+        assert_eq!(sort(SortKey::Name, true), vec![(1, 0), (0, 2), (0, 0)]);
+    }
+
+    #[test]
+    fn test_date_digits() {
+        assert_eq!(
+            date_digits("D:20240131120000+01'00'").as_deref(),
+            Some("20240131120000")
+        );
+        assert_eq!(date_digits("D:2024").as_deref(), Some("20240000000000"));
+        assert_eq!(date_digits("yesterday"), None);
+    }
+
+    #[test]
+    fn test_natural_cmp() {
+        assert_eq!(natural_cmp("scan2.pdf", "scan10.pdf"), Ordering::Less);
+        assert_eq!(natural_cmp("scan010", "scan10"), Ordering::Equal);
+        assert_eq!(natural_cmp("b", "a10"), Ordering::Greater);
+        assert_eq!(natural_cmp("a", "ab"), Ordering::Less);
+    }
+}
@@ -0,0 +1,246 @@
+use super::{pdfium, Project, Rotation, Selector};
+use anyhow::{anyhow, Result};
+use pdfium_render::prelude::*;
+use serde::Serialize;
+use std::ops::Range;
+
+/// Bookmarks read before giving up, malformed outlines can loop on themselves.
+const MAX_BOOKMARKS: usize = 10_000;
+
+/// Pages of a source file covered by one of its bookmarks.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct OutlineSection {
+    title: String,
+    pages: Range<usize>,
+}
+
+impl Project {
+    /// Sections of a source file from its bookmarks, down to `depth` (1 for the top level only).
+    /// A section starts at its bookmark's page and ends where the next one starts. Of bookmarks
+    /// sharing a page, the last one names the section.
+    pub fn outline_sections(
+        &self,
+        source_file_index: usize,
+        depth: usize,
+    ) -> Result<Vec<OutlineSection>> {
+        let source_file = self
+            .source_files
+            .get(source_file_index)
+            .ok_or_else(|| anyhow!("Invalid source_file_index: {}", source_file_index))?;
+
+        let pdfium = pdfium()?;
+        let bytes = source_file.to_bytes()?;
+        let document = pdfium.load_pdf_from_byte_slice(&bytes, None)?;
+
+        let mut bookmarks = Vec::new();
+        collect_bookmarks(document.bookmarks().root(), 1, depth, &mut bookmarks);
+        bookmarks.retain(|(page_index, _)| *page_index < source_file.pages.len());
+        bookmarks.sort_by_key(|(page_index, _)| *page_index);
+
+        let mut sections: Vec<OutlineSection> = Vec::new();
+        for (page_index, title) in bookmarks {
+            match sections.last_mut() {
+                Some(section) if section.pages.start == page_index => section.title = title,
+                last => {
+                    if let Some(section) = last {
+                        section.pages.end = page_index;
+                    }
+                    sections.push(OutlineSection {
+                        title,
+                        pages: page_index..source_file.pages.len(),
+                    });
+                }
+            }
+        }
+
+        Ok(sections)
+    }
+
+    /// Every page of a source file, in the group of the outline section it belongs to. Pages
+    /// before the first bookmark aren't part of any group.
+    pub fn outline_selectors(
+        &self,
+        source_file_index: usize,
+        depth: usize,
+    ) -> Result<Vec<Selector>> {
+        let sections = self.outline_sections(source_file_index, depth)?;
+        let page_count = self.source_files[source_file_index].pages.len();
+
+        Ok((0..page_count)
+            .map(|page_index| Selector {
+                source_file_index,
+                page_index,
+                rotation: Rotation::R0,
+                rasterize: None,
+                group: sections
+                    .iter()
+                    .find(|section| section.pages.contains(&page_index))
+                    .map(|section| section.title.clone()),
+            })
+            .collect())
+    }
+}
+
+/// Pushes the page and title of the bookmark and its siblings, then of their children until
+/// `depth`.
+fn collect_bookmarks(
+    first: Option<PdfBookmark>,
+    level: usize,
+    depth: usize,
+    bookmarks: &mut Vec<(usize, String)>,
+) {
+    let mut next = first;
+
+    while let Some(bookmark) = next {
+        if bookmarks.len() >= MAX_BOOKMARKS {
+            return;
+        }
+
+        if let Some(page_index) = destination_page(&bookmark) {
+            bookmarks.push((page_index, bookmark.title().unwrap_or_default()));
+        }
+        if level < depth {
+            collect_bookmarks(bookmark.first_child(), level + 1, depth, bookmarks);
+        }
+
+        next = bookmark.next_sibling();
+    }
+}
+
+/// The page a bookmark goes to, either directly or through a `GoTo` action.
+fn destination_page(bookmark: &PdfBookmark) -> Option<usize> {
+    if let Some(destination) = bookmark.destination() {
+        return destination.page_index().ok().map(usize::from);
+    }
+
+    let action = bookmark.action()?;
+    let page_index = action
+        .as_local_destination_action()?
+        .destination()
+        .ok()?
+        .page_index()
+        .ok()?;
+
+    Some(usize::from(page_index))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::project::SourceFile;
+    use lopdf::{dictionary, Object};
+    use std::path::PathBuf;
+
+    /// basic.pdf with "One" and "Three" bookmarks, "One" having a "Two" child.
+    fn project() -> Project {
+        let mut source_file = SourceFile::open(&PathBuf::from("test/basic.pdf"), None).unwrap();
+        let document = &mut source_file.document;
+        let pages = document.get_pages().into_values().collect::<Vec<_>>();
+        let destination = |page: usize| vec![pages[page].into(), "Fit".into()];
+
+        let outlines_id = document.new_object_id();
+        let one_id = document.new_object_id();
+        let two_id = document.new_object_id();
+        let three_id = document.new_object_id();
+
+        document.objects.insert(
+            outlines_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Outlines",
+                "First" => one_id,
+                "Last" => three_id,
+                "Count" => 3,
+            }),
+        );
+        document.objects.insert(
+            one_id,
+            Object::Dictionary(dictionary! {
+                "Title" => Object::string_literal("One"),
+                "Parent" => outlines_id,
+                "Next" => three_id,
+                "First" => two_id,
+                "Last" => two_id,
+                "Count" => 1,
+                "Dest" => destination(0),
+            }),
+        );
+        document.objects.insert(
+            two_id,
+            Object::Dictionary(dictionary! {
+                "Title" => Object::string_literal("Two"),
+                "Parent" => one_id,
+                "Dest" => destination(1),
+            }),
+        );
+        document.objects.insert(
+            three_id,
+            Object::Dictionary(dictionary! {
+                "Title" => Object::string_literal("Three"),
+                "Parent" => outlines_id,
+                "Prev" => one_id,
+                "A" => dictionary! { "S" => "GoTo", "D" => destination(2) },
+            }),
+        );
+        document.catalog_mut().unwrap().set("Outlines", outlines_id);
+
+        Project {
+            source_files: vec![source_file],
+        }
+    }
+
+    fn section(title: &str, pages: Range<usize>) -> OutlineSection {
+        OutlineSection {
+            title: title.to_string(),
+            pages,
+        }
+    }
+
+    #[test]
+    fn test_outline_sections() {
+        let project = project();
+
+        assert_eq!(
+            project.outline_sections(0, 1).unwrap(),
+            vec![section("One", 0..2), section("Three", 2..3)]
+        );
+        assert_eq!(
+            project.outline_sections(0, 2).unwrap(),
+            vec![
+                section("One", 0..1),
+                section("Two", 1..2),
+                section("Three", 2..3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_outline_selectors() {
+        let project = project();
+
+        let groups = project
+            .outline_selectors(0, 1)
+            .unwrap()
+            .into_iter()
+            .map(|selector| selector.group)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            groups,
+            vec![
+                Some("One".to_string()),
+                Some("One".to_string()),
+                Some("Three".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_outline_sections_without_outline() {
+        let project = Project {
+            source_files: vec![SourceFile::open(&PathBuf::from("test/basic.pdf"), None).unwrap()],
+        };
+
+        assert!(project.outline_sections(0, 1).unwrap().is_empty());
+        assert!(project.outline_sections(1, 1).is_err());
+    }
+}
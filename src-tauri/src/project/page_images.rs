@@ -0,0 +1,261 @@
+use super::rasterize::{default_dpi, default_quality, render_page};
+use super::{pdfium, ColorMode, Project, Selector};
+use anyhow::Result;
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, GrayImage};
+use serde::Deserialize;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+/// Gray level under which a pixel turns black in monochrome images.
+const MONOCHROME_THRESHOLD: u8 = 128;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PageImageFormat {
+    #[default]
+    Png,
+    Jpeg,
+    Tiff,
+    Webp,
+}
+
+impl PageImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PageImageFormat::Png => "png",
+            PageImageFormat::Jpeg => "jpg",
+            PageImageFormat::Tiff => "tiff",
+            PageImageFormat::Webp => "webp",
+        }
+    }
+
+    fn image_format(&self) -> image::ImageFormat {
+        match self {
+            PageImageFormat::Png => image::ImageFormat::Png,
+            PageImageFormat::Jpeg => image::ImageFormat::Jpeg,
+            PageImageFormat::Tiff => image::ImageFormat::Tiff,
+            PageImageFormat::Webp => image::ImageFormat::WebP,
+        }
+    }
+}
+
+/// How pages are turned into image files.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PageImageOptions {
+    #[serde(default)]
+    format: PageImageFormat,
+    #[serde(default = "default_dpi")]
+    dpi: f32,
+    #[serde(default)]
+    color_mode: ColorMode,
+    /// JPEG quality, from 1 to 100. Other formats are lossless.
+    #[serde(default = "default_quality")]
+    quality: u8,
+}
+
+impl Default for PageImageOptions {
+    fn default() -> Self {
+        PageImageOptions {
+            format: PageImageFormat::default(),
+            dpi: default_dpi(),
+            color_mode: ColorMode::default(),
+            quality: default_quality(),
+        }
+    }
+}
+
+impl Project {
+    /// Writes an image of every selected page, calling `write` with the page's position in the
+    /// ordering and the encoded file.
+    pub fn export_page_images(
+        &self,
+        selectors: &[Selector],
+        options: &PageImageOptions,
+        mut write: impl FnMut(usize, Vec<u8>) -> Result<()>,
+    ) -> Result<()> {
+        self.render_export(
+            selectors,
+            options.dpi,
+            options.color_mode,
+            |index, image| write(index, encode(&image, options.format, options.quality)?),
+        )
+    }
+
+    /// Paths of the images of the selected pages in `folder`, named after `template` like the
+    /// parts of a split export, with `{index}` being the page's position in the ordering.
+    pub fn page_image_paths(
+        &self,
+        folder: &Path,
+        template: &str,
+        selectors: &[Selector],
+        options: &PageImageOptions,
+    ) -> Vec<PathBuf> {
+        self.template_paths(
+            folder,
+            template,
+            options.format.extension(),
+            selectors,
+            0..selectors.len(),
+        )
+    }
+
+    /// Renders the selected pages as they would be exported, rotated, redacted and rasterized,
+    /// calling `each` with the page's position in the ordering and its image.
+    pub(super) fn render_export(
+        &self,
+        selectors: &[Selector],
+        dpi: f32,
+        color_mode: ColorMode,
+        mut each: impl FnMut(usize, DynamicImage) -> Result<()>,
+    ) -> Result<()> {
+        let (mut document, _) = self.export(selectors)?;
+        let mut bytes = Vec::new();
+        document.save_to(&mut bytes)?;
+
+        let pdfium = pdfium()?;
+        let document = pdfium.load_pdf_from_byte_slice(&bytes, None)?;
+
+        for (index, page) in document.pages().iter().enumerate() {
+            let image = render_page(&page, dpi)?;
+            each(index, with_color_mode(image, color_mode))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn with_color_mode(image: DynamicImage, color_mode: ColorMode) -> DynamicImage {
+    match color_mode {
+        ColorMode::Color => DynamicImage::ImageRgb8(image.into_rgb8()),
+        ColorMode::Grayscale => DynamicImage::ImageLuma8(image.into_luma8()),
+        ColorMode::Monochrome => DynamicImage::ImageLuma8(monochrome(image.into_luma8())),
+    }
+}
+
+/// Turns every pixel either black or white.
+fn monochrome(mut image: GrayImage) -> GrayImage {
+    for pixel in image.pixels_mut() {
+        pixel.0[0] = if pixel.0[0] < MONOCHROME_THRESHOLD {
+            0
+        } else {
+            u8::MAX
+        };
+    }
+    image
+}
+
+/// Encodes an image file, `quality` only applying to JPEG.
+pub(super) fn encode(
+    image: &DynamicImage,
+    format: PageImageFormat,
+    quality: u8,
+) -> Result<Vec<u8>> {
+    let mut bytes = Cursor::new(Vec::new());
+    match format {
+        PageImageFormat::Jpeg => {
+            JpegEncoder::new_with_quality(&mut bytes, quality.clamp(1, 100)).encode_image(image)?
+        }
+        format => image.write_to(&mut bytes, format.image_format())?,
+    }
+
+    Ok(bytes.into_inner())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::project::test::project;
+    use crate::project::Rotation;
+    use image::GenericImageView;
+
+    fn options(format: PageImageFormat, color_mode: ColorMode) -> PageImageOptions {
+        PageImageOptions {
+            format,
+            dpi: 72.0,
+            color_mode,
+            ..Default::default()
+        }
+    }
+
+    fn images(selectors: &[Selector], options: &PageImageOptions) -> Vec<DynamicImage> {
+        let mut images = Vec::new();
+        project(&["test/basic.pdf"])
+            .export_page_images(selectors, options, |index, bytes| {
+                assert_eq!(index, images.len());
+                images.push(image::load_from_memory_with_format(
+                    &bytes,
+                    options.format.image_format(),
+                )?);
+                Ok(())
+            })
+            .unwrap();
+        images
+    }
+
+    #[test]
+    fn test_export_page_images() {
+        let mut rotated = Selector::new(0, 1);
+        rotated.rotation = Rotation::R90;
+
+        for format in [
+            PageImageFormat::Png,
+            PageImageFormat::Jpeg,
+            PageImageFormat::Tiff,
+            PageImageFormat::Webp,
+        ] {
+            let images = images(
+                &[Selector::new(0, 0), rotated.clone()],
+                &options(format, ColorMode::Color),
+            );
+
+            assert_eq!(images.len(), 2);
+            assert_eq!(images[0].dimensions(), (612, 792));
+            assert_eq!(images[1].dimensions(), (792, 612));
+        }
+    }
+
+    #[test]
+    fn test_export_page_images_color_modes() {
+        let selectors = [Selector::new(0, 0)];
+
+        let gray = images(
+            &selectors,
+            &options(PageImageFormat::Png, ColorMode::Grayscale),
+        );
+        assert!(matches!(gray[0], DynamicImage::ImageLuma8(_)));
+
+        let black_and_white = images(
+            &selectors,
+            &options(PageImageFormat::Png, ColorMode::Monochrome),
+        );
+        let image = black_and_white[0].as_luma8().unwrap();
+        assert!(image
+            .pixels()
+            .all(|pixel| [0, u8::MAX].contains(&pixel.0[0])));
+    }
+
+    #[test]
+    fn test_monochrome() {
+        let image = GrayImage::from_raw(3, 1, vec![10, 127, 200]).unwrap();
+
+        assert_eq!(monochrome(image).into_raw(), vec![0, 0, u8::MAX]);
+    }
+
+    #[test]
+    fn test_page_image_paths() {
+        let project = project(&["test/basic.pdf"]);
+        let folder = PathBuf::from("/nonexistent");
+        let selectors = [Selector::new(0, 2), Selector::new(0, 0)];
+
+        assert_eq!(
+            project.page_image_paths(
+                &folder,
+                "{source}-{first_page}",
+                &selectors,
+                &options(PageImageFormat::Jpeg, ColorMode::Color),
+            ),
+            vec![folder.join("basic-3.jpg"), folder.join("basic-1.jpg")]
+        );
+    }
+}
@@ -0,0 +1,453 @@
+use super::{Project, Rotation, Selector};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use thiserror::Error;
+
+/// An invalid page range expression, with the characters at fault.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, Error)]
+#[error("{message} at character {}", position + 1)]
+pub struct PageRangeError {
+    /// Position of the first character at fault, counted in characters from 0.
+    position: usize,
+    length: usize,
+    message: String,
+}
+
+impl PageRangeError {
+    fn new(position: usize, length: usize, message: impl Into<String>) -> Self {
+        PageRangeError {
+            position,
+            length,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(usize),
+    /// `last` or `end`.
+    Last,
+    Odd,
+    Even,
+    /// `r90`, `r180`, `r270` or `r0`, in degrees.
+    Rotation(u32),
+    Colon,
+    Dash,
+    DotDot,
+    Comma,
+    Bang,
+}
+
+#[derive(Debug, Clone)]
+struct Spanned {
+    token: Token,
+    position: usize,
+    length: usize,
+}
+
+/// One end of a range, `last-2` being an offset from the last page.
+#[derive(Debug, Clone)]
+enum Bound {
+    Page(usize),
+    Last(usize),
+}
+
+#[derive(Debug, Clone)]
+enum Pages {
+    Range(Bound, Bound),
+    Odd,
+    Even,
+}
+
+#[derive(Debug, Clone)]
+struct Item {
+    exclude: bool,
+    rotation: Option<Rotation>,
+    pages: Pages,
+    position: usize,
+    length: usize,
+}
+
+impl Project {
+    /// Selectors for the pages of a source file picked by a page range expression, such as
+    /// `1-5, 8, 10-end, odd, r90:3-4, last-2..last, !7`. See [`parse_page_ranges`].
+    pub fn page_range_selectors(
+        &self,
+        source_file_index: usize,
+        expression: &str,
+    ) -> Result<Vec<Selector>> {
+        let source_file = self
+            .source_files
+            .get(source_file_index)
+            .ok_or_else(|| anyhow!("Invalid source_file_index: {}", source_file_index))?;
+
+        let pages = parse_page_ranges(expression, source_file.pages.len())?;
+
+        Ok(pages
+            .into_iter()
+            .map(|(page_index, rotation)| Selector {
+                source_file_index,
+                page_index,
+                rotation,
+                rasterize: None,
+                group: None,
+            })
+            .collect())
+    }
+}
+
+/// Resolves a page range expression against a document of `page_count` pages, into page indices
+/// and their rotation.
+///
+/// The expression is a comma separated list of page numbers starting at 1, ranges (`1-5` or
+/// `1..5`, backwards when the first page is after the second), and `odd` or `even` pages. `last`
+/// or `end` is the last page, and `last-2` the one two pages before. An item can be prefixed
+/// with a rotation (`r90:3-4`), or with `!` to leave its pages out of the others. An expression
+/// with only exclusions starts from every page.
+pub(super) fn parse_page_ranges(
+    expression: &str,
+    page_count: usize,
+) -> std::result::Result<Vec<(usize, Rotation)>, PageRangeError> {
+    let tokens = tokenize(expression)?;
+    let items = Parser {
+        tokens: &tokens,
+        index: 0,
+        end: expression.chars().count(),
+    }
+    .items()?;
+
+    let mut pages = Vec::new();
+    let mut excluded = BTreeSet::new();
+    for item in &items {
+        let indices = resolve(item, page_count)?;
+        if item.exclude {
+            excluded.extend(indices);
+        } else {
+            let rotation = item.rotation.clone().unwrap_or(Rotation::R0);
+            pages.extend(indices.into_iter().map(|index| (index, rotation.clone())));
+        }
+    }
+
+    if items.iter().all(|item| item.exclude) {
+        pages = (0..page_count).map(|index| (index, Rotation::R0)).collect();
+    }
+    pages.retain(|(index, _)| !excluded.contains(index));
+
+    Ok(pages)
+}
+
+fn tokenize(expression: &str) -> std::result::Result<Vec<Spanned>, PageRangeError> {
+    let chars = expression.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+
+    let mut position = 0;
+    while position < chars.len() {
+        let start = position;
+        let character = chars[position];
+        position += 1;
+
+        let token = match character {
+            _ if character.is_whitespace() => continue,
+            ',' => Token::Comma,
+            ':' => Token::Colon,
+            '!' => Token::Bang,
+            '-' => Token::Dash,
+            '.' if chars.get(position) == Some(&'.') => {
+                position += 1;
+                Token::DotDot
+            }
+            '.' => return Err(PageRangeError::new(start, 1, "Expected '..'")),
+            _ if character.is_ascii_digit() => {
+                while chars.get(position).is_some_and(char::is_ascii_digit) {
+                    position += 1;
+                }
+                let digits = chars[start..position].iter().collect::<String>();
+                let number = digits.parse().map_err(|_| {
+                    PageRangeError::new(start, position - start, "Page number is too large")
+                })?;
+                Token::Number(number)
+            }
+            _ if character.is_alphabetic() => {
+                while chars.get(position).is_some_and(|c| c.is_alphanumeric()) {
+                    position += 1;
+                }
+                let word = chars[start..position]
+                    .iter()
+                    .collect::<String>()
+                    .to_lowercase();
+                match word.as_str() {
+                    "last" | "end" => Token::Last,
+                    "odd" => Token::Odd,
+                    "even" => Token::Even,
+                    "r0" => Token::Rotation(0),
+                    "r90" => Token::Rotation(90),
+                    "r180" => Token::Rotation(180),
+                    "r270" => Token::Rotation(270),
+                    _ => {
+                        return Err(PageRangeError::new(
+                            start,
+                            position - start,
+                            format!("Unknown word '{}'", word),
+                        ))
+                    }
+                }
+            }
+            _ => {
+                return Err(PageRangeError::new(
+                    start,
+                    1,
+                    format!("Unexpected character '{}'", character),
+                ))
+            }
+        };
+
+        tokens.push(Spanned {
+            token,
+            position: start,
+            length: position - start,
+        });
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Spanned],
+    index: usize,
+    /// Length of the expression, where errors about missing tokens point.
+    end: usize,
+}
+
+impl Parser<'_> {
+    fn items(&mut self) -> std::result::Result<Vec<Item>, PageRangeError> {
+        let mut items = vec![self.item()?];
+        while self.index < self.tokens.len() {
+            self.expect(Token::Comma, "Expected ',' between ranges")?;
+            items.push(self.item()?);
+        }
+        Ok(items)
+    }
+
+    fn item(&mut self) -> std::result::Result<Item, PageRangeError> {
+        let position = self.position();
+        let exclude = self.eat(&Token::Bang);
+
+        let rotation = match self.peek() {
+            Some(Token::Rotation(degrees)) => {
+                let rotation = match degrees {
+                    90 => Rotation::R90,
+                    180 => Rotation::R180,
+                    270 => Rotation::R270,
+                    _ => Rotation::R0,
+                };
+                self.index += 1;
+                self.expect(Token::Colon, "Expected ':' after the rotation")?;
+                Some(rotation)
+            }
+            _ => None,
+        };
+
+        let pages = match self.peek() {
+            Some(Token::Odd) => {
+                self.index += 1;
+                Pages::Odd
+            }
+            Some(Token::Even) => {
+                self.index += 1;
+                Pages::Even
+            }
+            _ => {
+                let first = self.bound()?;
+                if self.eat(&Token::Dash) || self.eat(&Token::DotDot) {
+                    Pages::Range(first.clone(), self.bound()?)
+                } else {
+                    Pages::Range(first.clone(), first)
+                }
+            }
+        };
+
+        Ok(Item {
+            exclude,
+            rotation,
+            pages,
+            position,
+            length: self.position() - position,
+        })
+    }
+
+    fn bound(&mut self) -> std::result::Result<Bound, PageRangeError> {
+        match self.peek() {
+            Some(Token::Number(number)) => {
+                let number = *number;
+                self.index += 1;
+                Ok(Bound::Page(number))
+            }
+            Some(Token::Last) => {
+                self.index += 1;
+                // `last-2` is an offset, while `last-2-5` is a range from it.
+                if let [Spanned {
+                    token: Token::Dash, ..
+                }, Spanned {
+                    token: Token::Number(offset),
+                    ..
+                }, ..] = &self.tokens[self.index..]
+                {
+                    self.index += 2;
+                    return Ok(Bound::Last(*offset));
+                }
+                Ok(Bound::Last(0))
+            }
+            _ => Err(self.error("Expected a page number, 'last', 'odd' or 'even'")),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|spanned| &spanned.token)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.peek() == Some(token);
+        if found {
+            self.index += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, token: Token, message: &str) -> std::result::Result<(), PageRangeError> {
+        match self.eat(&token) {
+            true => Ok(()),
+            false => Err(self.error(message)),
+        }
+    }
+
+    /// Start of the next token, or the end of the expression.
+    fn position(&self) -> usize {
+        match self.tokens.get(self.index) {
+            Some(spanned) => spanned.position,
+            None => self.end,
+        }
+    }
+
+    /// An error about the next token.
+    fn error(&self, message: &str) -> PageRangeError {
+        match self.tokens.get(self.index) {
+            Some(spanned) => PageRangeError::new(spanned.position, spanned.length, message),
+            None => PageRangeError::new(self.end, 0, message),
+        }
+    }
+}
+
+/// Indices of the pages of an item, in the order the item lists them.
+fn resolve(item: &Item, page_count: usize) -> std::result::Result<Vec<usize>, PageRangeError> {
+    let error = |message: String| PageRangeError::new(item.position, item.length, message);
+
+    let index = |bound: &Bound| -> std::result::Result<usize, PageRangeError> {
+        let number = match bound {
+            Bound::Page(number) => *number,
+            Bound::Last(offset) => page_count.saturating_sub(*offset),
+        };
+        if number == 0 || number > page_count {
+            return Err(error(match bound {
+                Bound::Page(0) => "Pages are numbered from 1".to_string(),
+                Bound::Page(_) => format!("The document only has {} pages", page_count),
+                Bound::Last(_) => "Goes before the first page".to_string(),
+            }));
+        }
+        Ok(number - 1)
+    };
+
+    Ok(match &item.pages {
+        Pages::Range(first, last) => {
+            let (first, last) = (index(first)?, index(last)?);
+            if first <= last {
+                (first..=last).collect()
+            } else {
+                (last..=first).rev().collect()
+            }
+        }
+        Pages::Odd => (0..page_count).step_by(2).collect(),
+        Pages::Even => (1..page_count).step_by(2).collect(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pages(expression: &str, page_count: usize) -> Vec<(usize, Option<u32>)> {
+        parse_page_ranges(expression, page_count)
+            .unwrap()
+            .into_iter()
+            .map(|(index, rotation)| (index + 1, rotation.as_rotation()))
+            .collect()
+    }
+
+    fn numbers(expression: &str, page_count: usize) -> Vec<usize> {
+        pages(expression, page_count)
+            .into_iter()
+            .map(|(number, _)| number)
+            .collect()
+    }
+
+    fn error(expression: &str, page_count: usize) -> (usize, usize) {
+        let error = parse_page_ranges(expression, page_count).unwrap_err();
+        (error.position, error.length)
+    }
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(numbers("1-3, 8, 10-end", 12), vec![1, 2, 3, 8, 10, 11, 12]);
+        assert_eq!(numbers("last-2..last", 10), vec![8, 9, 10]);
+        assert_eq!(numbers("5-3", 10), vec![5, 4, 3]);
+        assert_eq!(numbers("LAST", 4), vec![4]);
+        assert_eq!(numbers("end-1", 4), vec![3]);
+    }
+
+    #[test]
+    fn test_odd_even() {
+        assert_eq!(numbers("odd", 5), vec![1, 3, 5]);
+        assert_eq!(numbers("even", 5), vec![2, 4]);
+    }
+
+    #[test]
+    fn test_exclusions() {
+        assert_eq!(numbers("1-5, !3", 10), vec![1, 2, 4, 5]);
+        assert_eq!(numbers("!7", 8), vec![1, 2, 3, 4, 5, 6, 8]);
+        assert_eq!(numbers("odd, !r90:1", 4), vec![3]);
+    }
+
+    #[test]
+    fn test_rotation() {
+        assert_eq!(
+            pages("1, r90:3-4, r270:last", 5),
+            vec![(1, None), (3, Some(90)), (4, Some(90)), (5, Some(270))]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(error("1-5, 12", 10), (5, 2));
+        assert_eq!(error("0", 10), (0, 1));
+        assert_eq!(error("1,,2", 10), (2, 1));
+        assert_eq!(error("1 2", 10), (2, 1));
+        assert_eq!(error("1-", 10), (2, 0));
+        assert_eq!(error("r90 3", 10), (4, 1));
+        assert_eq!(error("1, pages", 10), (3, 5));
+        assert_eq!(error("1.2", 10), (1, 1));
+        assert_eq!(error("3 & 4", 10), (2, 1));
+        assert_eq!(error("last-20", 10), (0, 7));
+        assert_eq!(error("", 10), (0, 0));
+    }
+
+    #[test]
+    fn test_error_message() {
+        let error = parse_page_ranges("1-5, 12", 10).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "The document only has 10 pages at character 6"
+        );
+    }
+}
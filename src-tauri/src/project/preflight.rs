@@ -0,0 +1,505 @@
+use super::{Project, Selector};
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Images above this many pixels are reported, they make exports slow and large.
+const LARGE_IMAGE_PIXELS: i64 = 25_000_000;
+
+/// Fonts every PDF reader must provide, they don't need to be embedded.
+const STANDARD_FONTS: &[&str] = &[
+    "Times-Roman",
+    "Times-Bold",
+    "Times-Italic",
+    "Times-BoldItalic",
+    "Helvetica",
+    "Helvetica-Bold",
+    "Helvetica-Oblique",
+    "Helvetica-BoldOblique",
+    "Courier",
+    "Courier-Bold",
+    "Courier-Oblique",
+    "Courier-BoldOblique",
+    "Symbol",
+    "ZapfDingbats",
+];
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PageRef {
+    source_file_index: usize,
+    page_index: usize,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(
+    rename_all = "snake_case",
+    rename_all_fields = "snake_case",
+    tag = "kind"
+)]
+pub enum PreflightCheck {
+    Encrypted { readable: bool },
+    Signed,
+    UnembeddedFont { font: String },
+    BrokenContent,
+    LargeImage { width: i64, height: i64 },
+    MixedPageSizes,
+    MixedVersions { versions: Vec<String> },
+    JavaScript,
+    Attachments,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PreflightIssue {
+    severity: Severity,
+    check: PreflightCheck,
+    /// The source the issue was found in, `None` when it concerns the whole project.
+    source_file_index: Option<usize>,
+    /// Pages affected by the issue, empty when it concerns the whole document.
+    pages: Vec<PageRef>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PreflightReport {
+    issues: Vec<PreflightIssue>,
+}
+
+impl PreflightReport {
+    #[cfg(test)]
+    fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    #[cfg(test)]
+    fn severity(&self) -> Option<Severity> {
+        self.issues.iter().map(|issue| issue.severity).max()
+    }
+
+    fn push(
+        &mut self,
+        severity: Severity,
+        check: PreflightCheck,
+        source_file_index: Option<usize>,
+        pages: Vec<PageRef>,
+    ) {
+        self.issues.push(PreflightIssue {
+            severity,
+            check,
+            source_file_index,
+            pages,
+        });
+    }
+}
+
+impl Project {
+    /// Looks for problems in the sources of the selected pages before they are exported.
+    pub fn preflight(&self, selectors: &[Selector]) -> PreflightReport {
+        let mut report = PreflightReport::default();
+
+        let mut selected = BTreeMap::<usize, BTreeSet<usize>>::new();
+        for selector in selectors {
+            if selector.source_file_index < self.source_files.len() {
+                selected
+                    .entry(selector.source_file_index)
+                    .or_default()
+                    .insert(selector.page_index);
+            }
+        }
+
+        for (&source_file_index, page_indices) in &selected {
+            let source_file = &self.source_files[source_file_index];
+            let document = &source_file.document;
+            let source = Some(source_file_index);
+
+            if document.is_encrypted() {
+                let readable = document.encryption_state.is_some();
+                let severity = if readable {
+                    Severity::Warning
+                } else {
+                    Severity::Error
+                };
+                report.push(
+                    severity,
+                    PreflightCheck::Encrypted { readable },
+                    source,
+                    vec![],
+                );
+            }
+
+            if !source_file.signatures.is_empty() {
+                report.push(Severity::Warning, PreflightCheck::Signed, source, vec![]);
+            }
+
+            if has_javascript(document) {
+                report.push(
+                    Severity::Warning,
+                    PreflightCheck::JavaScript,
+                    source,
+                    vec![],
+                );
+            }
+
+            if has_attachments(document) {
+                report.push(Severity::Info, PreflightCheck::Attachments, source, vec![]);
+            }
+
+            let page_ids = document.get_pages().into_values().collect::<Vec<_>>();
+            let mut unembedded_fonts = BTreeMap::<String, Vec<PageRef>>::new();
+            let mut broken_pages = Vec::new();
+
+            for &page_index in page_indices {
+                let Some(&page_id) = page_ids.get(page_index) else {
+                    continue;
+                };
+                let page = PageRef {
+                    source_file_index,
+                    page_index,
+                };
+
+                if has_broken_content(document, page_id) {
+                    broken_pages.push(page);
+                }
+
+                for font in unembedded_page_fonts(document, page_id) {
+                    unembedded_fonts.entry(font).or_default().push(page);
+                }
+
+                for (width, height) in large_page_images(document, page_id) {
+                    report.push(
+                        Severity::Warning,
+                        PreflightCheck::LargeImage { width, height },
+                        source,
+                        vec![page],
+                    );
+                }
+            }
+
+            if !broken_pages.is_empty() {
+                report.push(
+                    Severity::Error,
+                    PreflightCheck::BrokenContent,
+                    source,
+                    broken_pages,
+                );
+            }
+
+            for (font, pages) in unembedded_fonts {
+                report.push(
+                    Severity::Warning,
+                    PreflightCheck::UnembeddedFont { font },
+                    source,
+                    pages,
+                );
+            }
+        }
+
+        self.check_page_sizes(selectors, &mut report);
+        self.check_versions(&selected, &mut report);
+
+        report
+    }
+
+    fn check_page_sizes(&self, selectors: &[Selector], report: &mut PreflightReport) {
+        let mut sizes = BTreeMap::<(i64, i64), Vec<PageRef>>::new();
+
+        for selector in selectors {
+            let Some(page) = self
+                .source_files
+                .get(selector.source_file_index)
+                .and_then(|source_file| source_file.pages.get(selector.page_index))
+            else {
+                continue;
+            };
+
+            // Orientation is ignored, rotating a page is already easy.
+            let (width, height) = page.metadata.media_box;
+            let (width, height) = (width.round() as i64, height.round() as i64);
+            let size = (width.min(height), width.max(height));
+
+            let page = PageRef {
+                source_file_index: selector.source_file_index,
+                page_index: selector.page_index,
+            };
+            let pages = sizes.entry(size).or_default();
+            if !pages.contains(&page) {
+                pages.push(page);
+            }
+        }
+
+        if sizes.len() < 2 {
+            return;
+        }
+
+        // Point out the pages that don't have the most common size.
+        let most_common = sizes
+            .iter()
+            .max_by_key(|(_, pages)| pages.len())
+            .map(|(size, _)| *size);
+        let pages = sizes
+            .into_iter()
+            .filter(|(size, _)| Some(*size) != most_common)
+            .flat_map(|(_, pages)| pages)
+            .collect();
+
+        report.push(Severity::Info, PreflightCheck::MixedPageSizes, None, pages);
+    }
+
+    fn check_versions(
+        &self,
+        selected: &BTreeMap<usize, BTreeSet<usize>>,
+        report: &mut PreflightReport,
+    ) {
+        let versions = selected
+            .keys()
+            .map(|&index| self.source_files[index].document.version.clone())
+            .collect::<BTreeSet<_>>();
+
+        if versions.len() > 1 {
+            report.push(
+                Severity::Info,
+                PreflightCheck::MixedVersions {
+                    versions: versions.into_iter().collect(),
+                },
+                None,
+                vec![],
+            );
+        }
+    }
+}
+
+fn has_javascript(document: &Document) -> bool {
+    document.objects.values().any(|object| {
+        object.as_dict().is_ok_and(|dict| {
+            dict.has(b"JS")
+                || dict.get(b"S").and_then(Object::as_name).ok() == Some(b"JavaScript".as_slice())
+        })
+    })
+}
+
+fn has_attachments(document: &Document) -> bool {
+    let embedded_files = document
+        .catalog()
+        .and_then(|catalog| document.get_dict_in_dict(catalog, b"Names"))
+        .is_ok_and(|names| names.has(b"EmbeddedFiles"));
+
+    embedded_files
+        || document.objects.values().any(|object| {
+            object.as_dict().is_ok_and(|dict| {
+                dict.get(b"Subtype").and_then(Object::as_name).ok()
+                    == Some(b"FileAttachment".as_slice())
+            })
+        })
+}
+
+fn has_broken_content(document: &Document, page_id: ObjectId) -> bool {
+    let mut content = Vec::new();
+
+    for id in document.get_page_contents(page_id) {
+        let Ok(stream) = document.get_object(id).and_then(Object::as_stream) else {
+            return true;
+        };
+
+        match stream.decompressed_content() {
+            Ok(data) => content.extend(data),
+            Err(_) if stream.dict.has(b"Filter") => return true,
+            Err(_) => content.extend_from_slice(&stream.content),
+        }
+    }
+
+    Content::decode(&content).is_err()
+}
+
+fn unembedded_page_fonts(document: &Document, page_id: ObjectId) -> Vec<String> {
+    let Ok(fonts) = document.get_page_fonts(page_id) else {
+        return vec![];
+    };
+
+    fonts
+        .values()
+        .filter(|font| !is_font_embedded(document, font))
+        .filter_map(|font| {
+            let name = font.get(b"BaseFont").and_then(Object::as_name).ok()?;
+            let name = String::from_utf8_lossy(name).into_owned();
+            let is_standard = STANDARD_FONTS.contains(&without_subset_tag(&name));
+            (!is_standard).then_some(name)
+        })
+        .collect()
+}
+
+/// The font name without the `ABCDEF+` tag of a subset.
+fn without_subset_tag(name: &str) -> &str {
+    match name.split_once('+') {
+        Some((tag, base_name))
+            if tag.len() == 6 && tag.bytes().all(|byte| byte.is_ascii_uppercase()) =>
+        {
+            base_name
+        }
+        _ => name,
+    }
+}
+
+fn is_font_embedded(document: &Document, font: &Dictionary) -> bool {
+    match font.get(b"Subtype").and_then(Object::as_name) {
+        // Type 3 glyphs are drawn by the content streams of the font itself.
+        Ok(b"Type3") => true,
+        Ok(b"Type0") => font
+            .get(b"DescendantFonts")
+            .and_then(|fonts| document.dereference(fonts))
+            .and_then(|(_, fonts)| fonts.as_array())
+            .ok()
+            .and_then(|fonts| fonts.first())
+            .and_then(|font| document.dereference(font).ok())
+            .and_then(|(_, font)| font.as_dict().ok())
+            .is_some_and(|font| is_font_embedded(document, font)),
+        _ => document
+            .get_dict_in_dict(font, b"FontDescriptor")
+            .is_ok_and(|descriptor| {
+                descriptor.has(b"FontFile")
+                    || descriptor.has(b"FontFile2")
+                    || descriptor.has(b"FontFile3")
+            }),
+    }
+}
+
+fn large_page_images(document: &Document, page_id: ObjectId) -> Vec<(i64, i64)> {
+    document
+        .get_page_images(page_id)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|image| image.width * image.height > LARGE_IMAGE_PIXELS)
+        .map(|image| (image.width, image.height))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::project::signature;
+    use crate::project::test::project;
+    use lopdf::dictionary;
+
+    #[test]
+    fn test_preflight_clean_documents() {
+        let project = project(&["test/basic.pdf"]);
+        let selectors = vec![Selector::new(0, 0), Selector::new(0, 1)];
+
+        let report = project.preflight(&selectors);
+
+        assert!(report.is_empty(), "{:?}", report);
+    }
+
+    #[test]
+    fn test_preflight_mixed_page_sizes() {
+        let project = project(&["test/basic.pdf", "test/legal.pdf", "test/paysage.pdf"]);
+        let selectors = vec![
+            Selector::new(0, 0),
+            Selector::new(2, 0),
+            Selector::new(1, 0),
+        ];
+
+        let report = project.preflight(&selectors);
+
+        assert_eq!(1, report.issues.len());
+        assert_eq!(PreflightCheck::MixedPageSizes, report.issues[0].check);
+        assert_eq!(
+            vec![PageRef {
+                source_file_index: 1,
+                page_index: 0
+            }],
+            report.issues[0].pages
+        );
+        assert_eq!(Some(Severity::Info), report.severity());
+    }
+
+    #[test]
+    fn test_preflight_javascript_and_signatures() {
+        let mut project = project(&["test/basic.pdf"]);
+        let document = &mut project.source_files[0].document;
+        document.add_object(dictionary! {
+            "S" => "JavaScript",
+            "JS" => Object::string_literal("app.alert('hello');"),
+        });
+        document.add_object(dictionary! {
+            "Type" => "Sig",
+            "ByteRange" => vec![0.into(), 10.into(), 20.into(), 30.into()],
+        });
+        project.source_files[0].signatures = signature::read_signatures(document, 50);
+
+        let report = project.preflight(&[Selector::new(0, 0)]);
+
+        let checks = report
+            .issues
+            .iter()
+            .map(|issue| issue.check.clone())
+            .collect::<Vec<_>>();
+        assert!(checks.contains(&PreflightCheck::JavaScript));
+        assert!(checks.contains(&PreflightCheck::Signed));
+        assert_eq!(Some(Severity::Warning), report.severity());
+    }
+
+    #[test]
+    fn test_preflight_unembedded_fonts() {
+        let mut project = project(&["test/basic.pdf"]);
+        let document = &mut project.source_files[0].document;
+        let page_id = document.page_iter().next().unwrap();
+        let fonts = ["TimesNewRomanPSMT", "Times-Roman", "ABCDEF+Helvetica-Bold"]
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let font = dictionary! {
+                    "Type" => "Font",
+                    "Subtype" => "TrueType",
+                    "BaseFont" => *name,
+                };
+                (format!("F{}", index), Object::Dictionary(font))
+            })
+            .collect::<Dictionary>();
+        document
+            .get_dictionary_mut(page_id)
+            .unwrap()
+            .set("Resources", dictionary! { "Font" => fonts });
+
+        let report = project.preflight(&[Selector::new(0, 0)]);
+
+        let fonts = report
+            .issues
+            .iter()
+            .filter_map(|issue| match &issue.check {
+                PreflightCheck::UnembeddedFont { font } => Some(font.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(fonts, vec!["TimesNewRomanPSMT"]);
+    }
+
+    #[test]
+    fn test_without_subset_tag() {
+        assert_eq!(without_subset_tag("ABCDEF+Symbol"), "Symbol");
+        assert_eq!(without_subset_tag("Abcdef+Symbol"), "Abcdef+Symbol");
+        assert_eq!(without_subset_tag("Courier"), "Courier");
+    }
+
+    #[test]
+    fn test_preflight_broken_content() {
+        let mut project = project(&["test/basic.pdf"]);
+        let document = &mut project.source_files[0].document;
+        let page_id = document.page_iter().next().unwrap();
+        document
+            .get_dictionary_mut(page_id)
+            .unwrap()
+            .set("Contents", Object::Reference((9999, 0)));
+
+        let report = project.preflight(&[Selector::new(0, 0)]);
+
+        assert_eq!(PreflightCheck::BrokenContent, report.issues[0].check);
+        assert_eq!(Some(Severity::Error), report.severity());
+    }
+}
@@ -0,0 +1,333 @@
+use super::{pdfium, Selector, MAX_TILE_RENDER_SIZE};
+use anyhow::{anyhow, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, GrayImage};
+use lopdf::{dictionary, Document, Object, ObjectId, Stream};
+use pdfium_render::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const JPEG_QUALITY: u8 = 85;
+
+/// Page entries kept on a rasterized page, everything else may hold hidden content.
+const KEPT_PAGE_KEYS: &[&[u8]] = &[b"Type", b"Parent", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Replaces a page with a picture of it on export, leaving no text or hidden objects behind.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct RasterizeOptions {
+    #[serde(default = "default_dpi")]
+    dpi: f32,
+    #[serde(default)]
+    color_mode: ColorMode,
+}
+
+/// Resolution of rendered pages and resampled images when none is chosen, in dots per inch.
+pub(super) fn default_dpi() -> f32 {
+    150.0
+}
+
+/// JPEG quality of rendered pages when none is chosen.
+pub(super) fn default_quality() -> u8 {
+    JPEG_QUALITY
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    #[default]
+    Color,
+    Grayscale,
+    /// Black and white, for text pages and line art.
+    Monochrome,
+}
+
+/// Rasterizes the pages of an export whose selector asks for it. The pages are rendered from the
+/// export itself, so redactions are applied to the picture too.
+pub(super) fn rasterize_pages(document: &mut Document, selectors: &[Selector]) -> Result<()> {
+    let page_ids = document.get_pages().into_values().collect::<Vec<_>>();
+
+    let mut targets = BTreeMap::new();
+    for (index, (selector, page_id)) in selectors.iter().zip(&page_ids).enumerate() {
+        if let Some(options) = &selector.rasterize {
+            targets.entry(*page_id).or_insert((index, options));
+        }
+    }
+    if targets.is_empty() {
+        return Ok(());
+    }
+
+    // The page's rotation is kept on the page itself, render it upright.
+    let mut upright = document.clone();
+    for page_id in targets.keys() {
+        upright.get_dictionary_mut(*page_id)?.set("Rotate", 0);
+    }
+    let mut bytes = Vec::new();
+    upright.save_to(&mut bytes)?;
+
+    let pdfium = pdfium()?;
+    let rendered = pdfium.load_pdf_from_byte_slice(&bytes, None)?;
+
+    for (page_id, (index, options)) in targets {
+        let page = rendered.pages().get(index as u16)?;
+        let image = render(&page, options)?;
+        replace_page(document, page_id, image)?;
+    }
+
+    Ok(())
+}
+
+fn render(page: &PdfPage, options: &RasterizeOptions) -> Result<Stream> {
+    let image = render_page(page, options.dpi)?;
+
+    Ok(match options.color_mode {
+        ColorMode::Color => jpeg_image(DynamicImage::ImageRgb8(image.into_rgb8()), "DeviceRGB")?,
+        ColorMode::Grayscale => {
+            jpeg_image(DynamicImage::ImageLuma8(image.into_luma8()), "DeviceGray")?
+        }
+        ColorMode::Monochrome => monochrome_image(image),
+    })
+}
+
+/// Renders a page, with its annotations and form fields, at a resolution in dots per inch.
+pub(super) fn render_page(page: &PdfPage, dpi: f32) -> Result<DynamicImage> {
+    let scale = dpi / 72.0;
+    let (width, height) = (page.width().value * scale, page.height().value * scale);
+    if width.max(height) > MAX_TILE_RENDER_SIZE as f32 {
+        return Err(anyhow!(
+            "Requested resolution is too large: {}x{} pixels",
+            width.round(),
+            height.round()
+        ));
+    }
+
+    let render_config = PdfRenderConfig::new()
+        .scale_page_by_factor(scale)
+        .render_annotations(true)
+        .render_form_data(true);
+
+    Ok(page.render_with_config(&render_config)?.as_image())
+}
+
+fn jpeg_image(image: DynamicImage, color_space: &str) -> Result<Stream> {
+    let mut bytes = Vec::new();
+    JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY).encode_image(&image)?;
+
+    Ok(Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => image.width(),
+            "Height" => image.height(),
+            "ColorSpace" => color_space,
+            "BitsPerComponent" => 8,
+            "Filter" => "DCTDecode",
+        },
+        bytes,
+    ))
+}
+
+/// A 1 bit per pixel image, left for the export to compress.
+fn monochrome_image(image: DynamicImage) -> Stream {
+    let image = image.into_luma8();
+    let (width, height) = image.dimensions();
+
+    Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => width,
+            "Height" => height,
+            "ColorSpace" => "DeviceGray",
+            "BitsPerComponent" => 1,
+        },
+        pack_bits(&image),
+    )
+}
+
+/// The pixels of an image at 1 bit each, white when set, with rows starting on a byte.
+pub(super) fn pack_bits(image: &GrayImage) -> Vec<u8> {
+    let row_bytes = (image.width() as usize).div_ceil(8);
+    let mut data = vec![0u8; row_bytes * image.height() as usize];
+
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if pixel.0[0] >= 128 {
+            data[y as usize * row_bytes + x as usize / 8] |= 0x80 >> (x % 8);
+        }
+    }
+
+    data
+}
+
+/// Replaces the content of a page with an image covering its visible area.
+fn replace_page(document: &mut Document, page_id: ObjectId, image: Stream) -> Result<()> {
+    let visible_box = inherited(document, page_id, b"CropBox")
+        .or_else(|| inherited(document, page_id, b"MediaBox"))
+        .ok_or_else(|| anyhow!("Invalid PDF: page without a MediaBox"))?;
+    let [x0, y0, x1, y1] = visible_box;
+
+    let image_id = document.add_object(image);
+    let content = format!(
+        "q {} 0 0 {} {} {} cm /Raster0 Do Q",
+        x1 - x0,
+        y1 - y0,
+        x0,
+        y0
+    );
+    let content_id = document.add_object(Stream::new(dictionary! {}, content.into_bytes()));
+
+    let media_box = inherited(document, page_id, b"MediaBox");
+    let rotate = document
+        .get_dictionary(page_id)?
+        .get(b"Rotate")
+        .ok()
+        .cloned();
+
+    let page = document.get_dictionary_mut(page_id)?;
+    let removed = page
+        .iter()
+        .map(|(key, _)| key.clone())
+        .filter(|key| !KEPT_PAGE_KEYS.contains(&key.as_slice()))
+        .collect::<Vec<_>>();
+    for key in removed {
+        page.remove(&key);
+    }
+
+    // Inherited entries are lost with the content they applied to, set them on the page.
+    if let Some(media_box) = media_box {
+        page.set("MediaBox", media_box.map(Object::Real).to_vec());
+    }
+    if let Some(rotate) = rotate {
+        page.set("Rotate", rotate);
+    }
+    page.set("Contents", content_id);
+    page.set(
+        "Resources",
+        dictionary! {
+            "XObject" => dictionary! { "Raster0" => image_id },
+        },
+    );
+
+    Ok(())
+}
+
+/// A rectangle of the page, or of its closest ancestor defining it.
+fn inherited(document: &Document, page_id: ObjectId, key: &[u8]) -> Option<[f32; 4]> {
+    let mut node = document.get_dictionary(page_id).ok()?;
+
+    loop {
+        if let Ok(value) = node.get(key) {
+            let (_, value) = document.dereference(value).ok()?;
+            let values = value
+                .as_array()
+                .ok()?
+                .iter()
+                .map(|value| value.as_float().ok())
+                .collect::<Option<Vec<_>>>()?;
+            let [x0, y0, x1, y1] = values.try_into().ok()?;
+
+            return Some([x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)]);
+        }
+
+        let parent = node.get(b"Parent").and_then(Object::as_reference).ok()?;
+        node = document.get_dictionary(parent).ok()?;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::project::{Project, SourceFile};
+    use std::path::PathBuf;
+
+    fn rasterized(page_index: usize, color_mode: ColorMode) -> Selector {
+        let mut selector = Selector::new(0, page_index);
+        selector.rasterize = Some(RasterizeOptions {
+            dpi: 72.0,
+            color_mode,
+        });
+        selector
+    }
+
+    fn page_image(document: &Document, page_number: u32) -> Stream {
+        let page_id = *document.get_pages().get(&page_number).unwrap();
+        let (resources, _) = document.get_page_resources(page_id).unwrap();
+        let image_id = resources
+            .unwrap()
+            .get_deref(b"XObject", document)
+            .and_then(Object::as_dict)
+            .and_then(|xobjects| xobjects.get(b"Raster0"))
+            .and_then(Object::as_reference)
+            .unwrap();
+
+        document
+            .get_object(image_id)
+            .unwrap()
+            .as_stream()
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn test_rasterize_page() {
+        let project = Project {
+            source_files: vec![SourceFile::open(&PathBuf::from("test/basic.pdf"), None).unwrap()],
+        };
+        let selectors = vec![rasterized(0, ColorMode::Color), Selector::new(0, 1)];
+
+        let (document, _) = project.export(&selectors).unwrap();
+
+        assert!(!document.extract_text(&[1]).unwrap().contains('1'));
+        assert!(document.extract_text(&[2]).unwrap().contains('2'));
+
+        let image = page_image(&document, 1);
+        assert_eq!(image.dict.get(b"Width").unwrap().as_i64().unwrap(), 612);
+        assert_eq!(image.dict.get(b"Height").unwrap().as_i64().unwrap(), 792);
+        assert_eq!(
+            image.dict.get(b"Filter").unwrap().as_name().unwrap(),
+            b"DCTDecode"
+        );
+
+        let page_id = *document.get_pages().get(&1).unwrap();
+        let media_box = document
+            .get_dictionary(page_id)
+            .unwrap()
+            .get(b"MediaBox")
+            .unwrap();
+        assert_eq!(
+            media_box
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|value| value.as_float().unwrap())
+                .collect::<Vec<_>>(),
+            vec![0.0, 0.0, 612.0, 792.0]
+        );
+    }
+
+    #[test]
+    fn test_rasterize_monochrome() {
+        let project = Project {
+            source_files: vec![SourceFile::open(&PathBuf::from("test/paysage.pdf"), None).unwrap()],
+        };
+
+        let (document, _) = project
+            .export(&[rasterized(0, ColorMode::Monochrome)])
+            .unwrap();
+
+        let image = page_image(&document, 1);
+        assert_eq!(
+            image
+                .dict
+                .get(b"BitsPerComponent")
+                .unwrap()
+                .as_i64()
+                .unwrap(),
+            1
+        );
+        assert_eq!(image.dict.get(b"Width").unwrap().as_i64().unwrap(), 792);
+        assert_eq!(
+            image.decompressed_content().unwrap().len(),
+            792usize.div_ceil(8) * 612
+        );
+    }
+}
@@ -0,0 +1,1606 @@
+use super::{pdfium, Project, SourceFile};
+use anyhow::{anyhow, Result};
+use lopdf::content::{Content, Operation};
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+use pdfium_render::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Forms nested deeper than this are removed from redacted regions rather than rewritten.
+pub(super) const MAX_FORM_DEPTH: usize = 16;
+
+/// Form fields nested deeper than this are kept as they are, their tree may be looping.
+const MAX_FIELD_DEPTH: usize = 32;
+
+/// Glyph boxes are approximated from the font size, fonts rarely reach outside these bounds.
+const GLYPH_ASCENT: f32 = 0.8;
+const GLYPH_DESCENT: f32 = -0.2;
+
+/// Overlap below which a glyph merely touching a region is kept, in points.
+const TOLERANCE: f32 = 0.1;
+
+/// A region of a source page whose content is removed on export and covered with a box.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct Redaction {
+    page_index: usize,
+    /// Position and size of the region, in the default user space of the unrotated page, the
+    /// coordinates its content uses. They only start at the bottom-left corner of the page when
+    /// its media box does.
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    /// RGB components of the box, between 0 and 1. Black by default.
+    #[serde(default)]
+    color: [f32; 3],
+}
+
+impl Redaction {
+    fn rect(&self) -> Rect {
+        Rect {
+            x0: self.x.min(self.x + self.width),
+            y0: self.y.min(self.y + self.height),
+            x1: self.x.max(self.x + self.width),
+            y1: self.y.max(self.y + self.height),
+        }
+    }
+}
+
+impl Project {
+    pub fn add_redactions(
+        &mut self,
+        source_file_index: usize,
+        redactions: Vec<Redaction>,
+    ) -> Result<()> {
+        let source_file = self
+            .source_files
+            .get_mut(source_file_index)
+            .ok_or_else(|| anyhow!("Invalid source_file_index: {}", source_file_index))?;
+
+        if let Some(redaction) = redactions
+            .iter()
+            .find(|redaction| redaction.page_index >= source_file.pages.len())
+        {
+            return Err(anyhow!("Invalid page_index: {}", redaction.page_index));
+        }
+
+        source_file.redactions.extend(
+            redactions
+                .into_iter()
+                .filter(|redaction| redaction.width != 0.0 && redaction.height != 0.0),
+        );
+
+        Ok(())
+    }
+
+    /// Removes the redactions of a page, or of the whole source file when `page_index` is `None`.
+    pub fn clear_redactions(
+        &mut self,
+        source_file_index: usize,
+        page_index: Option<usize>,
+    ) -> Result<()> {
+        let source_file = self
+            .source_files
+            .get_mut(source_file_index)
+            .ok_or_else(|| anyhow!("Invalid source_file_index: {}", source_file_index))?;
+
+        source_file
+            .redactions
+            .retain(|redaction| page_index.is_some_and(|index| redaction.page_index != index));
+
+        Ok(())
+    }
+
+    /// Marks every match of `query` in a source file for redaction. Returns the added
+    /// redactions, one per line of each match.
+    pub fn redact_text(
+        &mut self,
+        source_file_index: usize,
+        query: &str,
+        color: [f32; 3],
+    ) -> Result<Vec<Redaction>> {
+        let source_file = self
+            .source_files
+            .get(source_file_index)
+            .ok_or_else(|| anyhow!("Invalid source_file_index: {}", source_file_index))?;
+
+        let pdfium = pdfium()?;
+        let bytes = source_file.to_bytes()?;
+        let document = pdfium.load_pdf_from_byte_slice(&bytes, None)?;
+
+        let mut redactions = Vec::new();
+        for (page_index, page) in document.pages().iter().enumerate() {
+            let text = page.text()?;
+            let search = text.search(query, &PdfSearchOptions::new())?;
+
+            for segments in search.iter(PdfSearchDirection::SearchForward) {
+                for segment in segments.iter() {
+                    let bounds = segment.bounds();
+                    redactions.push(Redaction {
+                        page_index,
+                        x: bounds.left().value,
+                        y: bounds.bottom().value,
+                        width: bounds.width().value,
+                        height: bounds.height().value,
+                        color,
+                    });
+                }
+            }
+        }
+
+        self.add_redactions(source_file_index, redactions.clone())?;
+
+        Ok(redactions)
+    }
+}
+
+impl SourceFile {
+    pub(super) fn page_redactions(&self, page_index: usize) -> Vec<Redaction> {
+        self.redactions
+            .iter()
+            .filter(|redaction| redaction.page_index == page_index)
+            .copied()
+            .collect()
+    }
+}
+
+/// Removes the content of a page under the redacted regions: glyphs are dropped from the text,
+/// paths overlapping the regions are removed, image pixels are cleared, and a box is drawn over
+/// each region. Filled rectangles enclosing a whole region, like backgrounds, are kept.
+///
+/// The page gets its own content stream and resources, objects shared with other pages are
+/// copied before being changed.
+pub(super) fn redact_page(
+    document: &mut Document,
+    page_id: ObjectId,
+    redactions: &[Redaction],
+) -> Result<()> {
+    let regions = redactions.iter().map(Redaction::rect).collect::<Vec<_>>();
+
+    let content = document.get_page_content(page_id)?;
+    let mut resources = page_resources(document, page_id);
+
+    let mut redactor = Redactor {
+        document,
+        regions: &regions,
+    };
+    let redacted = redactor.redact_content(&content, &mut resources, Matrix::IDENTITY, 0)?;
+
+    // Balance the original content so the boxes are drawn in the default user space.
+    let mut content = b"q\n".to_vec();
+    content.extend(redacted);
+    content.extend(b"\nQ\n");
+    for redaction in redactions {
+        let [r, g, b] = redaction.color;
+        let rect = redaction.rect();
+        content.extend(
+            format!(
+                "q {} {} {} rg {} {} {} {} re f Q\n",
+                r,
+                g,
+                b,
+                rect.x0,
+                rect.y0,
+                rect.x1 - rect.x0,
+                rect.y1 - rect.y0
+            )
+            .as_bytes(),
+        );
+    }
+
+    let content_id = document.add_object(Stream::new(Dictionary::new(), content));
+    let annotations = kept_annotations(document, page_id, &regions);
+
+    let page = document.get_dictionary_mut(page_id)?;
+    page.set("Contents", content_id);
+    page.set("Resources", resources);
+    if let Some((annotations, removed)) = annotations {
+        page.set("Annots", annotations);
+        remove_form_fields(document, &removed);
+    }
+
+    Ok(())
+}
+
+/// The resources of a page, inherited from its ancestors when the page has none.
+pub(super) fn page_resources(document: &Document, page_id: ObjectId) -> Dictionary {
+    let Ok((direct, ids)) = document.get_page_resources(page_id) else {
+        return Dictionary::new();
+    };
+
+    direct
+        .or_else(|| ids.first().and_then(|id| document.get_dictionary(*id).ok()))
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Annotations of the page that don't overlap a region, and the ids of the removed ones. `None`
+/// when all of them are kept.
+fn kept_annotations(
+    document: &Document,
+    page_id: ObjectId,
+    regions: &[Rect],
+) -> Option<(Vec<Object>, BTreeSet<ObjectId>)> {
+    let annotations = document
+        .get_dictionary(page_id)
+        .and_then(|page| page.get(b"Annots"))
+        .and_then(|annotations| document.dereference(annotations))
+        .and_then(|(_, annotations)| annotations.as_array())
+        .ok()?;
+
+    let (kept, removed): (Vec<_>, Vec<_>) = annotations.iter().cloned().partition(|annotation| {
+        let rect = document
+            .dereference(annotation)
+            .and_then(|(_, annotation)| annotation.as_dict())
+            .and_then(|annotation| annotation.get(b"Rect"))
+            .and_then(Object::as_array)
+            .ok()
+            .and_then(|rect| Rect::from_array(rect));
+
+        !rect.is_some_and(|rect| regions.iter().any(|region| rect.overlaps(region)))
+    });
+
+    let removed = removed
+        .iter()
+        .filter_map(|annotation| annotation.as_reference().ok())
+        .collect::<BTreeSet<_>>();
+    (kept.len() != annotations.len()).then_some((kept, removed))
+}
+
+/// Removes the widgets in `removed` from the fields of the document's form, so their values
+/// aren't exported. Fields left without widgets are removed too.
+fn remove_form_fields(document: &mut Document, removed: &BTreeSet<ObjectId>) {
+    let form_id = match document
+        .catalog()
+        .and_then(|catalog| catalog.get(b"AcroForm"))
+    {
+        Ok(Object::Reference(id)) => Some(*id),
+        Ok(Object::Dictionary(_)) => None,
+        _ => return,
+    };
+    let form = match form_id {
+        Some(id) => document.get_dictionary(id),
+        None => document
+            .catalog()
+            .and_then(|catalog| catalog.get(b"AcroForm"))
+            .and_then(Object::as_dict),
+    };
+    let Ok(fields) = form
+        .and_then(|form| form.get(b"Fields"))
+        .and_then(|fields| document.dereference(fields))
+        .and_then(|(_, fields)| fields.as_array())
+        .cloned()
+    else {
+        return;
+    };
+
+    let fields = remaining_fields(document, fields, removed, 0);
+
+    let form = match form_id {
+        Some(id) => document.get_dictionary_mut(id),
+        None => document
+            .catalog_mut()
+            .and_then(|catalog| catalog.get_mut(b"AcroForm"))
+            .and_then(Object::as_dict_mut),
+    };
+    if let Ok(form) = form {
+        form.set("Fields", fields);
+    }
+}
+
+/// The fields still having widgets once the `removed` ones are, updating their `/Kids`.
+fn remaining_fields(
+    document: &mut Document,
+    fields: Vec<Object>,
+    removed: &BTreeSet<ObjectId>,
+    depth: usize,
+) -> Vec<Object> {
+    fields
+        .into_iter()
+        .filter(|field| {
+            let Ok(id) = field.as_reference() else {
+                return true;
+            };
+            if removed.contains(&id) {
+                return false;
+            }
+
+            let Ok(kids) = document
+                .get_dictionary(id)
+                .and_then(|field| field.get(b"Kids"))
+                .and_then(Object::as_array)
+                .cloned()
+            else {
+                return true;
+            };
+            if kids.is_empty() || depth >= MAX_FIELD_DEPTH {
+                return true;
+            }
+
+            let kids = remaining_fields(document, kids, removed, depth + 1);
+            if kids.is_empty() {
+                return false;
+            }
+            if let Ok(field) = document.get_dictionary_mut(id) {
+                field.set("Kids", kids);
+            }
+            true
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rect {
+    x0: f32,
+    y0: f32,
+    x1: f32,
+    y1: f32,
+}
+
+impl Rect {
+    fn from_array(values: &[Object]) -> Option<Self> {
+        let values = values
+            .iter()
+            .map(|value| value.as_float().ok())
+            .collect::<Option<Vec<_>>>()?;
+
+        match values.as_slice() {
+            &[x0, y0, x1, y1] => Some(Self::bounding(&[(x0, y0), (x1, y1)])),
+            _ => None,
+        }
+    }
+
+    fn bounding(points: &[(f32, f32)]) -> Self {
+        points.iter().fold(
+            Self {
+                x0: f32::INFINITY,
+                y0: f32::INFINITY,
+                x1: f32::NEG_INFINITY,
+                y1: f32::NEG_INFINITY,
+            },
+            |rect, &(x, y)| Self {
+                x0: rect.x0.min(x),
+                y0: rect.y0.min(y),
+                x1: rect.x1.max(x),
+                y1: rect.y1.max(y),
+            },
+        )
+    }
+
+    fn corners(&self) -> [(f32, f32); 4] {
+        [
+            (self.x0, self.y0),
+            (self.x1, self.y0),
+            (self.x0, self.y1),
+            (self.x1, self.y1),
+        ]
+    }
+
+    fn expand(&self, by: f32) -> Self {
+        Self {
+            x0: self.x0 - by,
+            y0: self.y0 - by,
+            x1: self.x1 + by,
+            y1: self.y1 + by,
+        }
+    }
+
+    fn overlaps(&self, other: &Rect) -> bool {
+        self.x0.max(other.x0) + TOLERANCE < self.x1.min(other.x1)
+            && self.y0.max(other.y0) + TOLERANCE < self.y1.min(other.y1)
+    }
+
+    fn contains(&self, other: &Rect) -> bool {
+        self.x0 <= other.x0 && self.y0 <= other.y0 && self.x1 >= other.x1 && self.y1 >= other.y1
+    }
+}
+
+/// The area of a path made of a single rectangle with horizontal and vertical sides, from its
+/// construction operators and their `points` in device space.
+fn filled_rectangle(path: &[Operation], points: &[(f32, f32)]) -> Option<Rect> {
+    let operators = path
+        .iter()
+        .map(|operation| operation.operator.as_str())
+        .filter(|operator| !matches!(*operator, "h" | "W" | "W*"))
+        .collect::<Vec<_>>();
+    let single_shape = match operators.as_slice() {
+        ["re"] => true,
+        ["m", lines @ ..] => (3..=4).contains(&lines.len()) && lines.iter().all(|op| *op == "l"),
+        _ => false,
+    };
+    if !single_shape {
+        return None;
+    }
+
+    let bounds = Rect::bounding(points);
+    let near = |a: f32, b: f32| (a - b).abs() <= TOLERANCE;
+    let on_corner = |&(x, y): &(f32, f32)| {
+        (near(x, bounds.x0) || near(x, bounds.x1)) && (near(y, bounds.y0) || near(y, bounds.y1))
+    };
+    let axis_aligned = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .all(|(a, b)| near(a.0, b.0) || near(a.1, b.1));
+
+    (points.iter().all(on_corner) && axis_aligned).then_some(bounds)
+}
+
+/// An affine transformation, `[a b c d e f]` as in PDF operators.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Matrix([f32; 6]);
+
+impl Matrix {
+    pub(super) const IDENTITY: Matrix = Matrix([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    fn translate(x: f32, y: f32) -> Self {
+        Matrix([1.0, 0.0, 0.0, 1.0, x, y])
+    }
+
+    pub(super) fn from_operands(operands: &[Object]) -> Option<Self> {
+        let values = operands
+            .iter()
+            .map(|value| value.as_float().ok())
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Matrix(values.try_into().ok()?))
+    }
+
+    /// The transformation applying `self`, then `other`.
+    pub(super) fn then(&self, other: &Matrix) -> Self {
+        let [a1, b1, c1, d1, e1, f1] = self.0;
+        let [a2, b2, c2, d2, e2, f2] = other.0;
+
+        Matrix([
+            a1 * a2 + b1 * c2,
+            a1 * b2 + b1 * d2,
+            c1 * a2 + d1 * c2,
+            c1 * b2 + d1 * d2,
+            e1 * a2 + f1 * c2 + e2,
+            e1 * b2 + f1 * d2 + f2,
+        ])
+    }
+
+    fn invert(&self) -> Option<Self> {
+        let [a, b, c, d, e, f] = self.0;
+        let det = a * d - b * c;
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+
+        Some(Matrix([
+            d / det,
+            -b / det,
+            -c / det,
+            a / det,
+            (c * f - d * e) / det,
+            (b * e - a * f) / det,
+        ]))
+    }
+
+    /// Lengths of the sides of the unit square once transformed.
+    pub(super) fn scale(&self) -> (f32, f32) {
+        let [a, b, c, d, _, _] = self.0;
+        (a.hypot(b), c.hypot(d))
+    }
+
+    fn apply(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let [a, b, c, d, e, f] = self.0;
+        (a * x + c * y + e, b * x + d * y + f)
+    }
+
+    fn apply_rect(&self, rect: &Rect) -> Rect {
+        Rect::bounding(&rect.corners().map(|corner| self.apply(corner)))
+    }
+}
+
+/// The parts of the graphics state the redaction needs to locate content.
+#[derive(Debug, Clone)]
+struct State {
+    ctm: Matrix,
+    line_width: f32,
+    font: Option<Vec<u8>>,
+    font_size: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    horizontal_scaling: f32,
+    leading: f32,
+    rise: f32,
+}
+
+impl State {
+    fn new(ctm: Matrix) -> Self {
+        Self {
+            ctm,
+            line_width: 1.0,
+            font: None,
+            font_size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scaling: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+        }
+    }
+}
+
+/// Glyph widths of a font, in thousandths of the font size.
+struct FontMetrics {
+    two_byte_codes: bool,
+    first_char: u32,
+    widths: Vec<f32>,
+    cid_widths: BTreeMap<u32, f32>,
+    default_width: f32,
+}
+
+impl FontMetrics {
+    /// Used when the font can't be read, the glyphs are still located roughly.
+    const FALLBACK: FontMetrics = FontMetrics {
+        two_byte_codes: false,
+        first_char: 0,
+        widths: Vec::new(),
+        cid_widths: BTreeMap::new(),
+        default_width: 500.0,
+    };
+
+    fn read(document: &Document, font: &Dictionary) -> Self {
+        let number = |object: &Object| {
+            document
+                .dereference(object)
+                .and_then(|(_, object)| object.as_float())
+                .ok()
+        };
+        let array = |dict: &Dictionary, key: &[u8]| {
+            dict.get(key)
+                .and_then(|object| document.dereference(object))
+                .and_then(|(_, object)| object.as_array())
+                .map(|array| array.to_vec())
+                .unwrap_or_default()
+        };
+
+        if font.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Type0") {
+            let descendant = array(font, b"DescendantFonts")
+                .first()
+                .and_then(|descendant| document.dereference(descendant).ok())
+                .and_then(|(_, descendant)| descendant.as_dict().ok())
+                .cloned()
+                .unwrap_or_default();
+
+            let mut cid_widths = BTreeMap::new();
+            let mut entries = array(&descendant, b"W").into_iter();
+            while let Some(first) = entries.next().as_ref().and_then(number) {
+                match entries.next() {
+                    Some(Object::Array(widths)) => {
+                        for (offset, width) in widths.iter().enumerate() {
+                            if let Some(width) = number(width) {
+                                cid_widths.insert(first as u32 + offset as u32, width);
+                            }
+                        }
+                    }
+                    Some(last) => {
+                        let (Some(last), Some(width)) =
+                            (number(&last), entries.next().as_ref().and_then(number))
+                        else {
+                            break;
+                        };
+                        for cid in first as u32..=last as u32 {
+                            cid_widths.insert(cid, width);
+                        }
+                    }
+                    None => break,
+                }
+            }
+
+            return Self {
+                two_byte_codes: true,
+                first_char: 0,
+                widths: Vec::new(),
+                cid_widths,
+                default_width: descendant
+                    .get(b"DW")
+                    .ok()
+                    .and_then(number)
+                    .unwrap_or(1000.0),
+            };
+        }
+
+        // Type 3 glyphs are measured in their own glyph space.
+        let scale = array(font, b"FontMatrix")
+            .first()
+            .and_then(number)
+            .map_or(1.0, |scale| scale * 1000.0);
+
+        let missing_width = font
+            .get(b"FontDescriptor")
+            .and_then(|descriptor| document.dereference(descriptor))
+            .and_then(|(_, descriptor)| descriptor.as_dict())
+            .and_then(|descriptor| descriptor.get(b"MissingWidth"))
+            .ok()
+            .and_then(number);
+
+        Self {
+            two_byte_codes: false,
+            first_char: font.get(b"FirstChar").ok().and_then(number).unwrap_or(0.0) as u32,
+            widths: array(font, b"Widths")
+                .iter()
+                .map(|width| number(width).unwrap_or(0.0) * scale)
+                .collect(),
+            cid_widths: BTreeMap::new(),
+            default_width: missing_width.map_or(Self::FALLBACK.default_width, |w| w * scale),
+        }
+    }
+
+    fn codes(&self, bytes: &[u8]) -> Vec<Vec<u8>> {
+        let size = if self.two_byte_codes { 2 } else { 1 };
+        bytes.chunks(size).map(<[u8]>::to_vec).collect()
+    }
+
+    fn width(&self, code: &[u8]) -> f32 {
+        let code = code
+            .iter()
+            .fold(0u32, |code, byte| code << 8 | *byte as u32);
+
+        if self.two_byte_codes {
+            return self
+                .cid_widths
+                .get(&code)
+                .copied()
+                .unwrap_or(self.default_width);
+        }
+
+        code.checked_sub(self.first_char)
+            .and_then(|index| self.widths.get(index as usize))
+            .copied()
+            .unwrap_or(self.default_width)
+    }
+}
+
+struct Redactor<'a> {
+    document: &'a mut Document,
+    regions: &'a [Rect],
+}
+
+impl Redactor<'_> {
+    fn touches(&self, rect: &Rect) -> bool {
+        self.regions.iter().any(|region| rect.overlaps(region))
+    }
+
+    /// Rewrites a content stream drawn with `ctm`, adding the XObjects it creates to
+    /// `resources`.
+    fn redact_content(
+        &mut self,
+        content: &[u8],
+        resources: &mut Dictionary,
+        ctm: Matrix,
+        depth: usize,
+    ) -> Result<Vec<u8>> {
+        let content = Content::decode(content)
+            .map_err(|e| anyhow!("Couldn't read the page content to redact it: {}", e))?;
+
+        let mut fonts = BTreeMap::new();
+        let mut output = Vec::new();
+        let mut stack = Vec::new();
+        let mut state = State::new(ctm);
+        let mut text_matrix = Matrix::IDENTITY;
+        let mut line_matrix = Matrix::IDENTITY;
+        let mut path = Vec::new();
+        let mut path_points = Vec::new();
+        let mut clipping = false;
+        let mut replaced = Vec::new();
+
+        for operation in content.operations {
+            let operands = &operation.operands;
+            let float = |index: usize| {
+                operands
+                    .get(index)
+                    .and_then(|operand| operand.as_float().ok())
+                    .unwrap_or(0.0)
+            };
+
+            match operation.operator.as_str() {
+                "q" => stack.push(state.clone()),
+                "Q" => state = stack.pop().unwrap_or_else(|| State::new(ctm)),
+                "cm" => {
+                    if let Some(matrix) = Matrix::from_operands(operands) {
+                        state.ctm = matrix.then(&state.ctm);
+                    }
+                }
+                "w" => state.line_width = float(0),
+                "m" | "l" => {
+                    path_points.push(state.ctm.apply((float(0), float(1))));
+                    path.push(operation);
+                    continue;
+                }
+                "c" | "v" | "y" => {
+                    for index in (0..operands.len()).step_by(2) {
+                        path_points.push(state.ctm.apply((float(index), float(index + 1))));
+                    }
+                    path.push(operation);
+                    continue;
+                }
+                "re" => {
+                    let (x, y, width, height) = (float(0), float(1), float(2), float(3));
+                    // Around the rectangle, so its shape is kept for `filled_rectangle`.
+                    for corner in [
+                        (x, y),
+                        (x + width, y),
+                        (x + width, y + height),
+                        (x, y + height),
+                    ] {
+                        path_points.push(state.ctm.apply(corner));
+                    }
+                    path.push(operation);
+                    continue;
+                }
+                "h" => {
+                    path.push(operation);
+                    continue;
+                }
+                "W" | "W*" => {
+                    clipping = true;
+                    path.push(operation);
+                    continue;
+                }
+                "S" | "s" | "f" | "F" | "f*" | "B" | "B*" | "b" | "b*" | "n" => {
+                    let bounds = Rect::bounding(&path_points).expand(state.line_width / 2.0);
+                    // Only the inside of a filled rectangle can surround a region without
+                    // drawing anything in it.
+                    let background = match operation.operator.as_str() {
+                        "f" | "F" | "f*" => filled_rectangle(&path, &path_points),
+                        "B" | "B*" | "b" | "b*" => filled_rectangle(&path, &path_points)
+                            .map(|rect| rect.expand(-state.line_width / 2.0)),
+                        _ => None,
+                    };
+                    let removed = operation.operator != "n"
+                        && self.regions.iter().any(|region| {
+                            bounds.overlaps(region)
+                                && !background.is_some_and(|background| background.contains(region))
+                        });
+
+                    if !removed {
+                        output.append(&mut path);
+                        output.push(operation);
+                    } else if clipping {
+                        output.append(&mut path);
+                        output.push(Operation::new("n", vec![]));
+                    }
+
+                    path.clear();
+                    path_points.clear();
+                    clipping = false;
+                    continue;
+                }
+                "BT" => {
+                    text_matrix = Matrix::IDENTITY;
+                    line_matrix = Matrix::IDENTITY;
+                }
+                "Tf" => {
+                    state.font = operands
+                        .first()
+                        .and_then(|name| name.as_name().ok())
+                        .map(<[u8]>::to_vec);
+                    state.font_size = float(1);
+                }
+                "Tc" => state.char_spacing = float(0),
+                "Tw" => state.word_spacing = float(0),
+                "Tz" => state.horizontal_scaling = float(0) / 100.0,
+                "TL" => state.leading = float(0),
+                "Ts" => state.rise = float(0),
+                "Td" | "TD" => {
+                    if operation.operator == "TD" {
+                        state.leading = -float(1);
+                    }
+                    line_matrix = Matrix::translate(float(0), float(1)).then(&line_matrix);
+                    text_matrix = line_matrix;
+                }
+                "Tm" => {
+                    if let Some(matrix) = Matrix::from_operands(operands) {
+                        line_matrix = matrix;
+                        text_matrix = matrix;
+                    }
+                }
+                "T*" => {
+                    line_matrix = Matrix::translate(0.0, -state.leading).then(&line_matrix);
+                    text_matrix = line_matrix;
+                }
+                "Tj" | "TJ" | "'" | "\"" => {
+                    // The string shown, after the spacings of `"`.
+                    let text = match operation.operator.as_str() {
+                        "\"" => operands.get(2),
+                        _ => operands.first(),
+                    };
+                    let elements = match operation.operator.as_str() {
+                        "TJ" => text
+                            .and_then(|array| array.as_array().ok())
+                            .cloned()
+                            .unwrap_or_default(),
+                        _ => text.cloned().into_iter().collect(),
+                    };
+
+                    if operation.operator == "\"" {
+                        state.word_spacing = float(0);
+                        state.char_spacing = float(1);
+                        output.push(Operation::new("Tw", vec![state.word_spacing.into()]));
+                        output.push(Operation::new("Tc", vec![state.char_spacing.into()]));
+                    }
+                    if matches!(operation.operator.as_str(), "'" | "\"") {
+                        line_matrix = Matrix::translate(0.0, -state.leading).then(&line_matrix);
+                        text_matrix = line_matrix;
+                        output.push(Operation::new("T*", vec![]));
+                    }
+
+                    let metrics = fonts.entry(state.font.clone()).or_insert_with(|| {
+                        self.font(resources, state.font.as_deref())
+                            .unwrap_or(FontMetrics::FALLBACK)
+                    });
+                    let (elements, changed) =
+                        self.redact_text(elements, metrics, &state, &mut text_matrix);
+
+                    output.push(match (changed, operation.operator.as_str()) {
+                        (false, "Tj" | "TJ") => operation,
+                        (false, _) => Operation::new("Tj", text.cloned().into_iter().collect()),
+                        (true, _) => Operation::new("TJ", vec![Object::Array(elements)]),
+                    });
+                    continue;
+                }
+                "Do" => {
+                    let Some(name) = operands.first().and_then(|name| name.as_name().ok()) else {
+                        continue;
+                    };
+                    let redacted = self.redact_xobject(name, resources, &state, depth)?;
+                    let drawn = redacted
+                        .as_ref()
+                        .and_then(|redacted| redacted.operands.first()?.as_name().ok());
+                    if drawn != Some(name) {
+                        replaced.push(name.to_vec());
+                    }
+                    output.extend(redacted);
+                    continue;
+                }
+                "BI" => {
+                    let bounds = state.ctm.apply_rect(&UNIT_SQUARE);
+                    if self.touches(&bounds) {
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+
+            output.push(operation);
+        }
+
+        self.remove_replaced_xobjects(resources, &replaced, &output);
+        encode(&output)
+    }
+
+    fn font(&self, resources: &Dictionary, name: Option<&[u8]>) -> Option<FontMetrics> {
+        let font = self
+            .resource(resources, b"Font", name?)
+            .and_then(|font| font.as_dict().ok())?;
+
+        Some(FontMetrics::read(self.document, font))
+    }
+
+    fn resource<'b>(
+        &'b self,
+        resources: &'b Dictionary,
+        category: &[u8],
+        name: &[u8],
+    ) -> Option<&'b Object> {
+        let category = resources.get(category).ok()?;
+        let (_, category) = self.document.dereference(category).ok()?;
+        let resource = category.as_dict().ok()?.get(name).ok()?;
+
+        self.document
+            .dereference(resource)
+            .ok()
+            .map(|(_, resource)| resource)
+    }
+
+    /// Removes glyphs overlapping the regions from the strings of a text showing operator,
+    /// replacing them with spacing so the remaining glyphs stay in place. Advances the text
+    /// matrix past the text.
+    fn redact_text(
+        &self,
+        elements: Vec<Object>,
+        metrics: &FontMetrics,
+        state: &State,
+        text_matrix: &mut Matrix,
+    ) -> (Vec<Object>, bool) {
+        let size = state.font_size;
+        let scaling = state.horizontal_scaling;
+        let mut output = Vec::new();
+        let mut changed = false;
+
+        for element in elements {
+            let (bytes, format) = match element {
+                Object::String(bytes, format) => (bytes, format),
+                adjustment => {
+                    let offset = adjustment.as_float().unwrap_or(0.0);
+                    *text_matrix =
+                        Matrix::translate(-offset / 1000.0 * size * scaling, 0.0).then(text_matrix);
+                    output.push(adjustment);
+                    continue;
+                }
+            };
+
+            let mut kept = Vec::new();
+            for code in metrics.codes(&bytes) {
+                let width = metrics.width(&code) / 1000.0 * size;
+                let word_spacing = if code == [b' '] {
+                    state.word_spacing
+                } else {
+                    0.0
+                };
+                let advance = (width + state.char_spacing + word_spacing) * scaling;
+
+                let glyph = Rect {
+                    x0: 0.0,
+                    y0: state.rise + GLYPH_DESCENT * size,
+                    x1: width * scaling,
+                    y1: state.rise + GLYPH_ASCENT * size,
+                };
+                let bounds = text_matrix.then(&state.ctm).apply_rect(&glyph);
+
+                if self.touches(&bounds) {
+                    changed = true;
+                    if !kept.is_empty() {
+                        output.push(Object::String(std::mem::take(&mut kept), format));
+                    }
+                    if size * scaling != 0.0 {
+                        output.push(Object::Real(-advance * 1000.0 / (size * scaling)));
+                    }
+                } else {
+                    kept.extend(code);
+                }
+
+                *text_matrix = Matrix::translate(advance, 0.0).then(text_matrix);
+            }
+
+            if !kept.is_empty() {
+                output.push(Object::String(kept, format));
+            }
+        }
+
+        (output, changed)
+    }
+
+    /// Returns the operation drawing the XObject once redacted, or `None` when it must not be
+    /// drawn at all.
+    fn redact_xobject(
+        &mut self,
+        name: &[u8],
+        resources: &mut Dictionary,
+        state: &State,
+        depth: usize,
+    ) -> Result<Option<Operation>> {
+        let unchanged = Operation::new("Do", vec![Object::Name(name.to_vec())]);
+
+        let Some(Object::Stream(xobject)) = self.resource(resources, b"XObject", name).cloned()
+        else {
+            return Ok(Some(unchanged));
+        };
+
+        let redacted = match xobject.dict.get(b"Subtype").and_then(Object::as_name) {
+            Ok(b"Image") => {
+                if !self.touches(&state.ctm.apply_rect(&UNIT_SQUARE)) {
+                    return Ok(Some(unchanged));
+                }
+                match redact_image(self.document, xobject, &state.ctm, self.regions) {
+                    Some(image) => image,
+                    None => return Ok(None),
+                }
+            }
+            Ok(b"Form") => {
+                let matrix = xobject
+                    .dict
+                    .get(b"Matrix")
+                    .and_then(Object::as_array)
+                    .ok()
+                    .and_then(|matrix| Matrix::from_operands(matrix))
+                    .unwrap_or(Matrix::IDENTITY);
+                let ctm = matrix.then(&state.ctm);
+
+                let bounds = xobject
+                    .dict
+                    .get(b"BBox")
+                    .and_then(Object::as_array)
+                    .ok()
+                    .and_then(|bbox| Rect::from_array(bbox));
+                if bounds.is_some_and(|bounds| !self.touches(&ctm.apply_rect(&bounds))) {
+                    return Ok(Some(unchanged));
+                }
+                if depth >= MAX_FORM_DEPTH {
+                    return Ok(None);
+                }
+
+                self.redact_form(xobject, resources, ctm, depth)?
+            }
+            _ => return Ok(Some(unchanged)),
+        };
+
+        let id = self.document.add_object(redacted);
+        let name = self.add_xobject(resources, id);
+
+        Ok(Some(Operation::new("Do", vec![Object::Name(name)])))
+    }
+
+    fn redact_form(
+        &mut self,
+        mut form: Stream,
+        resources: &Dictionary,
+        ctm: Matrix,
+        depth: usize,
+    ) -> Result<Stream> {
+        let content = form
+            .decompressed_content()
+            .unwrap_or_else(|_| form.content.clone());
+
+        // Forms without resources use the ones of the content drawing them.
+        let mut form_resources = match form.dict.get(b"Resources") {
+            Ok(form_resources) => self
+                .document
+                .dereference(form_resources)
+                .and_then(|(_, form_resources)| form_resources.as_dict())
+                .cloned()
+                .unwrap_or_default(),
+            Err(_) => resources.clone(),
+        };
+
+        let content = self.redact_content(&content, &mut form_resources, ctm, depth + 1)?;
+
+        form.dict.remove(b"Filter");
+        form.dict.remove(b"DecodeParms");
+        form.dict.set("Resources", form_resources);
+
+        Ok(Stream::new(form.dict, content))
+    }
+
+    /// Removes the `replaced` XObjects the rewritten content no longer draws from `resources`,
+    /// so their unredacted versions aren't exported.
+    fn remove_replaced_xobjects(
+        &self,
+        resources: &mut Dictionary,
+        replaced: &[Vec<u8>],
+        operations: &[Operation],
+    ) {
+        let drawn = operations
+            .iter()
+            .filter(|operation| operation.operator == "Do")
+            .filter_map(|operation| operation.operands.first()?.as_name().ok())
+            .collect::<Vec<_>>();
+        let unused = replaced
+            .iter()
+            .filter(|name| !drawn.contains(&name.as_slice()))
+            .collect::<Vec<_>>();
+        if unused.is_empty() {
+            return;
+        }
+
+        let mut xobjects = resources
+            .get(b"XObject")
+            .and_then(|xobjects| self.document.dereference(xobjects))
+            .and_then(|(_, xobjects)| xobjects.as_dict())
+            .cloned()
+            .unwrap_or_default();
+        for name in unused {
+            xobjects.remove(name);
+        }
+        resources.set("XObject", xobjects);
+    }
+
+    /// Adds an XObject to `resources` under a new name, returning the name.
+    fn add_xobject(&self, resources: &mut Dictionary, id: ObjectId) -> Vec<u8> {
+        let mut xobjects = resources
+            .get(b"XObject")
+            .and_then(|xobjects| self.document.dereference(xobjects))
+            .and_then(|(_, xobjects)| xobjects.as_dict())
+            .cloned()
+            .unwrap_or_default();
+
+        let name = (0..)
+            .map(|index| format!("Redacted{}", index).into_bytes())
+            .find(|name| !xobjects.has(name))
+            .unwrap_or_default();
+
+        xobjects.set(name.clone(), id);
+        resources.set("XObject", xobjects);
+
+        name
+    }
+}
+
+/// Images are drawn in the unit square of their transformation.
+const UNIT_SQUARE: Rect = Rect {
+    x0: 0.0,
+    y0: 0.0,
+    x1: 1.0,
+    y1: 1.0,
+};
+
+/// Clears the pixels of an image drawn with `ctm` under the regions. Returns `None` when the
+/// image's encoding isn't supported, it must then be removed entirely.
+fn redact_image(
+    document: &Document,
+    mut image: Stream,
+    ctm: &Matrix,
+    regions: &[Rect],
+) -> Option<Stream> {
+    let inverse = ctm.invert()?;
+    let integer = |key: &[u8]| image.dict.get(key).and_then(Object::as_i64).ok();
+
+    let width = integer(b"Width")? as usize;
+    let height = integer(b"Height")? as usize;
+    let bits = integer(b"BitsPerComponent");
+    let image_mask = image
+        .dict
+        .get(b"ImageMask")
+        .and_then(Object::as_bool)
+        .unwrap_or(false);
+    let filters = image
+        .filters()
+        .map(|filters| filters.iter().map(|filter| filter.to_vec()).collect())
+        .unwrap_or_else(|_| Vec::new());
+
+    let (mut data, components, bits) = if filters == [b"DCTDecode".to_vec()] {
+        let decoded =
+            image::load_from_memory_with_format(&image.content, image::ImageFormat::Jpeg).ok()?;
+        let (data, color_space, components) = match decoded.color() {
+            image::ColorType::L8 => (decoded.into_luma8().into_raw(), "DeviceGray", 1),
+            _ => (decoded.into_rgb8().into_raw(), "DeviceRGB", 3),
+        };
+
+        image.dict.set("ColorSpace", color_space);
+        image.dict.set("BitsPerComponent", 8);
+        image.dict.remove(b"Decode");
+
+        (data, components, 8)
+    } else if image_mask {
+        (decoded_content(&image)?, 1, 1)
+    } else {
+        let components = color_components(document, image.dict.get(b"ColorSpace").ok()?)?;
+        (decoded_content(&image)?, components, bits? as usize)
+    };
+
+    let pixel_bits = components * bits;
+    let row_bytes = (width * pixel_bits).div_ceil(8);
+    if data.len() < row_bytes * height {
+        return None;
+    }
+
+    for region in regions {
+        let area = inverse.apply_rect(region);
+        let columns = pixel_range(area.x0, area.x1, width);
+        // Image rows go from the top down.
+        let rows = pixel_range(1.0 - area.y1, 1.0 - area.y0, height);
+
+        for row in rows {
+            let row = &mut data[row * row_bytes..(row + 1) * row_bytes];
+            for bit in columns.start * pixel_bits..columns.end * pixel_bits {
+                row[bit / 8] &= !(0x80 >> (bit % 8));
+            }
+        }
+    }
+
+    image.dict.remove(b"Filter");
+    image.dict.remove(b"DecodeParms");
+
+    Some(Stream::new(image.dict, data))
+}
+
+pub(super) fn decoded_content(stream: &Stream) -> Option<Vec<u8>> {
+    if stream.dict.has(b"Filter") {
+        stream.decompressed_content().ok()
+    } else {
+        Some(stream.content.clone())
+    }
+}
+
+/// Pixels covered by `start..end`, a range of the unit interval.
+fn pixel_range(start: f32, end: f32, size: usize) -> std::ops::Range<usize> {
+    let start = (start.max(0.0) * size as f32).floor() as usize;
+    let end = (end.min(1.0) * size as f32).ceil().max(0.0) as usize;
+
+    start.min(size)..end.min(size)
+}
+
+/// Number of color components of an image, for the color spaces images can be decoded with.
+pub(super) fn color_components(document: &Document, color_space: &Object) -> Option<usize> {
+    let (_, color_space) = document.dereference(color_space).ok()?;
+
+    match color_space {
+        Object::Name(name) => match name.as_slice() {
+            b"DeviceGray" | b"CalGray" => Some(1),
+            b"DeviceRGB" | b"CalRGB" | b"Lab" => Some(3),
+            b"DeviceCMYK" => Some(4),
+            _ => None,
+        },
+        Object::Array(array) => match array.first()?.as_name().ok()? {
+            b"Indexed" | b"Separation" | b"CalGray" => Some(1),
+            b"CalRGB" | b"Lab" => Some(3),
+            b"DeviceN" => Some(array.get(1)?.as_array().ok()?.len()),
+            b"ICCBased" => {
+                let (_, profile) = document.dereference(array.get(1)?).ok()?;
+                let components = profile.as_stream().ok()?.dict.get(b"N").ok()?;
+                components
+                    .as_i64()
+                    .ok()
+                    .map(|components| components as usize)
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Encodes operations, writing inline images back in their `BI`/`ID`/`EI` form.
+fn encode(operations: &[Operation]) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+
+    for operation in operations {
+        match (operation.operator.as_str(), operation.operands.first()) {
+            ("BI", Some(Object::Stream(image))) => {
+                let mut dict = Vec::new();
+                for (key, value) in image.dict.iter() {
+                    dict.push(Operation::new(
+                        "",
+                        vec![Object::Name(key.clone()), value.clone()],
+                    ));
+                }
+                output.extend(b"BI\n");
+                output.extend(Content { operations: dict }.encode()?);
+                output.extend(b"\nID ");
+                output.extend(&image.content);
+                output.extend(b"\nEI\n");
+            }
+            _ => {
+                output.extend(
+                    Content {
+                        operations: vec![operation.clone()],
+                    }
+                    .encode()?,
+                );
+                output.push(b'\n');
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::project::test::project;
+    use crate::project::Selector;
+    use lopdf::dictionary;
+
+    fn redaction(page_index: usize, x: f32, y: f32, width: f32, height: f32) -> Redaction {
+        Redaction {
+            page_index,
+            x,
+            y,
+            width,
+            height,
+            color: [0.0, 0.0, 0.0],
+        }
+    }
+
+    #[test]
+    fn test_redact_page() {
+        let mut document = Document::load("test/basic.pdf").unwrap();
+        let page_id = *document.get_pages().get(&1).unwrap();
+        assert!(document.extract_text(&[1]).unwrap().contains('1'));
+
+        // The digit is drawn 200 points high from (72, 529).
+        redact_page(
+            &mut document,
+            page_id,
+            &[redaction(0, 60.0, 500.0, 200.0, 200.0)],
+        )
+        .unwrap();
+
+        assert!(!document.extract_text(&[1]).unwrap().contains('1'));
+
+        let content = String::from_utf8(document.get_page_content(page_id).unwrap()).unwrap();
+        assert!(content.contains("0 0 0 rg 60 500 200 200 re f"));
+        // The white background surrounds the region and is kept.
+        assert!(content.contains("612 792 l"));
+    }
+
+    #[test]
+    fn test_redact_page_form_fields() {
+        let mut document = Document::load("test/basic.pdf").unwrap();
+        let page_id = *document.get_pages().get(&1).unwrap();
+
+        let widget = |document: &mut Document, x: i64| {
+            document.add_object(dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "Widget",
+                "Rect" => vec![x.into(), 550.into(), (x + 50).into(), 570.into()],
+            })
+        };
+        let (inside, outside) = (widget(&mut document, 100), widget(&mut document, 400));
+        let (other_inside, other_outside) =
+            (widget(&mut document, 150), widget(&mut document, 450));
+        let secret = document.add_object(dictionary! {
+            "FT" => "Tx",
+            "T" => Object::string_literal("Secret"),
+            "V" => Object::string_literal("hidden value"),
+            "Kids" => vec![inside.into()],
+        });
+        let shared = document.add_object(dictionary! {
+            "FT" => "Tx",
+            "T" => Object::string_literal("Shared"),
+            "Kids" => vec![other_inside.into(), other_outside.into()],
+        });
+        document.catalog_mut().unwrap().set(
+            "AcroForm",
+            dictionary! { "Fields" => vec![secret.into(), outside.into(), shared.into()] },
+        );
+        document.get_dictionary_mut(page_id).unwrap().set(
+            "Annots",
+            vec![
+                inside.into(),
+                outside.into(),
+                other_inside.into(),
+                other_outside.into(),
+            ],
+        );
+
+        redact_page(
+            &mut document,
+            page_id,
+            &[redaction(0, 60.0, 500.0, 200.0, 200.0)],
+        )
+        .unwrap();
+
+        let references = |object: &Object| {
+            object
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|object| object.as_reference().unwrap())
+                .collect::<Vec<_>>()
+        };
+        let page = document.get_dictionary(page_id).unwrap();
+        assert_eq!(
+            references(page.get(b"Annots").unwrap()),
+            vec![outside, other_outside]
+        );
+        let form = document.catalog().unwrap().get(b"AcroForm").unwrap();
+        let fields = form.as_dict().unwrap().get(b"Fields").unwrap();
+        assert_eq!(references(fields), vec![outside, shared]);
+        let kids = document
+            .get_dictionary(shared)
+            .unwrap()
+            .get(b"Kids")
+            .unwrap();
+        assert_eq!(references(kids), vec![other_outside]);
+    }
+
+    #[test]
+    fn test_redact_page_paths() {
+        let mut document = Document::load("test/basic.pdf").unwrap();
+        let page_id = *document.get_pages().get(&1).unwrap();
+        let content = [
+            // Backgrounds enclosing the region.
+            "0 0 1 rg 10 10 500 500 re f",
+            "0 1 0 rg 20 20 m 400 20 l 400 400 l 20 400 l h f",
+            // A frame, a stroked rectangle and a disc enclosing the region.
+            "1 0 0 rg 30 30 300 300 re 40 40 280 280 re f*",
+            "1 0 0 RG 50 50 250 250 re S",
+            "0 0 0 rg 200 100 m 300 100 300 300 200 300 c 100 300 100 100 200 100 c f",
+        ]
+        .join("\n");
+        document
+            .change_page_content(page_id, content.into_bytes())
+            .unwrap();
+
+        redact_page(
+            &mut document,
+            page_id,
+            &[redaction(0, 150.0, 150.0, 50.0, 50.0)],
+        )
+        .unwrap();
+
+        let content = String::from_utf8(document.get_page_content(page_id).unwrap()).unwrap();
+        assert!(content.contains("10 10 500 500 re"));
+        assert!(content.contains("400 400 l"));
+        assert!(!content.contains("30 30 300 300 re"));
+        assert!(!content.contains("50 50 250 250 re"));
+        assert!(!content.contains("300 300 200 300 c"));
+    }
+
+    #[test]
+    fn test_redact_page_short_operands() {
+        let mut document = Document::load("test/basic.pdf").unwrap();
+        let page_id = *document.get_pages().get(&1).unwrap();
+        let content = "BT /F1 12 Tf 100 100 Td 14 TL 1 \" 1 2 \" 1 2 (Kept) \" ET";
+        document
+            .change_page_content(page_id, content.as_bytes().to_vec())
+            .unwrap();
+
+        redact_page(
+            &mut document,
+            page_id,
+            &[redaction(0, 300.0, 300.0, 50.0, 50.0)],
+        )
+        .unwrap();
+
+        let content = String::from_utf8(document.get_page_content(page_id).unwrap()).unwrap();
+        assert!(content.contains("(Kept) Tj"));
+    }
+
+    #[test]
+    fn test_redact_page_elsewhere() {
+        let mut document = Document::load("test/basic.pdf").unwrap();
+        let page_id = *document.get_pages().get(&1).unwrap();
+
+        redact_page(
+            &mut document,
+            page_id,
+            &[redaction(0, 300.0, 50.0, 100.0, 100.0)],
+        )
+        .unwrap();
+
+        assert!(document.extract_text(&[1]).unwrap().contains('1'));
+    }
+
+    #[test]
+    fn test_export_redactions() {
+        let mut project = project(&["test/basic.pdf"]);
+        project
+            .add_redactions(0, vec![redaction(1, 60.0, 500.0, 200.0, 200.0)])
+            .unwrap();
+
+        let selectors = vec![Selector::new(0, 0), Selector::new(0, 1)];
+        let (document, _) = project.export(&selectors).unwrap();
+
+        assert!(document.extract_text(&[1]).unwrap().contains('1'));
+        assert!(!document.extract_text(&[2]).unwrap().contains('2'));
+        // The source file is left untouched.
+        let source = &project.source_files[0].document;
+        assert!(source.extract_text(&[2]).unwrap().contains('2'));
+    }
+
+    #[test]
+    fn test_redact_text() {
+        let mut project = project(&["test/basic.pdf"]);
+
+        let redactions = project.redact_text(0, "3", [1.0, 0.0, 0.0]).unwrap();
+
+        assert_eq!(redactions.len(), 1);
+        assert_eq!(redactions[0].page_index, 2);
+        assert_eq!(project.source_files[0].redactions, redactions);
+
+        let (document, _) = project.export(&[Selector::new(0, 2)]).unwrap();
+        assert!(!document.extract_text(&[1]).unwrap().contains('3'));
+    }
+
+    #[test]
+    fn test_redact_image() {
+        let mut project = project(&["test/small-image.jpg"]);
+        project
+            .add_redactions(0, vec![redaction(0, 0.0, 0.0, 1000.0, 1000.0)])
+            .unwrap();
+
+        let (document, _) = project.export(&[Selector::new(0, 0)]).unwrap();
+
+        // The page draws a cleared copy of the image, the original isn't exported with it.
+        let images = document
+            .objects
+            .values()
+            .filter_map(|object| object.as_stream().ok())
+            .filter(|stream| {
+                stream.dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Image")
+                    && stream
+                        .dict
+                        .get(b"ColorSpace")
+                        .and_then(Object::as_name)
+                        .ok()
+                        == Some(b"DeviceRGB")
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(images.len(), 1);
+        let pixels = images[0].decompressed_content().unwrap();
+        assert!(pixels.iter().all(|&value| value == 0));
+    }
+
+    /// A letter page drawing `image` over its whole surface.
+    fn image_document(image: Stream) -> (Document, ObjectId, ObjectId) {
+        let mut document = Document::with_version("1.5");
+        let image_id = document.add_object(image);
+        let content_id = document.add_object(Stream::new(
+            Dictionary::new(),
+            b"q 612 0 0 792 0 0 cm /Im0 Do Q".to_vec(),
+        ));
+        let pages_id = document.new_object_id();
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            "Contents" => content_id,
+            "Resources" => dictionary! {
+                "XObject" => dictionary! { "Im0" => image_id },
+            },
+        });
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+
+        (document, page_id, image_id)
+    }
+
+    #[test]
+    fn test_redact_page_replaces_images() {
+        for filter in [None, Some("JPXDecode")] {
+            let mut dict = dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => 4,
+                "Height" => 4,
+                "ColorSpace" => "DeviceRGB",
+                "BitsPerComponent" => 8,
+            };
+            if let Some(filter) = filter {
+                dict.set("Filter", filter);
+            }
+            let (mut document, page_id, image_id) =
+                image_document(Stream::new(dict, vec![255; 4 * 4 * 3]));
+
+            redact_page(
+                &mut document,
+                page_id,
+                &[redaction(0, 0.0, 0.0, 100.0, 100.0)],
+            )
+            .unwrap();
+            document.prune_objects();
+
+            // The image is cleared into a copy, or removed when it can't be decoded, and the
+            // original is gone either way.
+            assert!(!document.objects.contains_key(&image_id));
+            let images = document.get_page_images(page_id).unwrap();
+            assert_eq!(images.len(), usize::from(filter.is_none()));
+        }
+    }
+
+    #[test]
+    fn test_add_redactions_invalid_page() {
+        let mut project = project(&["test/basic.pdf"]);
+
+        assert!(project
+            .add_redactions(0, vec![redaction(10, 0.0, 0.0, 10.0, 10.0)])
+            .is_err());
+        assert!(project.source_files[0].redactions.is_empty());
+    }
+
+    #[test]
+    fn test_clear_redactions() {
+        let mut project = project(&["test/basic.pdf"]);
+        project
+            .add_redactions(
+                0,
+                vec![
+                    redaction(0, 0.0, 0.0, 10.0, 10.0),
+                    redaction(1, 0.0, 0.0, 10.0, 10.0),
+                ],
+            )
+            .unwrap();
+
+        project.clear_redactions(0, Some(0)).unwrap();
+        assert_eq!(project.source_files[0].redactions.len(), 1);
+
+        project.clear_redactions(0, None).unwrap();
+        assert!(project.source_files[0].redactions.is_empty());
+    }
+}
@@ -0,0 +1,349 @@
+use super::{Project, Selector};
+use lopdf::{Dictionary, Document, Object};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+
+/// Catalog entries that are rebuilt by the export rather than lost.
+const REBUILT_CATALOG_KEYS: &[&[u8]] = &[b"Type", b"Pages", b"Outlines"];
+
+/// Everything the export dropped or changed, per source file used in the export.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExportReport {
+    sources: Vec<SourceReport>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceReport {
+    source_file_index: usize,
+    name: String,
+    changes: Vec<ExportChange>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(
+    rename_all = "snake_case",
+    rename_all_fields = "snake_case",
+    tag = "kind"
+)]
+pub enum ExportChange {
+    /// Bookmarks are never merged.
+    Outlines,
+    /// Only the catalog of the last source file of the project is kept.
+    CatalogEntry { key: String },
+    /// Document-level scripts of every source but the last are dropped.
+    JavaScript,
+    /// The page's own `/Rotate` was replaced by the rotation chosen in the project.
+    Rotation {
+        page_index: usize,
+        from: i64,
+        to: u32,
+    },
+    /// The export is written as PDF 1.5, newer features may not be understood by readers.
+    Version { version: String },
+    /// Rewriting the document invalidates its digital signatures.
+    Signatures { count: usize },
+}
+
+impl ExportReport {
+    pub(super) fn new(project: &Project, selectors: &[Selector]) -> Self {
+        let used_sources = selectors
+            .iter()
+            .map(|selector| selector.source_file_index)
+            .collect::<BTreeSet<_>>();
+
+        // The merge keeps the entries of the last catalog, even when none of its pages are used.
+        let kept_catalog = project.source_files.len().saturating_sub(1);
+
+        let sources = used_sources
+            .into_iter()
+            .filter_map(|source_file_index| {
+                let source_file = project.source_files.get(source_file_index)?;
+                let document = &source_file.document;
+                let mut changes = Vec::new();
+
+                if let Ok(catalog) = document.catalog() {
+                    if catalog.has(b"Outlines") {
+                        changes.push(ExportChange::Outlines);
+                    }
+
+                    if source_file_index != kept_catalog {
+                        changes.extend(dropped_catalog_entries(document, catalog));
+                    }
+                }
+
+                if !source_file.signatures.is_empty() {
+                    changes.push(ExportChange::Signatures {
+                        count: source_file.signatures.len(),
+                    });
+                }
+
+                changes.extend(replaced_rotations(document, source_file_index, selectors));
+
+                if document.version.as_str() > "1.5" {
+                    changes.push(ExportChange::Version {
+                        version: document.version.clone(),
+                    });
+                }
+
+                Some(SourceReport {
+                    source_file_index,
+                    name: source_file.name(),
+                    changes,
+                })
+            })
+            .filter(|report| !report.changes.is_empty())
+            .collect();
+
+        Self { sources }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+}
+
+fn dropped_catalog_entries(document: &Document, catalog: &Dictionary) -> Vec<ExportChange> {
+    let mut changes = catalog
+        .iter()
+        .filter(|(key, _)| !REBUILT_CATALOG_KEYS.contains(&key.as_slice()))
+        .map(|(key, _)| ExportChange::CatalogEntry {
+            key: String::from_utf8_lossy(key).into_owned(),
+        })
+        .collect::<Vec<_>>();
+
+    if has_javascript(document, catalog) {
+        changes.push(ExportChange::JavaScript);
+    }
+
+    changes
+}
+
+fn has_javascript(document: &Document, catalog: &Dictionary) -> bool {
+    let names_javascript = document
+        .get_dict_in_dict(catalog, b"Names")
+        .map(|names| names.has(b"JavaScript"))
+        .unwrap_or(false);
+
+    let open_action_javascript = document
+        .get_dict_in_dict(catalog, b"OpenAction")
+        .and_then(|action| action.get(b"S"))
+        .and_then(Object::as_name)
+        .map(|name| name == b"JavaScript")
+        .unwrap_or(false);
+
+    names_javascript || open_action_javascript
+}
+
+fn replaced_rotations(
+    document: &Document,
+    source_file_index: usize,
+    selectors: &[Selector],
+) -> Vec<ExportChange> {
+    let page_ids = document.get_pages().into_values().collect::<Vec<_>>();
+
+    selectors
+        .iter()
+        .filter(|selector| selector.source_file_index == source_file_index)
+        .filter_map(|selector| {
+            let to = selector.rotation.as_rotation()?;
+            let page = document
+                .get_dictionary(*page_ids.get(selector.page_index)?)
+                .ok()?;
+            let from = page.get(b"Rotate").and_then(Object::as_i64).ok()?;
+
+            (from.rem_euclid(360) != 0 && from.rem_euclid(360) != to as i64).then_some(
+                ExportChange::Rotation {
+                    page_index: selector.page_index,
+                    from,
+                    to,
+                },
+            )
+        })
+        .collect()
+}
+
+impl Display for ExportChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportChange::Outlines => write!(f, "bookmarks were removed"),
+            ExportChange::CatalogEntry { key } => match key.as_str() {
+                "AcroForm" => write!(f, "form fields no longer work"),
+                "Names" => write!(f, "named destinations and attachments were removed"),
+                "OpenAction" => write!(f, "the action run when opening was removed"),
+                "ViewerPreferences" => write!(f, "viewer preferences were removed"),
+                "Metadata" => write!(f, "XMP metadata was removed"),
+                "PageLabels" => write!(f, "page labels were removed"),
+                key => write!(f, "the /{} entry was removed", key),
+            },
+            ExportChange::JavaScript => write!(f, "JavaScript was removed"),
+            ExportChange::Rotation {
+                page_index,
+                from,
+                to,
+            } => write!(
+                f,
+                "page {} was rotated {}° instead of {}°",
+                page_index + 1,
+                to,
+                from
+            ),
+            ExportChange::Version { version } => {
+                write!(f, "PDF {} was written as PDF 1.5", version)
+            }
+            ExportChange::Signatures { count: 1 } => {
+                write!(f, "the digital signature is no longer valid")
+            }
+            ExportChange::Signatures { count } => {
+                write!(f, "{} digital signatures are no longer valid", count)
+            }
+        }
+    }
+}
+
+impl Display for ExportReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for source in &self.sources {
+            writeln!(f, "{}:", source.name)?;
+            for change in &source.changes {
+                writeln!(f, "  - {}", change)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::project::signature::read_signatures;
+    use crate::project::{Rotation, SourceFile};
+    use lopdf::dictionary;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_report_dropped_catalog_entries() {
+        let mut first = SourceFile::open(&PathBuf::from("test/basic.pdf"), None).unwrap();
+        let catalog = first.document.catalog_mut().unwrap();
+        catalog.set("Lang", Object::string_literal("fr-CA"));
+        catalog.set("Outlines", Dictionary::new());
+
+        let project = Project {
+            source_files: vec![
+                first,
+                SourceFile::open(&PathBuf::from("test/legal.pdf"), None).unwrap(),
+            ],
+        };
+        let selectors = vec![Selector::new(0, 0), Selector::new(1, 0)];
+
+        let (document, report) = project.export(&selectors).unwrap();
+
+        assert!(!document.catalog().unwrap().has(b"Lang"));
+        assert_eq!(1, report.sources.len());
+        assert_eq!(0, report.sources[0].source_file_index);
+        assert!(report.sources[0].changes.contains(&ExportChange::Outlines));
+        assert!(report.sources[0]
+            .changes
+            .contains(&ExportChange::CatalogEntry {
+                key: "Lang".to_string()
+            }));
+    }
+
+    #[test]
+    fn test_report_kept_catalog() {
+        let mut last = SourceFile::open(&PathBuf::from("test/legal.pdf"), None).unwrap();
+        let catalog = last.document.catalog_mut().unwrap();
+        catalog.set("Lang", Object::string_literal("fr-CA"));
+
+        let project = Project {
+            source_files: vec![
+                SourceFile::open(&PathBuf::from("test/basic.pdf"), None).unwrap(),
+                last,
+            ],
+        };
+
+        // The last catalog is kept even when only pages of other sources are exported.
+        let (document, report) = project.export(&[Selector::new(0, 0)]).unwrap();
+
+        assert!(document.catalog().unwrap().has(b"Lang"));
+        assert!(report.is_empty());
+    }
+
+    #[test]
+    fn test_report_replaced_rotation() {
+        let mut source_file = SourceFile::open(&PathBuf::from("test/basic.pdf"), None).unwrap();
+        let page_id = source_file.document.page_iter().next().unwrap();
+        source_file
+            .document
+            .get_dictionary_mut(page_id)
+            .unwrap()
+            .set("Rotate", 180);
+
+        let project = Project {
+            source_files: vec![source_file],
+        };
+        let selectors = vec![Selector {
+            source_file_index: 0,
+            page_index: 0,
+            rotation: Rotation::R90,
+            rasterize: None,
+            group: None,
+        }];
+
+        let (_, report) = project.export(&selectors).unwrap();
+
+        assert_eq!(
+            vec![ExportChange::Rotation {
+                page_index: 0,
+                from: 180,
+                to: 90
+            }],
+            report.sources[0].changes
+        );
+    }
+
+    #[test]
+    fn test_report_invalidated_signatures() {
+        let mut source_file = SourceFile::open(&PathBuf::from("test/basic.pdf"), None).unwrap();
+        let signature_id = source_file.document.add_object(dictionary! {
+            "Type" => "Sig",
+            "ByteRange" => vec![0.into(), 10.into(), 20.into(), 30.into()],
+        });
+        source_file.document.add_object(dictionary! {
+            "FT" => "Sig",
+            "V" => signature_id,
+        });
+        source_file.signatures = read_signatures(&source_file.document, 50);
+
+        let project = Project {
+            source_files: vec![source_file],
+        };
+
+        let (_, report) = project.export(&[Selector::new(0, 0)]).unwrap();
+
+        assert_eq!(
+            vec![ExportChange::Signatures { count: 1 }],
+            report.sources[0].changes
+        );
+        assert_eq!(
+            vec!["basic.pdf"],
+            project.signed_sources(&[Selector::new(0, 1)])
+        );
+    }
+
+    #[test]
+    fn test_report_is_empty_for_plain_documents() {
+        let project = Project {
+            source_files: vec![
+                SourceFile::open(&PathBuf::from("test/basic.pdf"), None).unwrap(),
+                SourceFile::open(&PathBuf::from("test/legal.pdf"), None).unwrap(),
+            ],
+        };
+        let selectors = vec![Selector::new(0, 0), Selector::new(1, 0)];
+
+        let (_, report) = project.export(&selectors).unwrap();
+
+        assert!(report.is_empty(), "{}", report);
+    }
+}
@@ -0,0 +1,381 @@
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::ops::AddAssign;
+
+/// Action types that run code, open other files or send data out of the document.
+const UNSAFE_ACTIONS: &[&[u8]] = &[
+    b"JavaScript",
+    b"Launch",
+    b"SubmitForm",
+    b"ImportData",
+    b"GoToR",
+    b"GoToE",
+    b"Rendition",
+];
+
+/// What to strip from an exported document. Everything but annotations is removed by default.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SanitizeOptions {
+    /// The `/Info` dictionary and XMP metadata streams.
+    #[serde(default = "enabled")]
+    metadata: bool,
+    /// Scripts, document and page actions, and links running code or opening other files.
+    #[serde(default = "enabled")]
+    javascript: bool,
+    /// Attached files, both document-level and file attachment annotations.
+    #[serde(default = "enabled")]
+    embedded_files: bool,
+    /// Private application data (`/PieceInfo`) left by authoring tools.
+    #[serde(default = "enabled")]
+    private_data: bool,
+    /// Subtypes of the annotations to remove, e.g. `Text` or `Highlight`.
+    #[serde(default)]
+    annotations: Vec<String>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl Default for SanitizeOptions {
+    fn default() -> Self {
+        Self {
+            metadata: true,
+            javascript: true,
+            embedded_files: true,
+            private_data: true,
+            annotations: Vec::new(),
+        }
+    }
+}
+
+/// Number of items removed by [`sanitize`], per kind.
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct SanitizeReport {
+    metadata: usize,
+    javascript: usize,
+    actions: usize,
+    embedded_files: usize,
+    annotations: usize,
+    private_data: usize,
+}
+
+impl SanitizeReport {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether annotations were removed, including file attachment ones.
+    pub(super) fn removed_annotations(&self) -> bool {
+        self.annotations > 0 || self.embedded_files > 0
+    }
+}
+
+impl AddAssign for SanitizeReport {
+    fn add_assign(&mut self, other: Self) {
+        self.metadata += other.metadata;
+        self.javascript += other.javascript;
+        self.actions += other.actions;
+        self.embedded_files += other.embedded_files;
+        self.annotations += other.annotations;
+        self.private_data += other.private_data;
+    }
+}
+
+impl Display for SanitizeReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let lines = [
+            (self.metadata, "metadata entries"),
+            (self.javascript, "scripts"),
+            (self.actions, "actions"),
+            (self.embedded_files, "embedded files"),
+            (self.annotations, "annotations"),
+            (self.private_data, "private application data entries"),
+        ];
+
+        for (count, label) in lines.into_iter().filter(|(count, _)| *count > 0) {
+            writeln!(f, "- {} {}", count, label)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Strips privacy-sensitive content from an exported document.
+pub fn sanitize(document: &mut Document, options: &SanitizeOptions) -> SanitizeReport {
+    let mut report = SanitizeReport::default();
+
+    if options.embedded_files {
+        report.embedded_files = count_objects(document, |dict| {
+            dict.get(b"Type").and_then(Object::as_name).ok() == Some(b"EmbeddedFile".as_slice())
+        });
+        remove_name_tree(document, b"EmbeddedFiles");
+        remove_catalog_entry(document, b"AF");
+        remove_annotations(document, |subtype| subtype == b"FileAttachment");
+    }
+
+    if !options.annotations.is_empty() {
+        report.annotations = remove_annotations(document, |subtype| {
+            options
+                .annotations
+                .iter()
+                .any(|kind| kind.as_bytes() == subtype)
+        });
+    }
+
+    if options.javascript {
+        report.javascript = count_objects(document, |dict| dict.has(b"JS"));
+        remove_name_tree(document, b"JavaScript");
+        report.actions = remove_actions(document);
+    }
+
+    if options.metadata {
+        report.metadata = usize::from(document.trailer.remove(b"Info").is_some())
+            + remove_key(document, b"Metadata");
+    }
+
+    if options.private_data {
+        report.private_data = remove_key(document, b"PieceInfo");
+    }
+
+    document.prune_objects();
+
+    report
+}
+
+fn dictionaries_mut(document: &mut Document) -> impl Iterator<Item = &mut Dictionary> {
+    document
+        .objects
+        .values_mut()
+        .filter_map(|object| match object {
+            Object::Dictionary(dict) => Some(dict),
+            Object::Stream(stream) => Some(&mut stream.dict),
+            _ => None,
+        })
+}
+
+fn count_objects(document: &Document, predicate: impl Fn(&Dictionary) -> bool) -> usize {
+    document
+        .objects
+        .values()
+        .filter_map(|object| match object {
+            Object::Dictionary(dict) => Some(dict),
+            Object::Stream(stream) => Some(&stream.dict),
+            _ => None,
+        })
+        .filter(|dict| predicate(dict))
+        .count()
+}
+
+/// Removes `key` from every dictionary, returning how many had it.
+fn remove_key(document: &mut Document, key: &[u8]) -> usize {
+    dictionaries_mut(document)
+        .filter_map(|dict| dict.remove(key))
+        .count()
+}
+
+fn remove_catalog_entry(document: &mut Document, key: &[u8]) {
+    if let Ok(catalog) = document.catalog_mut() {
+        catalog.remove(key);
+    }
+}
+
+/// Removes an entry of the catalog's `/Names` dictionary, which may be indirect.
+fn remove_name_tree(document: &mut Document, key: &[u8]) {
+    let names_id = match document.catalog().and_then(|catalog| catalog.get(b"Names")) {
+        Ok(Object::Reference(id)) => Some(*id),
+        _ => None,
+    };
+
+    let names = match names_id {
+        Some(id) => document.get_dictionary_mut(id),
+        None => document
+            .catalog_mut()
+            .and_then(|catalog| catalog.get_mut(b"Names"))
+            .and_then(Object::as_dict_mut),
+    };
+
+    if let Ok(names) = names {
+        names.remove(key);
+    }
+}
+
+/// Removes the annotations whose subtype matches from every page, returning how many were
+/// removed.
+fn remove_annotations(document: &mut Document, matches: impl Fn(&[u8]) -> bool) -> usize {
+    let mut removed = 0;
+
+    for page_id in document.get_pages().into_values() {
+        let annotations = match document
+            .get_dictionary(page_id)
+            .and_then(|page| page.get(b"Annots"))
+        {
+            Ok(Object::Array(annotations)) => annotations.clone(),
+            Ok(Object::Reference(id)) => {
+                match document.get_object(*id).and_then(Object::as_array) {
+                    Ok(annotations) => annotations.clone(),
+                    Err(_) => continue,
+                }
+            }
+            _ => continue,
+        };
+
+        let kept = annotations
+            .into_iter()
+            .filter(|annotation| {
+                let subtype = document
+                    .dereference(annotation)
+                    .and_then(|(_, annotation)| annotation.as_dict())
+                    .and_then(|annotation| annotation.get(b"Subtype"))
+                    .and_then(Object::as_name);
+
+                match subtype {
+                    Ok(subtype) if matches(subtype) => {
+                        removed += 1;
+                        false
+                    }
+                    _ => true,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if let Ok(page) = document.get_dictionary_mut(page_id) {
+            page.set("Annots", kept);
+        }
+    }
+
+    removed
+}
+
+/// Removes document and page actions, along with links and form actions running code or opening
+/// other files. Returns how many were removed.
+fn remove_actions(document: &mut Document) -> usize {
+    let is_unsafe = |dict: &Dictionary| {
+        dict.get(b"S")
+            .and_then(Object::as_name)
+            .is_ok_and(|kind| UNSAFE_ACTIONS.contains(&kind))
+    };
+
+    let unsafe_ids = document
+        .objects
+        .iter()
+        .filter(|(_, object)| object.as_dict().is_ok_and(is_unsafe))
+        .map(|(id, _)| *id)
+        .collect::<BTreeSet<ObjectId>>();
+
+    let mut removed = 0;
+    for dict in dictionaries_mut(document) {
+        // Additional actions only ever trigger on events, none of them are kept.
+        removed += usize::from(dict.remove(b"AA").is_some());
+
+        for key in [b"A".as_slice(), b"OpenAction"] {
+            let remove = match dict.get(key) {
+                Ok(Object::Reference(id)) => unsafe_ids.contains(id),
+                Ok(Object::Dictionary(action)) => is_unsafe(action),
+                _ => false,
+            };
+
+            if remove {
+                dict.remove(key);
+                removed += 1;
+            }
+        }
+    }
+
+    removed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::project::test::exported_document;
+    use lopdf::{dictionary, Stream};
+
+    #[test]
+    fn test_sanitize() {
+        let mut document = exported_document();
+        let page_id = *document.get_pages().get(&1).unwrap();
+
+        let script_id = document.add_object(dictionary! {
+            "S" => "JavaScript",
+            "JS" => Object::string_literal("app.alert('hello')"),
+        });
+        let file_id = document.add_object(Stream::new(
+            dictionary! { "Type" => "EmbeddedFile" },
+            b"secret".to_vec(),
+        ));
+        let names_id = document.add_object(dictionary! {
+            "EmbeddedFiles" => dictionary! {
+                "Names" => vec![
+                    Object::string_literal("secret.txt"),
+                    dictionary! {
+                        "Type" => "Filespec",
+                        "EF" => dictionary! { "F" => file_id },
+                    }
+                    .into(),
+                ],
+            },
+        });
+        let link = document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Link",
+            "A" => dictionary! { "S" => "URI", "URI" => Object::string_literal("https://example.com") },
+        });
+        let note = document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Text",
+            "Contents" => Object::string_literal("Internal note"),
+        });
+
+        let catalog = document.catalog_mut().unwrap();
+        catalog.set("OpenAction", script_id);
+        catalog.set("Names", names_id);
+        let page = document.get_dictionary_mut(page_id).unwrap();
+        page.set("Annots", vec![link.into(), note.into()]);
+        page.set("PieceInfo", dictionary! {});
+
+        let report = sanitize(
+            &mut document,
+            &SanitizeOptions {
+                annotations: vec!["Text".to_string()],
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(report.javascript, 1);
+        assert_eq!(report.actions, 1);
+        assert_eq!(report.embedded_files, 1);
+        assert_eq!(report.annotations, 1);
+        assert_eq!(report.private_data, 1);
+        assert!(report.metadata >= 2);
+
+        let catalog = document.catalog().unwrap();
+        assert!(catalog.get(b"OpenAction").is_err());
+        assert!(catalog.get(b"Metadata").is_err());
+        assert!(document.trailer.get(b"Info").is_err());
+        assert!(document.get_object(script_id).is_err());
+        assert!(document.get_object(file_id).is_err());
+
+        let annotations = document.get_page_annotations(page_id).unwrap();
+        assert_eq!(annotations.len(), 1);
+        assert!(annotations[0].get(b"A").is_ok());
+    }
+
+    #[test]
+    fn test_sanitize_nothing() {
+        let mut document = exported_document();
+
+        let report = sanitize(
+            &mut document,
+            &SanitizeOptions {
+                metadata: false,
+                ..Default::default()
+            },
+        );
+
+        assert!(report.is_empty());
+        assert!(document.trailer.get(b"Info").is_ok());
+    }
+}
@@ -0,0 +1,315 @@
+use super::{pdfium, Project};
+use anyhow::Result;
+use pdfium_render::prelude::*;
+use serde::Serialize;
+use std::ops::Range;
+
+/// Characters of context on each side of the first hit of a page in its snippet.
+const SNIPPET_CONTEXT: usize = 40;
+
+/// Text of a source page, indexed on import so searching doesn't go through pdfium.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(super) struct PageText {
+    /// Characters of the page in reading order, lowercased, with runs of whitespace as a single
+    /// space.
+    characters: Vec<char>,
+    /// Characters as printed, for snippets.
+    original: Vec<char>,
+    /// Box of each character, `None` for spaces and line breaks pdfium adds between words.
+    boxes: Vec<Option<HitRect>>,
+}
+
+/// A region of a page with a hit, in the default user space of the unrotated page like
+/// [`Redaction`](super::Redaction) regions.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct HitRect {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+/// A page of a source file matching a search.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SearchResult {
+    source_file_index: usize,
+    page_index: usize,
+    /// Text around the first hit of the page.
+    snippet: String,
+    /// Characters of the snippet matching the search.
+    highlight: Range<usize>,
+    /// Regions of every hit of the page, one per line a hit spans.
+    hits: Vec<HitRect>,
+}
+
+/// Extracts the text of every page of a PDF, for [`Project::search`].
+pub(super) fn index_text(bytes: &[u8]) -> Result<Vec<PageText>> {
+    let pdfium = pdfium()?;
+    let document = pdfium.load_pdf_from_byte_slice(bytes, None)?;
+
+    let mut pages = Vec::new();
+    for page in document.pages().iter() {
+        let mut page_text = PageText::default();
+        for char in page.text()?.chars().iter() {
+            let Some(character) = char.unicode_char() else {
+                continue;
+            };
+            let bounds = char
+                .loose_bounds()
+                .ok()
+                .filter(|_| !character.is_whitespace());
+
+            page_text.push(
+                character,
+                bounds.map(|bounds| HitRect {
+                    x: bounds.left().value,
+                    y: bounds.bottom().value,
+                    width: bounds.width().value,
+                    height: bounds.height().value,
+                }),
+            );
+        }
+        pages.push(page_text);
+    }
+
+    Ok(pages)
+}
+
+impl PageText {
+    fn push(&mut self, character: char, rect: Option<HitRect>) {
+        if character.is_whitespace() || character.is_control() {
+            if self.characters.last().is_some_and(|last| *last != ' ') {
+                self.characters.push(' ');
+                self.original.push(' ');
+                self.boxes.push(None);
+            }
+            return;
+        }
+
+        self.characters.push(fold(character));
+        self.original.push(character);
+        self.boxes.push(rect);
+    }
+
+    /// Start of every occurrence of `query`, which must be folded already.
+    fn find(&self, query: &[char]) -> Vec<usize> {
+        if query.is_empty() || query.len() > self.characters.len() {
+            return Vec::new();
+        }
+
+        let mut starts = Vec::new();
+        let mut start = 0;
+        while start + query.len() <= self.characters.len() {
+            if self.characters[start..start + query.len()] == *query {
+                starts.push(start);
+                start += query.len();
+            } else {
+                start += 1;
+            }
+        }
+        starts
+    }
+
+    /// Boxes of the characters of a hit, merged into one per line.
+    fn hit_rects(&self, hit: Range<usize>) -> Vec<HitRect> {
+        let mut rects: Vec<HitRect> = Vec::new();
+        for rect in self.boxes[hit].iter().flatten() {
+            match rects.last_mut() {
+                Some(line) if line.same_line(rect) => *line = line.union(rect),
+                _ => rects.push(*rect),
+            }
+        }
+        rects
+    }
+
+    /// Text around a hit, with the position of the hit in it.
+    fn snippet(&self, hit: Range<usize>) -> (String, Range<usize>) {
+        let start = hit.start.saturating_sub(SNIPPET_CONTEXT);
+        let end = (hit.end + SNIPPET_CONTEXT).min(self.original.len());
+
+        let mut snippet = String::new();
+        let mut offset = 0;
+        if start > 0 {
+            snippet.push('…');
+            offset = 1;
+        }
+        snippet.extend(&self.original[start..end]);
+        if end < self.original.len() {
+            snippet.push('…');
+        } else {
+            snippet.truncate(snippet.trim_end().len());
+        }
+
+        let highlight = hit.start - start + offset..hit.end - start + offset;
+        (snippet, highlight)
+    }
+}
+
+impl HitRect {
+    /// Whether the other box's middle is within the height of this one.
+    fn same_line(&self, other: &HitRect) -> bool {
+        let middle = other.y + other.height / 2.0;
+        middle >= self.y && middle <= self.y + self.height
+    }
+
+    fn union(&self, other: &HitRect) -> HitRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        HitRect {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+}
+
+/// Case insensitive form of a character. Only the first character of multi-character lowercase
+/// forms is kept so positions in the text don't shift.
+fn fold(character: char) -> char {
+    character.to_lowercase().next().unwrap_or(character)
+}
+
+impl Project {
+    /// Pages of every source file containing `query`, ignoring case and how the text is spread
+    /// over lines, in the order of the source files.
+    pub fn search(&self, query: &str) -> Vec<SearchResult> {
+        let query = query
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .chars()
+            .map(fold)
+            .collect::<Vec<_>>();
+
+        let mut results = Vec::new();
+        for (source_file_index, source_file) in self.source_files.iter().enumerate() {
+            for (page_index, page_text) in source_file.text.iter().enumerate() {
+                let starts = page_text.find(&query);
+                let Some(first) = starts.first() else {
+                    continue;
+                };
+
+                let (snippet, highlight) = page_text.snippet(*first..*first + query.len());
+                let hits = starts
+                    .iter()
+                    .flat_map(|start| page_text.hit_rects(*start..*start + query.len()))
+                    .collect();
+
+                results.push(SearchResult {
+                    source_file_index,
+                    page_index,
+                    snippet,
+                    highlight,
+                    hits,
+                });
+            }
+        }
+
+        results
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::project::SourceFile;
+    use std::path::PathBuf;
+
+    /// A page with the given lines, in a 10 points font.
+    fn page_text(lines: &[&str]) -> PageText {
+        let mut page_text = PageText::default();
+        for (line, text) in lines.iter().enumerate() {
+            for (column, character) in text.chars().enumerate() {
+                let rect = HitRect {
+                    x: 72.0 + column as f32 * 5.0,
+                    y: 700.0 - line as f32 * 12.0,
+                    width: 5.0,
+                    height: 10.0,
+                };
+                page_text.push(character, Some(rect));
+            }
+            page_text.push('\r', None);
+            page_text.push('\n', None);
+        }
+        page_text
+    }
+
+    fn folded(query: &str) -> Vec<char> {
+        query.chars().map(fold).collect()
+    }
+
+    #[test]
+    fn test_find() {
+        let page_text = page_text(&["Invoice  number: 42", "INVOICE total"]);
+
+        assert_eq!(page_text.find(&folded("invoice")), vec![0, 19]);
+        assert_eq!(page_text.find(&folded("42 invoice")), vec![16]);
+        assert!(page_text.find(&folded("receipt")).is_empty());
+        assert!(page_text.find(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_hit_rects() {
+        let page_text = page_text(&["Invoice number", "42 total"]);
+        let start = page_text.find(&folded("number 42"))[0];
+
+        assert_eq!(
+            page_text.hit_rects(start..start + 9),
+            vec![
+                HitRect {
+                    x: 112.0,
+                    y: 700.0,
+                    width: 30.0,
+                    height: 10.0
+                },
+                HitRect {
+                    x: 72.0,
+                    y: 688.0,
+                    width: 10.0,
+                    height: 10.0
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snippet() {
+        let line = format!("{} needle {}", "a".repeat(50), "b".repeat(50));
+        let page_text = page_text(&[&line]);
+
+        let (snippet, highlight) = page_text.snippet(51..57);
+
+        assert_eq!(snippet.chars().count(), 1 + 40 + 6 + 40 + 1);
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        assert_eq!(
+            snippet
+                .chars()
+                .skip(highlight.start)
+                .take(highlight.len())
+                .collect::<String>(),
+            "needle"
+        );
+    }
+
+    #[test]
+    fn test_search() {
+        let mut source_file = SourceFile::open(&PathBuf::from("test/basic.pdf"), None).unwrap();
+        source_file.text = vec![
+            page_text(&["Nothing here"]),
+            page_text(&["Invoice number 1234"]),
+        ];
+        let project = Project {
+            source_files: vec![source_file],
+        };
+
+        let results = project.search("  INVOICE\nnumber ");
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].page_index, 1);
+        assert_eq!(results[0].snippet, "Invoice number 1234");
+        assert_eq!(results[0].highlight, 0..14);
+        assert_eq!(results[0].hits.len(), 1);
+        assert!(project.search(" ").is_empty());
+    }
+}
@@ -0,0 +1,156 @@
+use super::{pdfium, Project, Rotation, Selector, SourceFile};
+use anyhow::{anyhow, Result};
+use image::GrayImage;
+use pdfium_render::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Resolution pages are rendered at to look for barcodes, enough for printed 1D codes.
+const SEPARATOR_DPI: f32 = 150.0;
+
+/// How separator sheets are recognized when importing scanned batches.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SeparatorOptions {
+    /// Only barcodes starting with this are separators, so codes printed on the documents
+    /// themselves don't split them.
+    #[serde(default)]
+    prefix: Option<String>,
+}
+
+/// A page of a source file with a separator barcode, starting a new group.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Separator {
+    page_index: usize,
+    /// Content of the barcode, without the prefix.
+    name: String,
+}
+
+impl SourceFile {
+    /// Looks for separator sheets among the pages, replacing the ones found before.
+    pub fn detect_separators(&mut self, options: &SeparatorOptions) -> Result<()> {
+        let pdfium = pdfium()?;
+        let bytes = self.to_bytes()?;
+        let document = pdfium.load_pdf_from_byte_slice(&bytes, None)?;
+
+        let render_config = PdfRenderConfig::new().scale_page_by_factor(SEPARATOR_DPI / 72.0);
+
+        let mut separators = Vec::new();
+        for (page_index, page) in document.pages().iter().enumerate() {
+            let image = page
+                .render_with_config(&render_config)?
+                .as_image()
+                .into_luma8();
+
+            if let Some(name) = separator_name(image, options) {
+                separators.push(Separator { page_index, name });
+            }
+        }
+
+        self.separators = separators;
+        Ok(())
+    }
+}
+
+impl Project {
+    /// Every page of a source file but its separator sheets, in groups named after the separator
+    /// before them. Pages before the first separator aren't part of any group.
+    pub fn separator_selectors(&self, source_file_index: usize) -> Result<Vec<Selector>> {
+        let source_file = self
+            .source_files
+            .get(source_file_index)
+            .ok_or_else(|| anyhow!("Invalid source_file_index: {}", source_file_index))?;
+
+        let mut group = None;
+        let mut selectors = Vec::new();
+        for page_index in 0..source_file.pages.len() {
+            let separator = source_file
+                .separators
+                .iter()
+                .find(|separator| separator.page_index == page_index);
+
+            match separator {
+                Some(separator) => group = Some(separator.name.clone()),
+                None => selectors.push(Selector {
+                    source_file_index,
+                    page_index,
+                    rotation: Rotation::R0,
+                    rasterize: None,
+                    group: group.clone(),
+                }),
+            }
+        }
+
+        Ok(selectors)
+    }
+}
+
+/// The name of the separator on a page, `None` when no barcode on it is a separator.
+fn separator_name(image: GrayImage, options: &SeparatorOptions) -> Option<String> {
+    let (width, height) = image.dimensions();
+    let barcodes = rxing::helpers::detect_multiple_in_luma(image.into_raw(), width, height).ok()?;
+
+    barcodes
+        .iter()
+        .find_map(|barcode| match &options.prefix {
+            Some(prefix) => barcode.getText().strip_prefix(prefix.as_str()),
+            None => Some(barcode.getText()),
+        })
+        .map(|name| name.trim().to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::imageops;
+    use rxing::{BarcodeFormat, MultiFormatWriter, Writer};
+
+    /// A white letter page at the separator resolution, with a barcode in its top left corner.
+    fn page(contents: &str, format: BarcodeFormat) -> GrayImage {
+        let barcode = MultiFormatWriter
+            .encode(contents, &format, 300, 150)
+            .unwrap();
+        let barcode = image::DynamicImage::from(&barcode).into_luma8();
+
+        let mut page = GrayImage::from_pixel(1275, 1650, image::Luma([u8::MAX]));
+        imageops::overlay(&mut page, &barcode, 150, 150);
+        page
+    }
+
+    fn options(prefix: Option<&str>) -> SeparatorOptions {
+        SeparatorOptions {
+            prefix: prefix.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_separator_name() {
+        assert_eq!(
+            separator_name(
+                page("SEP:Invoices", BarcodeFormat::QR_CODE),
+                &options(Some("SEP:"))
+            ),
+            Some("Invoices".to_string())
+        );
+        assert_eq!(
+            separator_name(page("Contracts", BarcodeFormat::CODE_128), &options(None)),
+            Some("Contracts".to_string())
+        );
+    }
+
+    #[test]
+    fn test_separator_name_other_barcode() {
+        assert_eq!(
+            separator_name(
+                page("INV-2024-001", BarcodeFormat::QR_CODE),
+                &options(Some("SEP:"))
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_separator_name_blank_page() {
+        let page = GrayImage::from_pixel(1275, 1650, image::Luma([u8::MAX]));
+
+        assert_eq!(separator_name(page, &options(None)), None);
+    }
+}
@@ -0,0 +1,607 @@
+use super::metadata::{civil_from_days, now, pdf_date};
+use anyhow::{anyhow, Result};
+use cms::builder::{SignedDataBuilder, SignerInfoBuilder};
+use cms::cert::{CertificateChoices, IssuerAndSerialNumber};
+use cms::signed_data::{EncapsulatedContentInfo, SignerIdentifier};
+use der::asn1::{ObjectIdentifier, OctetString, SetOfVec};
+use der::{Any, Decode, Encode, Sequence};
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+use p12_keystore::KeyStore;
+use rsa::pkcs1v15::SigningKey;
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::RsaPrivateKey;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use x509_cert::attr::Attribute;
+use x509_cert::spki::AlgorithmIdentifierOwned;
+use x509_cert::Certificate;
+
+const ID_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.1");
+const ID_SHA_256: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.1");
+const ID_SIGNING_CERTIFICATE_V2: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.16.2.47");
+
+/// Bytes reserved in the file for the CMS signature, enough for a 4096 bits key and a short
+/// certificate chain.
+const SIGNATURE_SIZE: usize = 16_384;
+
+/// Written in the `/ByteRange` before the offsets are known. Large enough that the real offsets
+/// always fit in its place.
+const BYTE_RANGE_PLACEHOLDER: i64 = 9_999_999_999;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SigningOptions {
+    /// A PKCS#12 (`.p12` or `.pfx`) file holding the certificate and its private key.
+    certificate_path: PathBuf,
+    password: String,
+    #[serde(default)]
+    reason: Option<String>,
+    #[serde(default)]
+    location: Option<String>,
+    /// Where to draw the signature, it is invisible when `None`.
+    #[serde(default)]
+    appearance: Option<SignatureAppearance>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SignatureAppearance {
+    /// Index of the page in the exported document.
+    page_index: usize,
+    /// Position and size of the signature, in the default user space of the page, which only
+    /// starts at its bottom-left corner when the media box does.
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+impl SigningOptions {
+    /// Index of the page the signature is drawn on, when it is visible.
+    pub(super) fn appearance_page(&self) -> Option<usize> {
+        self.appearance
+            .as_ref()
+            .map(|appearance| appearance.page_index)
+    }
+
+    /// The options for signing `document`, without the appearance when the document doesn't
+    /// have its page, as happens with the smaller parts of a split export.
+    pub fn for_document(&self, document: &Document) -> SigningOptions {
+        let mut options = self.clone();
+        if self
+            .appearance_page()
+            .is_some_and(|page_index| page_index >= document.get_pages().len())
+        {
+            options.appearance = None;
+        }
+        options
+    }
+}
+
+/// ESSCertIDv2 (RFC 5035), using the default SHA-256 hash and without the issuer serial.
+#[derive(Sequence)]
+struct EssCertIdV2 {
+    cert_hash: OctetString,
+}
+
+/// The signing-certificate-v2 attribute PAdES requires to bind the certificate to the signature.
+#[derive(Sequence)]
+struct SigningCertificateV2 {
+    certs: Vec<EssCertIdV2>,
+}
+
+struct Signer {
+    key: RsaPrivateKey,
+    /// The signer's certificate first, followed by its issuers.
+    chain: Vec<Certificate>,
+}
+
+impl Signer {
+    fn load(options: &SigningOptions) -> Result<Self> {
+        let bytes = std::fs::read(&options.certificate_path)?;
+        let keystore = KeyStore::from_pkcs12(&bytes, &options.password)
+            .map_err(|e| anyhow!("Couldn't open the certificate: {}", e))?;
+
+        let (_, key_chain) = keystore
+            .private_key_chain()
+            .ok_or_else(|| anyhow!("The certificate file doesn't contain a private key"))?;
+
+        let key = RsaPrivateKey::from_pkcs8_der(key_chain.key())
+            .map_err(|_| anyhow!("Only RSA keys can be used to sign documents"))?;
+
+        let chain = key_chain
+            .chain()
+            .iter()
+            .map(|certificate| Certificate::from_der(certificate.as_der()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if chain.is_empty() {
+            return Err(anyhow!(
+                "The certificate file doesn't contain a certificate"
+            ));
+        }
+
+        Ok(Self { key, chain })
+    }
+
+    /// Common name of the signer, falling back to the whole subject.
+    fn name(&self) -> String {
+        let subject = self.chain[0].tbs_certificate.subject.to_string();
+
+        subject
+            .split(',')
+            .find_map(|part| part.strip_prefix("CN="))
+            .map(str::to_string)
+            .unwrap_or(subject)
+    }
+
+    /// Builds a detached CMS signature of content hashing to `digest`.
+    fn sign(&self, digest: &[u8]) -> Result<Vec<u8>> {
+        let certificate = &self.chain[0];
+        let signing_key = SigningKey::<Sha256>::new(self.key.clone());
+
+        let content = EncapsulatedContentInfo {
+            econtent_type: ID_DATA,
+            econtent: None,
+        };
+        let digest_algorithm = AlgorithmIdentifierOwned {
+            oid: ID_SHA_256,
+            parameters: None,
+        };
+        let sid = SignerIdentifier::IssuerAndSerialNumber(IssuerAndSerialNumber {
+            issuer: certificate.tbs_certificate.issuer.clone(),
+            serial_number: certificate.tbs_certificate.serial_number.clone(),
+        });
+
+        let build_error = |e: cms::builder::Error| anyhow!("Couldn't build the signature: {}", e);
+
+        let mut signer_info = SignerInfoBuilder::new(
+            &signing_key,
+            sid,
+            digest_algorithm.clone(),
+            &content,
+            Some(digest),
+        )
+        .map_err(build_error)?;
+        signer_info
+            .add_signed_attribute(signing_certificate_attribute(certificate)?)
+            .map_err(build_error)?;
+
+        let mut builder = SignedDataBuilder::new(&content);
+        builder
+            .add_digest_algorithm(digest_algorithm)
+            .map_err(build_error)?;
+        for certificate in &self.chain {
+            builder
+                .add_certificate(CertificateChoices::Certificate(certificate.clone()))
+                .map_err(build_error)?;
+        }
+        builder
+            .add_signer_info::<_, rsa::pkcs1v15::Signature>(signer_info)
+            .map_err(build_error)?;
+
+        Ok(builder.build().map_err(build_error)?.to_der()?)
+    }
+}
+
+fn signing_certificate_attribute(certificate: &Certificate) -> Result<Attribute> {
+    let value = SigningCertificateV2 {
+        certs: vec![EssCertIdV2 {
+            cert_hash: OctetString::new(Sha256::digest(certificate.to_der()?).to_vec())?,
+        }],
+    };
+
+    Ok(Attribute {
+        oid: ID_SIGNING_CERTIFICATE_V2,
+        values: SetOfVec::try_from(vec![Any::encode_from(&value)?])?,
+    })
+}
+
+/// Signs an exported document with a PAdES baseline signature and returns the bytes of the
+/// signed file. The document must be saved as is, any later change invalidates the signature.
+pub fn sign(mut document: Document, options: &SigningOptions) -> Result<Vec<u8>> {
+    let signer = Signer::load(options)?;
+
+    add_signature_field(&mut document, &signer, options)?;
+
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes)?;
+
+    let (contents_start, contents_end) = fill_byte_range(&mut bytes)?;
+
+    let digest = Sha256::new()
+        .chain_update(&bytes[..contents_start])
+        .chain_update(&bytes[contents_end..])
+        .finalize();
+
+    let signature = signer.sign(&digest)?;
+    if signature.len() > SIGNATURE_SIZE {
+        return Err(anyhow!(
+            "The signature is too large: {} bytes",
+            signature.len()
+        ));
+    }
+
+    // Skip the opening `<`, the rest of the placeholder stays zero-padded.
+    let hex = signature
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<String>();
+    bytes[contents_start + 1..contents_start + 1 + hex.len()].copy_from_slice(hex.as_bytes());
+
+    Ok(bytes)
+}
+
+fn add_signature_field(
+    document: &mut Document,
+    signer: &Signer,
+    options: &SigningOptions,
+) -> Result<()> {
+    let page_index = options.appearance.as_ref().map_or(0, |a| a.page_index);
+    let page_id = document
+        .get_pages()
+        .into_values()
+        .nth(page_index)
+        .ok_or_else(|| anyhow!("Invalid signature page: {}", page_index + 1))?;
+
+    let now = now()?;
+
+    let mut signature = dictionary! {
+        "Type" => "Sig",
+        "Filter" => "Adobe.PPKLite",
+        "SubFilter" => "ETSI.CAdES.detached",
+        "ByteRange" => vec![
+            0.into(),
+            BYTE_RANGE_PLACEHOLDER.into(),
+            BYTE_RANGE_PLACEHOLDER.into(),
+            BYTE_RANGE_PLACEHOLDER.into(),
+        ],
+        "Contents" => Object::String(vec![0; SIGNATURE_SIZE], StringFormat::Hexadecimal),
+        "M" => Object::string_literal(pdf_date(now)),
+        "Name" => Object::string_literal(signer.name()),
+    };
+    if let Some(reason) = &options.reason {
+        signature.set("Reason", Object::string_literal(reason.as_str()));
+    }
+    if let Some(location) = &options.location {
+        signature.set("Location", Object::string_literal(location.as_str()));
+    }
+    let signature_id = document.add_object(signature);
+
+    let rect = match &options.appearance {
+        Some(a) => [a.x, a.y, a.x + a.width, a.y + a.height],
+        None => [0.0; 4],
+    };
+
+    let mut field = dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Widget",
+        "FT" => "Sig",
+        "T" => Object::string_literal("Signature1"),
+        "V" => signature_id,
+        // Print and Locked
+        "F" => 132,
+        "P" => page_id,
+        "Rect" => rect.iter().map(|&v| Object::Real(v)).collect::<Vec<_>>(),
+    };
+    if let Some(appearance) = &options.appearance {
+        let stream_id = document.add_object(appearance_stream(appearance, signer, now)?);
+        field.set("AP", dictionary! { "N" => stream_id });
+    }
+    let field_id = document.add_object(field);
+
+    append_to_array(document, page_id, b"Annots", field_id.into())?;
+
+    let catalog_id = document.trailer.get(b"Root")?.as_reference()?;
+    let acro_form_id = match document.get_dictionary(catalog_id)?.get(b"AcroForm") {
+        Ok(Object::Reference(id)) => *id,
+        Ok(Object::Dictionary(acro_form)) => {
+            let acro_form = acro_form.clone();
+            document.add_object(acro_form)
+        }
+        _ => document.add_object(Dictionary::new()),
+    };
+    document
+        .get_dictionary_mut(catalog_id)?
+        .set("AcroForm", acro_form_id);
+
+    append_to_array(document, acro_form_id, b"Fields", field_id.into())?;
+    // SignaturesExist and AppendOnly
+    document
+        .get_dictionary_mut(acro_form_id)?
+        .set("SigFlags", 3);
+
+    Ok(())
+}
+
+/// Appends to the array in `key` of a dictionary, whether the array is direct or indirect.
+fn append_to_array(document: &mut Document, id: ObjectId, key: &[u8], value: Object) -> Result<()> {
+    match document.get_dictionary(id)?.get(key) {
+        Ok(Object::Reference(array_id)) => {
+            let array_id = *array_id;
+            document
+                .get_object_mut(array_id)?
+                .as_array_mut()?
+                .push(value);
+        }
+        Ok(Object::Array(_)) => {
+            document
+                .get_dictionary_mut(id)?
+                .get_mut(key)?
+                .as_array_mut()?
+                .push(value);
+        }
+        _ => document.get_dictionary_mut(id)?.set(key, vec![value]),
+    }
+
+    Ok(())
+}
+
+fn appearance_stream(
+    appearance: &SignatureAppearance,
+    signer: &Signer,
+    now: u64,
+) -> Result<Stream> {
+    let (width, height) = (appearance.width, appearance.height);
+    let font_size = (height / 4.0).clamp(4.0, 12.0);
+    let (year, month, day) = civil_from_days((now / 86_400) as i64);
+
+    let lines = [
+        format!("Digitally signed by {}", signer.name()),
+        format!("Date: {:04}-{:02}-{:02}", year, month, day),
+    ];
+
+    let mut operations = vec![
+        Operation::new("q", vec![]),
+        Operation::new("w", vec![0.5.into()]),
+        Operation::new(
+            "re",
+            vec![
+                0.5.into(),
+                0.5.into(),
+                (width - 1.0).into(),
+                (height - 1.0).into(),
+            ],
+        ),
+        Operation::new("S", vec![]),
+        Operation::new("BT", vec![]),
+        Operation::new("Tf", vec!["F1".into(), font_size.into()]),
+        Operation::new("Td", vec![4.0.into(), (height - 4.0 - font_size).into()]),
+        Operation::new("TL", vec![(font_size * 1.2).into()]),
+    ];
+    for line in lines {
+        operations.push(Operation::new("Tj", vec![Object::string_literal(line)]));
+        operations.push(Operation::new("T*", vec![]));
+    }
+    operations.push(Operation::new("ET", vec![]));
+    operations.push(Operation::new("Q", vec![]));
+
+    let content = Content { operations }.encode()?;
+
+    Ok(Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+            "Resources" => dictionary! {
+                "Font" => dictionary! {
+                    "F1" => dictionary! {
+                        "Type" => "Font",
+                        "Subtype" => "Type1",
+                        "BaseFont" => "Helvetica",
+                    },
+                },
+            },
+        },
+        content,
+    ))
+}
+
+/// Writes the offsets of the `/Contents` placeholder in the `/ByteRange` placeholder, padding
+/// with spaces so no other offset moves. Returns the position of the `/Contents` hex string,
+/// including its delimiters.
+fn fill_byte_range(bytes: &mut [u8]) -> Result<(usize, usize)> {
+    let contents = format!("<{}>", "0".repeat(SIGNATURE_SIZE * 2));
+    let contents_start = find(bytes, contents.as_bytes())
+        .ok_or_else(|| anyhow!("Signature placeholder not found"))?;
+    let contents_end = contents_start + contents.len();
+
+    let placeholder = format!("{0} {0} {0}", BYTE_RANGE_PLACEHOLDER);
+    let range_start = find(bytes, placeholder.as_bytes())
+        .ok_or_else(|| anyhow!("Byte range placeholder not found"))?;
+
+    let range = format!(
+        "{:<width$}",
+        format!(
+            "{} {} {}",
+            contents_start,
+            contents_end,
+            bytes.len() - contents_end
+        ),
+        width = placeholder.len()
+    );
+    bytes[range_start..range_start + range.len()].copy_from_slice(range.as_bytes());
+
+    Ok((contents_start, contents_end))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::project::signature::read_signatures;
+    use crate::project::test::{exported_document, project};
+    use crate::project::{Selector, Split};
+    use cms::content_info::ContentInfo;
+    use cms::signed_data::{SignedData, SignerInfo};
+    use der::SliceReader;
+    use rsa::pkcs8::DecodePublicKey;
+    use rsa::signature::Verifier;
+    use rsa::RsaPublicKey;
+
+    fn options(appearance: Option<SignatureAppearance>) -> SigningOptions {
+        SigningOptions {
+            certificate_path: PathBuf::from("test/signing.p12"),
+            password: "rancher".to_string(),
+            reason: Some("Approved".to_string()),
+            location: None,
+            appearance,
+        }
+    }
+
+    fn signer_info(contents: &[u8]) -> (SignedData, SignerInfo) {
+        // The signature is followed by the zero padding of its placeholder.
+        let mut reader = SliceReader::new(contents).unwrap();
+        let content_info = ContentInfo::decode(&mut reader).unwrap();
+        let signed_data = content_info.content.decode_as::<SignedData>().unwrap();
+        let signer_info = signed_data.signer_infos.0.get(0).unwrap().clone();
+        (signed_data, signer_info)
+    }
+
+    #[test]
+    fn test_sign() {
+        let bytes = sign(exported_document(), &options(None)).unwrap();
+        let document = Document::load_mem(&bytes).unwrap();
+
+        let signatures = serde_json::to_value(read_signatures(&document, bytes.len())).unwrap();
+        assert_eq!(signatures[0]["signer"], "PDF Rancher Test");
+        assert_eq!(signatures[0]["covers_whole_document"], true);
+
+        let signature = document
+            .objects
+            .values()
+            .filter_map(|object| object.as_dict().ok())
+            .find(|dict| dict.get(b"Type").and_then(Object::as_name).ok() == Some(b"Sig"))
+            .unwrap();
+        assert_eq!(
+            signature.get(b"Reason").unwrap().as_str().unwrap(),
+            b"Approved"
+        );
+
+        let range = signature
+            .get(b"ByteRange")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value.as_i64().unwrap() as usize)
+            .collect::<Vec<_>>();
+        let digest = Sha256::new()
+            .chain_update(&bytes[range[0]..range[1]])
+            .chain_update(&bytes[range[2]..range[2] + range[3]])
+            .finalize();
+
+        let contents = signature.get(b"Contents").unwrap().as_str().unwrap();
+        let (signed_data, signer_info) = signer_info(contents);
+        let signed_attributes = signer_info.signed_attrs.unwrap();
+
+        let message_digest = signed_attributes
+            .iter()
+            .find(|attribute| attribute.oid.to_string() == "1.2.840.113549.1.9.4")
+            .unwrap();
+        let message_digest = message_digest
+            .values
+            .get(0)
+            .unwrap()
+            .decode_as::<OctetString>();
+        assert_eq!(message_digest.unwrap().as_bytes(), &digest[..]);
+
+        let Some(CertificateChoices::Certificate(certificate)) = signed_data
+            .certificates
+            .as_ref()
+            .and_then(|certificates| certificates.0.get(0))
+        else {
+            panic!("Missing certificate");
+        };
+        let public_key = RsaPublicKey::from_public_key_der(
+            &certificate
+                .tbs_certificate
+                .subject_public_key_info
+                .to_der()
+                .unwrap(),
+        )
+        .unwrap();
+        let signature =
+            rsa::pkcs1v15::Signature::try_from(signer_info.signature.as_bytes()).unwrap();
+        rsa::pkcs1v15::VerifyingKey::<Sha256>::new(public_key)
+            .verify(&signed_attributes.to_der().unwrap(), &signature)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_sign_split_parts() {
+        let project = project(&["test/basic.pdf"]);
+        let selectors = (0..3)
+            .map(|page| Selector::new(0, page))
+            .collect::<Vec<_>>();
+        let options = options(Some(SignatureAppearance {
+            page_index: 1,
+            x: 400.0,
+            y: 50.0,
+            width: 160.0,
+            height: 40.0,
+        }));
+
+        for (count, appearances) in [(1, vec![None, None, None]), (2, vec![Some(1), None])] {
+            let (parts, _) = project
+                .export_parts(
+                    &selectors,
+                    &project.default_metadata(),
+                    Some(&Split::Pages { count }),
+                    |document| {
+                        let options = options.for_document(&document);
+                        Ok((sign(document, &options)?, options.appearance_page()))
+                    },
+                )
+                .unwrap();
+
+            let signed = parts.iter().map(|part| *part.output()).collect::<Vec<_>>();
+            assert_eq!(signed, appearances);
+        }
+    }
+
+    #[test]
+    fn test_sign_with_appearance() {
+        let appearance = SignatureAppearance {
+            page_index: 0,
+            x: 400.0,
+            y: 50.0,
+            width: 160.0,
+            height: 40.0,
+        };
+        let bytes = sign(exported_document(), &options(Some(appearance))).unwrap();
+        let document = Document::load_mem(&bytes).unwrap();
+
+        let page_id = *document.get_pages().get(&1).unwrap();
+        let annotations = document.get_page_annotations(page_id).unwrap();
+        assert_eq!(annotations.len(), 1);
+
+        let widget = annotations[0];
+        assert!(widget.get(b"AP").is_ok());
+        assert_eq!(
+            widget
+                .get(b"Rect")
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|value| value.as_float().unwrap())
+                .collect::<Vec<_>>(),
+            vec![400.0, 50.0, 560.0, 90.0]
+        );
+    }
+
+    #[test]
+    fn test_sign_wrong_password() {
+        let mut options = options(None);
+        options.password = "wrong".to_string();
+
+        assert!(sign(exported_document(), &options).is_err());
+    }
+}
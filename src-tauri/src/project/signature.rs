@@ -0,0 +1,246 @@
+use super::{Project, Selector};
+use lopdf::{decode_text_string, Dictionary, Document, Object, ObjectId};
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+/// A digital signature found in a source file.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Signature {
+    /// Name of the signature field (`/T`).
+    field: Option<String>,
+    /// Name of the signer (`/Name`), when the signature carries it.
+    signer: Option<String>,
+    /// Signing time (`/M`), as a PDF date string.
+    signed_at: Option<String>,
+    /// The signed byte ranges cover the whole file, i.e. it wasn't modified after signing.
+    covers_whole_document: bool,
+}
+
+/// Reads the signatures of a document loaded from `file_len` bytes: the signature fields of its
+/// form, and signature dictionaries outside of it, which are recognized by their `/ByteRange`.
+pub(super) fn read_signatures(document: &Document, file_len: usize) -> Vec<Signature> {
+    let mut fields = Vec::new();
+    if let Some(form_fields) = document
+        .catalog()
+        .ok()
+        .and_then(|catalog| catalog.get(b"AcroForm").ok())
+        .and_then(|form| document.dereference(form).ok())
+        .and_then(|(_, form)| form.as_dict().ok())
+        .and_then(|form| form.get(b"Fields").and_then(Object::as_array).ok())
+    {
+        let mut visited = BTreeSet::new();
+        for field in form_fields {
+            collect_signature_fields(document, field, None, None, &mut visited, &mut fields);
+        }
+    }
+
+    let mut found = BTreeSet::new();
+    let mut signatures = Vec::new();
+    for (name, value) in fields {
+        let Ok((id, signature)) = document.dereference(value) else {
+            continue;
+        };
+        let Ok(signature) = signature.as_dict() else {
+            continue;
+        };
+        found.extend(id);
+        signatures.push(signature_of(signature, name, file_len));
+    }
+
+    // Signatures whose field isn't reachable from the form, like in damaged documents.
+    for (id, object) in &document.objects {
+        let Ok(signature) = object.as_dict() else {
+            continue;
+        };
+        if signature.has(b"ByteRange") && !found.contains(id) {
+            signatures.push(signature_of(signature, None, file_len));
+        }
+    }
+
+    signatures
+}
+
+/// Collects the fully qualified name and `/V` value of the signature fields of the tree rooted at
+/// `field`. The field type is inherited from parent fields.
+fn collect_signature_fields<'a>(
+    document: &'a Document,
+    field: &'a Object,
+    inherited_type: Option<&'a [u8]>,
+    parent_name: Option<&str>,
+    visited: &mut BTreeSet<ObjectId>,
+    fields: &mut Vec<(Option<String>, &'a Object)>,
+) {
+    if let Object::Reference(id) = field {
+        if !visited.insert(*id) {
+            return;
+        }
+    }
+    let Ok(field) = document
+        .dereference(field)
+        .and_then(|(_, field)| field.as_dict())
+    else {
+        return;
+    };
+
+    let field_type = field
+        .get(b"FT")
+        .and_then(Object::as_name)
+        .ok()
+        .or(inherited_type);
+    let name = match (parent_name, text(field, b"T")) {
+        (Some(parent), Some(name)) => Some(format!("{}.{}", parent, name)),
+        (parent, name) => name.or(parent.map(str::to_string)),
+    };
+
+    if field_type == Some(b"Sig".as_slice()) {
+        if let Ok(value) = field.get(b"V") {
+            fields.push((name.clone(), value));
+        }
+    }
+
+    if let Ok(kids) = field.get(b"Kids").and_then(Object::as_array) {
+        for kid in kids {
+            collect_signature_fields(document, kid, field_type, name.as_deref(), visited, fields);
+        }
+    }
+}
+
+fn signature_of(signature: &Dictionary, field: Option<String>, file_len: usize) -> Signature {
+    Signature {
+        field,
+        signer: text(signature, b"Name"),
+        signed_at: text(signature, b"M"),
+        covers_whole_document: covers_whole_document(signature, file_len),
+    }
+}
+
+fn text(dict: &Dictionary, key: &[u8]) -> Option<String> {
+    dict.get(key).and_then(decode_text_string).ok()
+}
+
+/// A signature covers the whole file when its `/ByteRange` starts at the first byte and ends at
+/// the last one, leaving out only the signature itself.
+fn covers_whole_document(signature: &Dictionary, file_len: usize) -> bool {
+    let Ok(range) = signature.get(b"ByteRange").and_then(Object::as_array) else {
+        return false;
+    };
+    let range = range
+        .iter()
+        .map(|value| value.as_i64().ok().and_then(|v| usize::try_from(v).ok()))
+        .collect::<Option<Vec<_>>>();
+
+    match range.as_deref() {
+        Some(&[start, first_len, second_start, second_len]) => {
+            start == 0 && start + first_len <= second_start && second_start + second_len == file_len
+        }
+        _ => false,
+    }
+}
+
+impl Project {
+    /// Names of the signed source files the selected pages come from. Exporting rewrites these
+    /// documents, which invalidates their signatures.
+    pub fn signed_sources(&self, selectors: &[Selector]) -> Vec<String> {
+        selectors
+            .iter()
+            .map(|selector| selector.source_file_index)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter_map(|index| self.source_files.get(index))
+            .filter(|source_file| !source_file.signatures.is_empty())
+            .map(|source_file| source_file.name())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lopdf::dictionary;
+
+    /// A document whose form has a signature field, inheriting its type from a parent field.
+    fn signed_document(byte_range: Vec<Object>) -> Document {
+        let mut document = Document::with_version("1.5");
+        let signature_id = document.add_object(dictionary! {
+            "Type" => "Sig",
+            "Name" => Object::string_literal("Jane Doe"),
+            "M" => Object::string_literal("D:20240101120000Z"),
+            "ByteRange" => byte_range,
+        });
+        let field_id = document.add_object(dictionary! {
+            "T" => Object::string_literal("Signature1"),
+            "V" => signature_id,
+        });
+        let parent_id = document.add_object(dictionary! {
+            "FT" => "Sig",
+            "Kids" => vec![field_id.into()],
+        });
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "AcroForm" => dictionary! {
+                "Fields" => vec![parent_id.into()],
+            },
+        });
+        document.trailer.set("Root", catalog_id);
+        document
+    }
+
+    #[test]
+    fn test_read_signatures() {
+        let document = signed_document(vec![0.into(), 100.into(), 200.into(), 50.into()]);
+
+        let signatures = read_signatures(&document, 250);
+
+        assert_eq!(
+            vec![Signature {
+                field: Some("Signature1".to_string()),
+                signer: Some("Jane Doe".to_string()),
+                signed_at: Some("D:20240101120000Z".to_string()),
+                covers_whole_document: true,
+            }],
+            signatures
+        );
+    }
+
+    #[test]
+    fn test_read_signatures_modified_after_signing() {
+        let document = signed_document(vec![0.into(), 100.into(), 200.into(), 50.into()]);
+
+        // An incremental update was appended after the signed bytes.
+        let signatures = read_signatures(&document, 400);
+
+        assert_eq!(1, signatures.len());
+        assert!(!signatures[0].covers_whole_document);
+    }
+
+    #[test]
+    fn test_read_signatures_ignores_empty_fields() {
+        let mut document = Document::with_version("1.5");
+        document.add_object(dictionary! {
+            "FT" => "Sig",
+            "T" => Object::string_literal("Signature1"),
+        });
+
+        assert!(read_signatures(&document, 100).is_empty());
+    }
+
+    #[test]
+    fn test_read_signatures_without_field() {
+        let mut document = Document::with_version("1.5");
+        document.add_object(dictionary! {
+            "Type" => "Sig",
+            // "Zoë" as a UTF-16 text string.
+            "Name" => Object::String(
+                vec![0xFE, 0xFF, 0x00, 0x5A, 0x00, 0x6F, 0x00, 0xEB],
+                lopdf::StringFormat::Hexadecimal,
+            ),
+            "ByteRange" => vec![0.into(), 10.into(), 20.into(), 30.into()],
+        });
+
+        let signatures = read_signatures(&document, 50);
+
+        assert_eq!(1, signatures.len());
+        assert_eq!(None, signatures[0].field);
+        assert_eq!(Some("Zoë".to_string()), signatures[0].signer);
+    }
+}
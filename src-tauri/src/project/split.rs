@@ -0,0 +1,505 @@
+use super::{DocumentMetadata, ExportReport, Project, Selector};
+use anyhow::{anyhow, Result};
+use lopdf::Document;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Characters that can't be part of a file name on one platform or another.
+const INVALID_FILE_NAME_CHARACTERS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// How an export is split in several files.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "mode")]
+pub enum Split {
+    /// Consecutive parts of at most `max_size` bytes each.
+    Size { max_size: usize },
+    /// One file per `count` pages, one per page with a count of 1.
+    Pages { count: usize },
+    /// One file per run of consecutive pages from the same source.
+    Source,
+    /// One file per run of consecutive pages of the same group.
+    Group,
+}
+
+impl Split {
+    /// Whether the parts are written to a folder and named from a template, rather than numbered
+    /// after the file chosen by the user.
+    pub fn uses_template(&self) -> bool {
+        !matches!(self, Split::Size { .. })
+    }
+
+    /// Pages of each part, for splits that don't depend on the exported files.
+    fn ranges(&self, selectors: &[Selector]) -> Result<Vec<Range<usize>>> {
+        let same_run = |a: &Selector, b: &Selector| match self {
+            Split::Source => a.source_file_index == b.source_file_index,
+            _ => a.group == b.group,
+        };
+
+        match self {
+            Split::Size { .. } => Err(anyhow!("Splitting by size requires exporting the parts")),
+            Split::Pages { count: 0 } => Err(anyhow!("Invalid number of pages per file: 0")),
+            Split::Pages { count } => Ok((0..selectors.len())
+                .step_by(*count)
+                .map(|start| start..(start + count).min(selectors.len()))
+                .collect()),
+            Split::Source | Split::Group => {
+                let mut ranges: Vec<Range<usize>> = Vec::new();
+                for (index, selector) in selectors.iter().enumerate() {
+                    match ranges.last_mut() {
+                        Some(range) if same_run(&selectors[range.start], selector) => {
+                            range.end = index + 1
+                        }
+                        _ => ranges.push(index..index + 1),
+                    }
+                }
+                Ok(ranges)
+            }
+        }
+    }
+}
+
+/// A file of an export.
+#[derive(Debug)]
+pub struct ExportPart<T> {
+    pages: Range<usize>,
+    bytes: Vec<u8>,
+    output: T,
+    oversized: bool,
+}
+
+impl<T> ExportPart<T> {
+    /// Positions of the part's pages in the ordering.
+    pub fn pages(&self) -> Range<usize> {
+        self.pages.clone()
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// What finishing the part returned besides its bytes.
+    pub fn output(&self) -> &T {
+        &self.output
+    }
+
+    /// Whether the part is over the maximum size. It then holds a single page, or group, too
+    /// large on its own.
+    pub fn is_oversized(&self) -> bool {
+        self.oversized
+    }
+}
+
+impl Project {
+    /// Exports the selected pages in one file, or several when splitting. `finish` turns each
+    /// exported document into the bytes of its file, sizes are measured on these bytes.
+    pub fn export_parts<T>(
+        &self,
+        selectors: &[Selector],
+        metadata: &DocumentMetadata,
+        split: Option<&Split>,
+        mut finish: impl FnMut(Document) -> Result<(Vec<u8>, T)>,
+    ) -> Result<(Vec<ExportPart<T>>, ExportReport)> {
+        let parts = match split {
+            None => vec![self.export_part(selectors, 0..selectors.len(), metadata, &mut finish)?],
+            Some(Split::Size { max_size }) => {
+                self.split_by_size(selectors, metadata, *max_size, &mut finish)?
+            }
+            Some(split) => split
+                .ranges(selectors)?
+                .into_iter()
+                .map(|pages| self.export_part(selectors, pages, metadata, &mut finish))
+                .collect::<Result<Vec<_>>>()?,
+        };
+
+        Ok((parts, ExportReport::new(self, selectors)))
+    }
+
+    fn export_part<T>(
+        &self,
+        selectors: &[Selector],
+        pages: Range<usize>,
+        metadata: &DocumentMetadata,
+        finish: &mut impl FnMut(Document) -> Result<(Vec<u8>, T)>,
+    ) -> Result<ExportPart<T>> {
+        let (document, _) = self.export_with_metadata(&selectors[pages.clone()], metadata)?;
+        let (bytes, output) = finish(document)?;
+
+        Ok(ExportPart {
+            pages,
+            bytes,
+            output,
+            oversized: false,
+        })
+    }
+
+    /// Fills each part with as many groups as fit, searching for the largest count with a few
+    /// exports rather than one per page.
+    fn split_by_size<T>(
+        &self,
+        selectors: &[Selector],
+        metadata: &DocumentMetadata,
+        max_size: usize,
+        finish: &mut impl FnMut(Document) -> Result<(Vec<u8>, T)>,
+    ) -> Result<Vec<ExportPart<T>>> {
+        if selectors.is_empty() {
+            return Err(anyhow!("No pages to export"));
+        }
+
+        let groups = group_ranges(selectors);
+        let mut parts = Vec::new();
+        let mut start = 0;
+
+        while start < groups.len() {
+            let mut export = |count: usize| {
+                let pages = groups[start].start..groups[start + count - 1].end;
+                self.export_part(selectors, pages, metadata, finish)
+            };
+
+            // Double the number of groups until the part is too large, then bisect.
+            let mut fitting: Option<(usize, ExportPart<T>)> = None;
+            let mut too_large = None;
+            let mut count = 1;
+            loop {
+                let part = export(count)?;
+                if part.bytes.len() > max_size {
+                    too_large = Some((count, part));
+                    break;
+                }
+                fitting = Some((count, part));
+                if start + count == groups.len() {
+                    break;
+                }
+                count = (count * 2).min(groups.len() - start);
+            }
+
+            if let Some((mut high, _)) = too_large {
+                let mut low = fitting.as_ref().map_or(0, |(count, _)| *count);
+                while high - low > 1 {
+                    let middle = (low + high) / 2;
+                    let part = export(middle)?;
+                    if part.bytes.len() > max_size {
+                        high = middle;
+                    } else {
+                        low = middle;
+                        fitting = Some((middle, part));
+                    }
+                }
+            }
+
+            let (count, part) = match (fitting, too_large) {
+                (Some(fitting), _) => fitting,
+                (None, Some((count, mut part))) => {
+                    part.oversized = true;
+                    (count, part)
+                }
+                (None, None) => unreachable!("every part is either fitting or too large"),
+            };
+
+            parts.push(part);
+            start += count;
+        }
+
+        Ok(parts)
+    }
+}
+
+impl Project {
+    /// Paths of the parts of an export to `folder`, named after `template`. Names already taken,
+    /// by another part or an existing file, get a number appended so nothing is overwritten.
+    ///
+    /// The template can use `{source}`, the name of the source of the part's first page,
+    /// `{first_page}`, that page's number in its source, `{group}`, its group, and `{index}`,
+    /// the number of the part.
+    pub fn part_paths<T>(
+        &self,
+        folder: &Path,
+        template: &str,
+        selectors: &[Selector],
+        parts: &[ExportPart<T>],
+    ) -> Vec<PathBuf> {
+        self.template_paths(
+            folder,
+            template,
+            "pdf",
+            selectors,
+            parts.iter().map(|part| part.pages.start),
+        )
+    }
+
+    /// Paths of files named after `template`, one per page given by its position in the
+    /// ordering. See [`Project::part_paths`] for the variables.
+    pub(super) fn template_paths(
+        &self,
+        folder: &Path,
+        template: &str,
+        extension: &str,
+        selectors: &[Selector],
+        first_pages: impl Iterator<Item = usize>,
+    ) -> Vec<PathBuf> {
+        let mut taken = BTreeSet::new();
+        let suffix = format!(".{}", extension);
+
+        first_pages
+            .enumerate()
+            .map(|(index, first_page)| {
+                let first = &selectors[first_page];
+                let source = self
+                    .source_files
+                    .get(first.source_file_index)
+                    .map(|source_file| source_file.name())
+                    .unwrap_or_default();
+                let source = Path::new(&source)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+
+                let name = template
+                    .replace("{source}", &source)
+                    .replace("{first_page}", &(first.page_index + 1).to_string())
+                    .replace("{group}", first.group.as_deref().unwrap_or_default())
+                    .replace("{index}", &(index + 1).to_string())
+                    .replace(INVALID_FILE_NAME_CHARACTERS, "_");
+                let name = name
+                    .strip_suffix(&suffix)
+                    .unwrap_or(&name)
+                    .trim()
+                    .to_string();
+                let name = if name.is_empty() {
+                    format!("{}", index + 1)
+                } else {
+                    name
+                };
+
+                let path = (1..)
+                    .map(|number| match number {
+                        1 => folder.join(format!("{}{}", name, suffix)),
+                        _ => folder.join(format!("{} ({}){}", name, number, suffix)),
+                    })
+                    .find(|path| !path.exists() && !taken.contains(path))
+                    .expect("there is always a free name");
+
+                taken.insert(path.clone());
+                path
+            })
+            .collect()
+    }
+}
+
+/// Ranges of the selectors that must stay in the same file: consecutive pages of a group, or
+/// single pages outside of any.
+pub(super) fn group_ranges(selectors: &[Selector]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+
+    for (index, selector) in selectors.iter().enumerate() {
+        match ranges.last_mut() {
+            Some(range)
+                if selector.group.is_some() && selectors[range.start].group == selector.group =>
+            {
+                range.end = index + 1
+            }
+            _ => ranges.push(index..index + 1),
+        }
+    }
+
+    ranges
+}
+
+/// Path of the `number`th part of an export to `path`, `name-part1.pdf` for `name.pdf`.
+pub fn part_path(path: &Path, number: usize) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut name = format!("{}-part{}", stem, number);
+    if let Some(extension) = path.extension() {
+        name = format!("{}.{}", name, extension.to_string_lossy());
+    }
+
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::project::test::project;
+
+    fn save(mut document: Document) -> Result<(Vec<u8>, ())> {
+        let mut bytes = Vec::new();
+        document.save_to(&mut bytes)?;
+        Ok((bytes, ()))
+    }
+
+    fn grouped(page_index: usize, group: Option<&str>) -> Selector {
+        let mut selector = Selector::new(0, page_index);
+        selector.group = group.map(String::from);
+        selector
+    }
+
+    fn size(project: &Project, selectors: &[Selector]) -> usize {
+        let (parts, _) = project
+            .export_parts(selectors, &project.default_metadata(), None, save)
+            .unwrap();
+        parts[0].bytes().len()
+    }
+
+    #[test]
+    fn test_split_by_size() {
+        let project = project(&["test/basic.pdf"]);
+        let selectors = (0..3)
+            .map(|page| Selector::new(0, page))
+            .collect::<Vec<_>>();
+        let max_size = size(&project, &selectors[0..2]);
+
+        let (parts, _) = project
+            .export_parts(
+                &selectors,
+                &project.default_metadata(),
+                Some(&Split::Size { max_size }),
+                save,
+            )
+            .unwrap();
+
+        assert_eq!(
+            parts.iter().map(ExportPart::pages).collect::<Vec<_>>(),
+            vec![0..2, 2..3]
+        );
+        assert!(parts.iter().all(|part| part.bytes().len() <= max_size));
+        assert!(parts.iter().all(|part| !part.is_oversized()));
+    }
+
+    #[test]
+    fn test_split_by_size_keeps_groups() {
+        let project = project(&["test/basic.pdf"]);
+        let selectors = vec![
+            grouped(0, Some("a")),
+            grouped(1, Some("a")),
+            grouped(2, None),
+        ];
+        let max_size = size(&project, &selectors[2..3]);
+
+        let (parts, _) = project
+            .export_parts(
+                &selectors,
+                &project.default_metadata(),
+                Some(&Split::Size { max_size }),
+                save,
+            )
+            .unwrap();
+
+        assert_eq!(
+            parts.iter().map(ExportPart::pages).collect::<Vec<_>>(),
+            vec![0..2, 2..3]
+        );
+        assert!(parts[0].is_oversized());
+        assert!(!parts[1].is_oversized());
+    }
+
+    #[test]
+    fn test_split_ranges() {
+        let selectors = vec![
+            grouped(0, None),
+            grouped(1, Some("a")),
+            grouped(2, Some("a")),
+            Selector::new(1, 0),
+            Selector::new(1, 1),
+        ];
+
+        assert_eq!(
+            Split::Pages { count: 2 }.ranges(&selectors).unwrap(),
+            vec![0..2, 2..4, 4..5]
+        );
+        assert_eq!(Split::Source.ranges(&selectors).unwrap(), vec![0..3, 3..5]);
+        assert_eq!(
+            Split::Group.ranges(&selectors).unwrap(),
+            vec![0..1, 1..3, 3..5]
+        );
+        assert!(Split::Pages { count: 0 }.ranges(&selectors).is_err());
+    }
+
+    #[test]
+    fn test_export_parts() {
+        let project = project(&["test/basic.pdf"]);
+        let selectors = (0..3)
+            .map(|page| Selector::new(0, page))
+            .collect::<Vec<_>>();
+
+        let (parts, _) = project
+            .export_parts(
+                &selectors,
+                &project.default_metadata(),
+                Some(&Split::Pages { count: 1 }),
+                save,
+            )
+            .unwrap();
+
+        assert_eq!(parts.len(), 3);
+        for part in &parts {
+            let document = Document::load_mem(part.bytes()).unwrap();
+            assert_eq!(document.get_pages().len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_part_paths() {
+        let project = project(&["test/basic.pdf"]);
+        let selectors = vec![grouped(0, Some("a/b")), grouped(2, None)];
+        let parts = vec![
+            ExportPart {
+                pages: 0..1,
+                bytes: Vec::new(),
+                output: (),
+                oversized: false,
+            },
+            ExportPart {
+                pages: 1..2,
+                bytes: Vec::new(),
+                output: (),
+                oversized: false,
+            },
+        ];
+
+        assert_eq!(
+            project.part_paths(Path::new("test"), "{source}", &selectors, &parts),
+            vec![
+                PathBuf::from("test/basic (2).pdf"),
+                PathBuf::from("test/basic (3).pdf")
+            ]
+        );
+        assert_eq!(
+            project.part_paths(
+                Path::new("out"),
+                "{group}-{index}-p{first_page}.pdf",
+                &selectors,
+                &parts
+            ),
+            vec![
+                PathBuf::from("out/a_b-1-p1.pdf"),
+                PathBuf::from("out/-2-p3.pdf")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_group_ranges() {
+        let selectors = vec![
+            grouped(0, None),
+            grouped(1, None),
+            grouped(2, Some("a")),
+            grouped(0, Some("a")),
+            grouped(1, Some("b")),
+            grouped(2, Some("a")),
+        ];
+
+        assert_eq!(group_ranges(&selectors), vec![0..1, 1..2, 2..4, 4..5, 5..6]);
+    }
+
+    #[test]
+    fn test_part_path() {
+        assert_eq!(
+            part_path(Path::new("/tmp/project.pdf"), 2),
+            PathBuf::from("/tmp/project-part2.pdf")
+        );
+    }
+}
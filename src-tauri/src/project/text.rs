@@ -0,0 +1,351 @@
+use super::{pdfium, Project, Selector};
+use anyhow::Result;
+use pdfium_render::prelude::*;
+use serde::Deserialize;
+
+/// Blank lines kept between two lines of a page laid out as it is printed. Larger gaps are
+/// usually figures or the space between a header and the body.
+const MAX_BLANK_LINES: usize = 2;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TextFormat {
+    #[default]
+    Plain,
+    /// Markdown with a heading per page.
+    Markdown,
+}
+
+impl TextFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TextFormat::Plain => "txt",
+            TextFormat::Markdown => "md",
+        }
+    }
+}
+
+/// How the text of the selected pages is extracted.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TextOptions {
+    #[serde(default)]
+    format: TextFormat,
+    /// Places the text of a page where it is printed, keeping columns and tables aligned,
+    /// instead of in reading order.
+    #[serde(default)]
+    preserve_layout: bool,
+    /// Writes a note for pages without any text, which are often scans.
+    #[serde(default)]
+    mark_empty_pages: bool,
+}
+
+impl TextOptions {
+    pub fn format(&self) -> TextFormat {
+        self.format
+    }
+}
+
+/// A character of a page, with its origin and size in points.
+#[derive(Debug, Clone, PartialEq)]
+struct Glyph {
+    character: char,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+impl Project {
+    /// Text of the selected pages, in the order of the selectors. The text is extracted from the
+    /// export itself, so redacted text is left out.
+    pub fn export_text(&self, selectors: &[Selector], options: &TextOptions) -> Result<String> {
+        let (mut document, _) = self.export(selectors)?;
+        let mut bytes = Vec::new();
+        document.save_to(&mut bytes)?;
+
+        let pdfium = pdfium()?;
+        let document = pdfium.load_pdf_from_byte_slice(&bytes, None)?;
+
+        let mut sections = Vec::new();
+        for (index, (page, selector)) in document.pages().iter().zip(selectors).enumerate() {
+            let text = page.text()?;
+            let text = if options.preserve_layout {
+                layout_text(&glyphs(&text))
+            } else {
+                reading_order_text(&text.all())
+            };
+
+            let source_file = &self.source_files[selector.source_file_index];
+            let label = format!(
+                "Page {} ({}, page {})",
+                index + 1,
+                source_file.name(),
+                selector.page_index + 1
+            );
+
+            if let Some(section) = section(&label, &text, options) {
+                sections.push(section);
+            }
+        }
+
+        Ok(sections.join("\n\n") + "\n")
+    }
+}
+
+/// The text of a page as written to the export, `None` when there's nothing to write.
+fn section(label: &str, text: &str, options: &TextOptions) -> Option<String> {
+    let empty = text.trim().is_empty();
+
+    match options.format {
+        TextFormat::Plain if empty => options
+            .mark_empty_pages
+            .then(|| format!("[{} has no text]", label)),
+        TextFormat::Plain => Some(text.to_string()),
+        TextFormat::Markdown => {
+            let body = if empty {
+                options.mark_empty_pages.then(|| "*No text*".to_string())
+            } else if options.preserve_layout {
+                Some(code_block(text))
+            } else {
+                Some(
+                    text.lines()
+                        .map(escape_markdown)
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                )
+            };
+
+            Some(match body {
+                Some(body) => format!("## {}\n\n{}", escape_markdown(label), body),
+                None => format!("## {}", escape_markdown(label)),
+            })
+        }
+    }
+}
+
+/// Characters of a page, without the spaces and line breaks, which are placed back from the
+/// positions of the others.
+fn glyphs(text: &PdfPageText) -> Vec<Glyph> {
+    text.chars()
+        .iter()
+        .filter_map(|char| {
+            let character = char.unicode_char()?;
+            if character.is_whitespace() || character.is_control() {
+                return None;
+            }
+
+            let bounds = char.loose_bounds().ok()?;
+            Some(Glyph {
+                character,
+                x: bounds.left().value,
+                y: bounds.bottom().value,
+                width: bounds.width().value,
+                height: bounds.height().value,
+            })
+        })
+        .collect()
+}
+
+/// Lays glyphs out on a grid of characters, so text keeps its position on the page. Cells are
+/// as wide as the median glyph, and lines as tall as the median glyph.
+fn layout_text(glyphs: &[Glyph]) -> String {
+    if glyphs.is_empty() {
+        return String::new();
+    }
+
+    let cell_width = median(glyphs.iter().map(|glyph| glyph.width)).max(1.0);
+    let line_height = median(glyphs.iter().map(|glyph| glyph.height)).max(1.0);
+    let left = glyphs
+        .iter()
+        .map(|glyph| glyph.x)
+        .fold(f32::INFINITY, f32::min);
+
+    // Top to bottom, since PDF coordinates go up.
+    let mut glyphs = glyphs.to_vec();
+    glyphs.sort_by(|a, b| b.y.total_cmp(&a.y));
+
+    let mut lines: Vec<(f32, Vec<Glyph>)> = Vec::new();
+    for glyph in glyphs {
+        match lines.last_mut() {
+            Some((y, line)) if *y - glyph.y < line_height / 2.0 => line.push(glyph),
+            _ => lines.push((glyph.y, vec![glyph])),
+        }
+    }
+
+    let mut text = Vec::new();
+    let mut previous_y: Option<f32> = None;
+    for (y, mut line) in lines {
+        if let Some(previous_y) = previous_y {
+            let blank_lines = ((previous_y - y) / line_height).round() as usize;
+            for _ in 1..blank_lines.min(MAX_BLANK_LINES + 1) {
+                text.push(String::new());
+            }
+        }
+        previous_y = Some(y);
+
+        line.sort_by(|a, b| a.x.total_cmp(&b.x));
+        let mut row = String::new();
+        let mut columns = 0;
+        for glyph in line {
+            let column = ((glyph.x - left) / cell_width).round() as usize;
+            // Glyphs wider than a cell push the next ones right rather than overlap them.
+            let gap = column.saturating_sub(columns);
+            row.push_str(&" ".repeat(gap));
+            row.push(glyph.character);
+            columns += gap + 1;
+        }
+        text.push(row);
+    }
+
+    text.join("\n")
+}
+
+fn median(values: impl Iterator<Item = f32>) -> f32 {
+    let mut values = values.collect::<Vec<_>>();
+    values.sort_by(f32::total_cmp);
+    values.get(values.len() / 2).copied().unwrap_or_default()
+}
+
+/// Text as pdfium orders it, with trailing spaces and runs of blank lines removed.
+fn reading_order_text(text: &str) -> String {
+    let mut lines = Vec::new();
+    let mut blank = false;
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank = !lines.is_empty();
+            continue;
+        }
+        if blank {
+            lines.push("");
+            blank = false;
+        }
+        lines.push(line);
+    }
+
+    lines.join("\n")
+}
+
+/// Escapes the characters Markdown would read as formatting.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        if "\\`*_[]#<>|".contains(character) {
+            escaped.push('\\');
+        }
+        escaped.push(character);
+    }
+    escaped
+}
+
+/// A fenced code block, with a fence longer than any run of backticks in the text.
+fn code_block(text: &str) -> String {
+    let longest_run = text
+        .split(|character| character != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest_run.max(2) + 1);
+
+    format!("{}\n{}\n{}", fence, text, fence)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::project::SourceFile;
+    use std::path::PathBuf;
+
+    fn glyph(character: char, column: usize, line: usize) -> Glyph {
+        Glyph {
+            character,
+            x: 72.0 + column as f32 * 6.0,
+            y: 720.0 - line as f32 * 12.0,
+            width: 6.0,
+            height: 12.0,
+        }
+    }
+
+    fn glyphs(lines: &[&str]) -> Vec<Glyph> {
+        lines
+            .iter()
+            .enumerate()
+            .flat_map(|(line, text)| {
+                text.chars()
+                    .enumerate()
+                    .filter(|(_, character)| *character != ' ')
+                    .map(move |(column, character)| glyph(character, column, line))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_layout_text() {
+        let lines = ["Name     Total", "", "Apples      12", "Pears        3"];
+
+        assert_eq!(layout_text(&glyphs(&lines)), lines.join("\n"));
+    }
+
+    #[test]
+    fn test_layout_text_gaps() {
+        let mut glyphs = glyphs(&["Header"]);
+        glyphs.push(glyph('1', 5, 40));
+
+        assert_eq!(layout_text(&glyphs), "Header\n\n\n     1");
+        assert_eq!(layout_text(&[]), "");
+    }
+
+    #[test]
+    fn test_reading_order_text() {
+        assert_eq!(
+            reading_order_text("\r\nFirst  \r\n\r\n\r\n\r\nSecond\r\n\r\n"),
+            "First\n\nSecond"
+        );
+    }
+
+    #[test]
+    fn test_section() {
+        let plain = TextOptions::default();
+        let markdown = TextOptions {
+            format: TextFormat::Markdown,
+            mark_empty_pages: true,
+            ..Default::default()
+        };
+
+        assert_eq!(section("Page 1", "Text", &plain).as_deref(), Some("Text"));
+        assert_eq!(section("Page 1", " \n", &plain), None);
+        assert_eq!(
+            section("Page 1", "*Bold* #1", &markdown).as_deref(),
+            Some("## Page 1\n\n\\*Bold\\* \\#1")
+        );
+        assert_eq!(
+            section("Page 2", "", &markdown).as_deref(),
+            Some("## Page 2\n\n*No text*")
+        );
+    }
+
+    #[test]
+    fn test_code_block() {
+        assert_eq!(code_block("a\n  b"), "```\na\n  b\n```");
+        assert_eq!(code_block("```rust"), "````\n```rust\n````");
+    }
+
+    #[test]
+    fn test_export_text() {
+        let project = Project {
+            source_files: vec![SourceFile::open(&PathBuf::from("test/basic.pdf"), None).unwrap()],
+        };
+        let options = TextOptions {
+            format: TextFormat::Markdown,
+            mark_empty_pages: true,
+            ..Default::default()
+        };
+
+        let text = project
+            .export_text(&[Selector::new(0, 2), Selector::new(0, 0)], &options)
+            .unwrap();
+
+        assert!(text.starts_with("## Page 1 (basic.pdf, page 3)\n"));
+        assert!(text.contains("## Page 2 (basic.pdf, page 1)\n"));
+    }
+}
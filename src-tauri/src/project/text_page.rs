@@ -0,0 +1,872 @@
+use super::blank::{neighbor, PageSize};
+use super::metadata::{civil_from_days, now};
+use super::{
+    load_pdf_pages_from_bytes, random_id, search, Project, Rotation, Selector, Source, SourceFile,
+};
+use anyhow::{anyhow, Result};
+use lopdf::content::{Content, Operation};
+use lopdf::{Dictionary, Document, Object, Stream, StringFormat};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+use ttf_parser::{Face, GlyphId};
+
+/// Font of generated pages, embedded with the glyphs each page uses so the text renders the same
+/// everywhere.
+const FONT: &[u8] = include_bytes!("../../fonts/DejaVuSans.ttf");
+const FONT_NAME: &str = "DejaVuSans";
+/// Tables of the embedded font, the ones PDF viewers need to draw glyphs addressed by index.
+const SUBSET_TABLES: [&[u8; 4]; 9] = [
+    b"cvt ", b"fpgm", b"glyf", b"head", b"hhea", b"hmtx", b"loca", b"maxp", b"prep",
+];
+
+/// Margin around the text, smaller on pages too small for it.
+const MARGIN: f32 = 72.0;
+const TITLE_SIZE: f32 = 32.0;
+const SUBTITLE_SIZE: f32 = 18.0;
+const TEXT_SIZE: f32 = 12.0;
+/// Height of a line relative to the font size.
+const LINE_HEIGHT: f32 = 1.25;
+/// Text that doesn't fit is shrunk down to this fraction of its size, and overflows past it.
+const MIN_SCALE: f32 = 0.25;
+
+/// Text of a generated page as entered by the user. `{source_name}`, `{date}` and
+/// `{page_count}` are replaced by the name of the source the page introduces, today's date, and
+/// the number of pages of that source in the ordering.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TextPageTemplate {
+    title: String,
+    #[serde(default)]
+    subtitle: String,
+    #[serde(default)]
+    text: String,
+    size: PageSize,
+}
+
+/// A generated page, with its variables replaced.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub(super) struct TextPage {
+    title: String,
+    subtitle: String,
+    text: String,
+    /// Width and height in points.
+    width: f32,
+    height: f32,
+}
+
+impl TextPage {
+    /// Name of the source, as shown to the user.
+    pub(super) fn name(&self) -> String {
+        match self.title.trim() {
+            "" => "Text page".to_string(),
+            title => title.to_string(),
+        }
+    }
+}
+
+/// A line of text placed on the page, at the left end of its baseline.
+#[derive(Debug, Clone, PartialEq)]
+struct Line {
+    text: String,
+    size: f32,
+    x: f32,
+    y: f32,
+}
+
+impl SourceFile {
+    fn text_page(page: TextPage) -> Result<Self> {
+        let document = text_page_document(&page)?;
+        let mut bytes = Vec::new();
+        document.clone().save_to(&mut bytes)?;
+
+        let pages = load_pdf_pages_from_bytes(&document, &bytes, None)?;
+        let text = search::index_text(&bytes)?;
+
+        Ok(Self {
+            id: random_id(),
+            source: Source::Text(page),
+            document,
+            pages,
+            signatures: Vec::new(),
+            redactions: Vec::new(),
+            separators: Vec::new(),
+            text,
+        })
+    }
+}
+
+impl Project {
+    /// The ordering with a page generated from `template` inserted at `position`. The page
+    /// introduces the pages after it, like a cover page, so its variables and group are taken
+    /// from the page after it, or the page before when it goes last.
+    pub fn insert_text_page(
+        &mut self,
+        ordering: &[Selector],
+        position: usize,
+        template: &TextPageTemplate,
+    ) -> Result<Vec<Selector>> {
+        let neighbor = neighbor(ordering, position, true)?;
+        let (width, height) = self.resolve_size(&template.size, neighbor)?;
+        let variables = self.variables(ordering, neighbor)?;
+
+        let page = TextPage {
+            title: expand(&template.title, &variables),
+            subtitle: expand(&template.subtitle, &variables),
+            text: expand(&template.text, &variables),
+            width,
+            height,
+        };
+        self.source_files.push(SourceFile::text_page(page)?);
+
+        let selector = Selector {
+            source_file_index: self.source_files.len() - 1,
+            page_index: 0,
+            rotation: Rotation::R0,
+            rasterize: None,
+            group: neighbor.and_then(|selector| selector.group.clone()),
+        };
+
+        let mut ordering = ordering.to_vec();
+        ordering.insert(position, selector);
+        Ok(ordering)
+    }
+
+    /// Values of the template variables for a page going with `neighbor`. Without a neighbor,
+    /// only the date is known.
+    fn variables(
+        &self,
+        ordering: &[Selector],
+        neighbor: Option<&Selector>,
+    ) -> Result<Vec<(&'static str, String)>> {
+        let (year, month, day) = civil_from_days((now()? / 86_400) as i64);
+        let mut variables = vec![("date", format!("{:04}-{:02}-{:02}", year, month, day))];
+
+        if let Some(neighbor) = neighbor {
+            let source_file = self
+                .source_files
+                .get(neighbor.source_file_index)
+                .ok_or_else(|| {
+                    anyhow!("Invalid source_file_index: {}", neighbor.source_file_index)
+                })?;
+            let page_count = ordering
+                .iter()
+                .filter(|selector| selector.source_file_index == neighbor.source_file_index)
+                .count();
+
+            variables.push(("source_name", source_file.name()));
+            variables.push(("page_count", page_count.to_string()));
+        }
+
+        Ok(variables)
+    }
+}
+
+/// Replaces the `{name}` of each variable in `template`. Unknown variables are left as written.
+fn expand(template: &str, variables: &[(&str, String)]) -> String {
+    variables
+        .iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+/// A one page document with the text of `page` laid out on it.
+fn text_page_document(page: &TextPage) -> Result<Document> {
+    let face = Face::parse(FONT, 0)?;
+    let lines = layout(&face, page);
+
+    // Text is written as glyph indices, which the ToUnicode map turns back into characters for
+    // copying and searching.
+    let mut glyphs = BTreeMap::new();
+    let mut operations = vec![Operation::new("BT", vec![])];
+    for line in &lines {
+        let mut encoded = Vec::new();
+        for character in line.text.chars() {
+            let glyph = glyph_index(&face, character);
+            glyphs.entry(glyph.0).or_insert(character);
+            encoded.extend(glyph.0.to_be_bytes());
+        }
+
+        operations.push(Operation::new("Tf", vec!["F0".into(), line.size.into()]));
+        operations.push(Operation::new(
+            "Tm",
+            vec![
+                1.into(),
+                0.into(),
+                0.into(),
+                1.into(),
+                line.x.into(),
+                line.y.into(),
+            ],
+        ));
+        operations.push(Operation::new(
+            "Tj",
+            vec![Object::String(encoded, StringFormat::Hexadecimal)],
+        ));
+    }
+    operations.push(Operation::new("ET", vec![]));
+
+    let mut document = Document::with_version("1.5");
+
+    let font_id = add_font(&mut document, &face, &glyphs)?;
+    let mut fonts = Dictionary::new();
+    fonts.set("F0", Object::Reference(font_id));
+    let mut resources = Dictionary::new();
+    resources.set("Font", fonts);
+
+    let content = Content { operations }.encode()?;
+    let mut content = Stream::new(Dictionary::new(), content);
+    content.compress()?;
+    let content_id = document.add_object(content);
+
+    let mut page_dictionary = Dictionary::new();
+    page_dictionary.set("Type", "Page");
+    page_dictionary.set(
+        "MediaBox",
+        vec![
+            Object::Integer(0),
+            Object::Integer(0),
+            Object::Real(page.width),
+            Object::Real(page.height),
+        ],
+    );
+    page_dictionary.set("Resources", resources);
+    page_dictionary.set("Contents", Object::Reference(content_id));
+    let page_id = document.add_object(page_dictionary);
+
+    let mut pages = Dictionary::new();
+    pages.set("Type", "Pages");
+    pages.set("Kids", vec![Object::Reference(page_id)]);
+    pages.set("Count", 1);
+    let pages_id = document.add_object(pages);
+
+    if let Some(Object::Dictionary(page)) = document.objects.get_mut(&page_id) {
+        page.set("Parent", Object::Reference(pages_id));
+    }
+
+    let mut catalog = Dictionary::new();
+    catalog.set("Type", "Catalog");
+    catalog.set("Pages", Object::Reference(pages_id));
+    let catalog_id = document.add_object(catalog);
+    document.trailer.set("Root", catalog_id);
+
+    Ok(document)
+}
+
+/// Adds the font as a composite font addressed by glyph index, with the widths and Unicode
+/// characters of the `glyphs` used.
+fn add_font(
+    document: &mut Document,
+    face: &Face,
+    glyphs: &BTreeMap<u16, char>,
+) -> Result<lopdf::ObjectId> {
+    // PDF font metrics are in thousandths of the font size.
+    let scale = 1000.0 / face.units_per_em() as f32;
+    let metric = |value: i16| Object::Integer((value as f32 * scale).round() as i64);
+
+    // Each text page is a document of its own, so embedding the whole font would add a copy of it
+    // to the export for every page.
+    let subset = subset_font(FONT, glyphs.keys().copied())?;
+    let font_name = format!("{}+{}", subset_tag(glyphs), FONT_NAME);
+
+    let mut font_file = Dictionary::new();
+    font_file.set("Length1", subset.len() as i64);
+    let mut font_file = Stream::new(font_file, subset);
+    font_file.compress()?;
+    let font_file_id = document.add_object(font_file);
+
+    let bounding_box = face.global_bounding_box();
+    let mut descriptor = Dictionary::new();
+    descriptor.set("Type", "FontDescriptor");
+    descriptor.set("FontName", font_name.as_str());
+    // Nonsymbolic.
+    descriptor.set("Flags", 32);
+    descriptor.set(
+        "FontBBox",
+        vec![
+            metric(bounding_box.x_min),
+            metric(bounding_box.y_min),
+            metric(bounding_box.x_max),
+            metric(bounding_box.y_max),
+        ],
+    );
+    descriptor.set("ItalicAngle", face.italic_angle());
+    descriptor.set("Ascent", metric(face.ascender()));
+    descriptor.set("Descent", metric(face.descender()));
+    descriptor.set(
+        "CapHeight",
+        metric(face.capital_height().unwrap_or(face.ascender())),
+    );
+    descriptor.set("StemV", 80);
+    descriptor.set("FontFile2", Object::Reference(font_file_id));
+    let descriptor_id = document.add_object(descriptor);
+
+    let widths = glyphs
+        .keys()
+        .flat_map(|glyph| {
+            let advance = face.glyph_hor_advance(GlyphId(*glyph)).unwrap_or_default();
+            [
+                Object::Integer(*glyph as i64),
+                Object::Array(vec![Object::Integer(
+                    (advance as f32 * scale).round() as i64
+                )]),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let mut system_info = Dictionary::new();
+    system_info.set("Registry", Object::string_literal("Adobe"));
+    system_info.set("Ordering", Object::string_literal("Identity"));
+    system_info.set("Supplement", 0);
+
+    let mut descendant = Dictionary::new();
+    descendant.set("Type", "Font");
+    descendant.set("Subtype", "CIDFontType2");
+    descendant.set("BaseFont", font_name.as_str());
+    descendant.set("CIDSystemInfo", system_info);
+    descendant.set("FontDescriptor", Object::Reference(descriptor_id));
+    descendant.set("W", widths);
+    descendant.set("CIDToGIDMap", "Identity");
+    let descendant_id = document.add_object(descendant);
+
+    let to_unicode = Stream::new(Dictionary::new(), to_unicode_cmap(glyphs).into_bytes());
+    let to_unicode_id = document.add_object(to_unicode);
+
+    let mut font = Dictionary::new();
+    font.set("Type", "Font");
+    font.set("Subtype", "Type0");
+    font.set("BaseFont", font_name.as_str());
+    font.set("Encoding", "Identity-H");
+    font.set("DescendantFonts", vec![Object::Reference(descendant_id)]);
+    font.set("ToUnicode", Object::Reference(to_unicode_id));
+
+    Ok(document.add_object(font))
+}
+
+/// A CMap from the two byte glyph indices of the text to the characters they were drawn for.
+fn to_unicode_cmap(glyphs: &BTreeMap<u16, char>) -> String {
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n\
+         12 dict begin\n\
+         begincmap\n\
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+         /CMapName /Adobe-Identity-UCS def\n\
+         /CMapType 2 def\n\
+         1 begincodespacerange\n\
+         <0000> <FFFF>\n\
+         endcodespacerange\n",
+    );
+
+    let glyphs = glyphs.iter().collect::<Vec<_>>();
+    // A CMap section has at most 100 entries.
+    for chunk in glyphs.chunks(100) {
+        cmap.push_str(&format!("{} beginbfchar\n", chunk.len()));
+        for (glyph, character) in chunk {
+            let unicode = character
+                .encode_utf16(&mut [0; 2])
+                .iter()
+                .map(|unit| format!("{:04X}", unit))
+                .collect::<String>();
+            cmap.push_str(&format!("<{:04X}> <{}>\n", glyph, unicode));
+        }
+        cmap.push_str("endbfchar\n");
+    }
+
+    cmap.push_str(
+        "endcmap\n\
+         CMapName currentdict /CMap defineresource pop\n\
+         end\n\
+         end\n",
+    );
+    cmap
+}
+
+/// Tag naming the subset of the font with `glyphs`, six capital letters that differ between
+/// subsets so viewers don't mistake one for another.
+fn subset_tag(glyphs: &BTreeMap<u16, char>) -> String {
+    let mut hash = glyphs.keys().fold(0u32, |hash, glyph| {
+        hash.wrapping_mul(31).wrapping_add(*glyph as u32)
+    });
+    (0..6)
+        .map(|_| {
+            let letter = (b'A' + (hash % 26) as u8) as char;
+            hash /= 26;
+            letter
+        })
+        .collect()
+}
+
+/// The TrueType `font` with only the outlines of `glyphs`, the components they are made of and
+/// the missing glyph box. Glyph indices stay the same, so text addressed by index draws the same.
+fn subset_font(font: &[u8], glyphs: impl IntoIterator<Item = u16>) -> Result<Vec<u8>> {
+    let tables = font_tables(font)?;
+    let table = |tag: &[u8; 4]| {
+        tables
+            .get(tag)
+            .copied()
+            .ok_or_else(|| anyhow!("Font has no {} table", String::from_utf8_lossy(tag)))
+    };
+
+    let head = table(b"head")?;
+    let loca = table(b"loca")?;
+    let glyf = table(b"glyf")?;
+    let glyph_count = read_u16(table(b"maxp")?, 4)? as usize;
+    let long_offsets = read_u16(head, 50)? == 1;
+
+    let glyph_range = |glyph: usize| -> Result<Range<usize>> {
+        let offset = |index: usize| -> Result<usize> {
+            Ok(match long_offsets {
+                true => read_u32(loca, index * 4)? as usize,
+                false => read_u16(loca, index * 2)? as usize * 2,
+            })
+        };
+        let range = offset(glyph)?..offset(glyph + 1)?;
+        match range.start <= range.end && range.end <= glyf.len() {
+            true => Ok(range),
+            false => Err(anyhow!("Invalid outline of glyph {}", glyph)),
+        }
+    };
+
+    let mut kept = BTreeSet::new();
+    let mut pending = glyphs.into_iter().chain([0]).collect::<Vec<_>>();
+    while let Some(glyph) = pending.pop() {
+        if (glyph as usize) < glyph_count && kept.insert(glyph) {
+            pending.extend(components(&glyf[glyph_range(glyph as usize)?])?);
+        }
+    }
+
+    // Glyphs left out keep their index with an empty outline.
+    let mut subset_glyf = Vec::new();
+    let mut subset_loca = Vec::with_capacity((glyph_count + 1) * 4);
+    for glyph in 0..glyph_count {
+        subset_loca.extend((subset_glyf.len() as u32).to_be_bytes());
+        if kept.contains(&(glyph as u16)) {
+            subset_glyf.extend_from_slice(&glyf[glyph_range(glyph)?]);
+            subset_glyf.resize(subset_glyf.len().next_multiple_of(4), 0);
+        }
+    }
+    subset_loca.extend((subset_glyf.len() as u32).to_be_bytes());
+
+    let mut subset_head = head.to_vec();
+    // Offsets are written in the long format.
+    subset_head[50..52].copy_from_slice(&1u16.to_be_bytes());
+
+    let mut subset_tables = BTreeMap::new();
+    for tag in SUBSET_TABLES {
+        let data = match tag {
+            b"glyf" => std::mem::take(&mut subset_glyf),
+            b"loca" => std::mem::take(&mut subset_loca),
+            b"head" => std::mem::take(&mut subset_head),
+            _ => match tables.get(tag) {
+                Some(data) => data.to_vec(),
+                // The hinting tables are optional.
+                None => continue,
+            },
+        };
+        subset_tables.insert(*tag, data);
+    }
+
+    Ok(write_font(subset_tables))
+}
+
+/// The tables of a TrueType font by tag.
+fn font_tables(font: &[u8]) -> Result<BTreeMap<[u8; 4], &[u8]>> {
+    let table_count = read_u16(font, 4)? as usize;
+    (0..table_count)
+        .map(|index| {
+            let record = 12 + index * 16;
+            let tag: [u8; 4] = font
+                .get(record..record + 4)
+                .and_then(|tag| tag.try_into().ok())
+                .ok_or_else(|| anyhow!("Invalid font"))?;
+            let offset = read_u32(font, record + 8)? as usize;
+            let length = read_u32(font, record + 12)? as usize;
+            let data = font
+                .get(offset..offset.saturating_add(length))
+                .ok_or_else(|| anyhow!("Invalid font table {}", String::from_utf8_lossy(&tag)))?;
+            Ok((tag, data))
+        })
+        .collect()
+}
+
+/// Glyphs a composite glyph is made of, none for a simple glyph.
+fn components(glyph: &[u8]) -> Result<Vec<u16>> {
+    const ARGS_ARE_WORDS: u16 = 0x0001;
+    const HAS_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const HAS_X_AND_Y_SCALE: u16 = 0x0040;
+    const HAS_TWO_BY_TWO: u16 = 0x0080;
+
+    // Composite glyphs have a negative number of contours, empty glyphs no header at all.
+    if glyph.is_empty() || read_u16(glyph, 0)? as i16 >= 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut components = Vec::new();
+    let mut offset = 10;
+    loop {
+        let flags = read_u16(glyph, offset)?;
+        components.push(read_u16(glyph, offset + 2)?);
+
+        offset += match flags & ARGS_ARE_WORDS {
+            0 => 6,
+            _ => 8,
+        };
+        if flags & HAS_SCALE != 0 {
+            offset += 2;
+        } else if flags & HAS_X_AND_Y_SCALE != 0 {
+            offset += 4;
+        } else if flags & HAS_TWO_BY_TWO != 0 {
+            offset += 8;
+        }
+
+        if flags & MORE_COMPONENTS == 0 {
+            return Ok(components);
+        }
+    }
+}
+
+/// A TrueType font made of `tables`, with their checksums and the one of the whole font.
+fn write_font(mut tables: BTreeMap<[u8; 4], Vec<u8>>) -> Vec<u8> {
+    let checksum = |data: &[u8]| {
+        data.chunks(4).fold(0u32, |sum, chunk| {
+            let mut word = [0; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            sum.wrapping_add(u32::from_be_bytes(word))
+        })
+    };
+
+    // The font's checksum is computed with the adjustment at zero.
+    if let Some(head) = tables.get_mut(b"head") {
+        head[8..12].fill(0);
+    }
+
+    let table_count = tables.len() as u16;
+    let entry_selector = table_count.max(1).ilog2() as u16;
+    let search_range = (1u16 << entry_selector) * 16;
+
+    let mut font = Vec::new();
+    font.extend(0x0001_0000u32.to_be_bytes());
+    font.extend(table_count.to_be_bytes());
+    font.extend(search_range.to_be_bytes());
+    font.extend(entry_selector.to_be_bytes());
+    font.extend((table_count * 16 - search_range).to_be_bytes());
+
+    let mut offset = 12 + tables.len() * 16;
+    let mut head_offset = None;
+    for (tag, data) in &tables {
+        if tag == b"head" {
+            head_offset = Some(offset);
+        }
+        font.extend(tag);
+        font.extend(checksum(data).to_be_bytes());
+        font.extend((offset as u32).to_be_bytes());
+        font.extend((data.len() as u32).to_be_bytes());
+        offset += data.len().next_multiple_of(4);
+    }
+    for data in tables.values() {
+        font.extend(data);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+
+    if let Some(head_offset) = head_offset {
+        let adjustment = 0xB1B0_AFBAu32.wrapping_sub(checksum(&font));
+        font[head_offset + 8..head_offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+    font
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| anyhow!("Invalid font"))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| anyhow!("Invalid font"))
+}
+
+/// Places the title and subtitle centered, and the text under them, the whole vertically
+/// centered. Text too long for the page is shrunk until it fits.
+fn layout(face: &Face, page: &TextPage) -> Vec<Line> {
+    let margin = MARGIN.min(page.width.min(page.height) / 8.0);
+    let available_width = page.width - 2.0 * margin;
+    let available_height = page.height - 2.0 * margin;
+
+    let blocks = [
+        (page.title.trim(), TITLE_SIZE, true),
+        (page.subtitle.trim(), SUBTITLE_SIZE, true),
+        (page.text.trim(), TEXT_SIZE, false),
+    ];
+
+    let mut scale = 1.0;
+    loop {
+        // Lines of each block, with a blank line of the block's size between blocks.
+        let mut lines = Vec::new();
+        let mut height = 0.0;
+        for (text, size, centered) in blocks.iter().filter(|(text, _, _)| !text.is_empty()) {
+            let size = size * scale;
+            if !lines.is_empty() {
+                height += size * LINE_HEIGHT;
+            }
+            for line in wrap(face, text, size, available_width) {
+                lines.push((line, size, *centered, height));
+                height += size * LINE_HEIGHT;
+            }
+        }
+
+        if height <= available_height || scale <= MIN_SCALE {
+            let top = page.height - margin - (available_height - height).max(0.0) / 2.0;
+            return lines
+                .into_iter()
+                .map(|(text, size, centered, offset)| {
+                    let x = match centered {
+                        true => (page.width - text_width(face, &text, size)) / 2.0,
+                        false => margin,
+                    };
+                    // Baselines sit a font size below the top of their line, leaving the rest
+                    // of the line height under descenders.
+                    let y = top - offset - size;
+                    Line { text, size, x, y }
+                })
+                .collect();
+        }
+
+        scale = (scale * 0.9).max(MIN_SCALE);
+    }
+}
+
+/// Breaks `text` into lines no wider than `max_width`, between words when possible. Line
+/// breaks of the text are kept.
+fn wrap(face: &Face, text: &str, size: f32, max_width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = match line.is_empty() {
+                true => word.to_string(),
+                false => format!("{} {}", line, word),
+            };
+            if text_width(face, &candidate, size) <= max_width {
+                line = candidate;
+                continue;
+            }
+
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            // Words wider than a line are broken anywhere.
+            for character in word.chars() {
+                line.push(character);
+                if line.chars().count() > 1 && text_width(face, &line, size) > max_width {
+                    line.pop();
+                    lines.push(std::mem::replace(&mut line, character.to_string()));
+                }
+            }
+        }
+        lines.push(line);
+    }
+
+    lines
+}
+
+fn text_width(face: &Face, text: &str, size: f32) -> f32 {
+    let units = text
+        .chars()
+        .map(|character| {
+            face.glyph_hor_advance(glyph_index(face, character))
+                .unwrap_or_default() as f32
+        })
+        .sum::<f32>();
+
+    units * size / face.units_per_em() as f32
+}
+
+/// The glyph drawn for a character, the font's missing glyph box when it has none.
+fn glyph_index(face: &Face, character: char) -> GlyphId {
+    face.glyph_index(character).unwrap_or(GlyphId(0))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn face() -> Face<'static> {
+        Face::parse(FONT, 0).unwrap()
+    }
+
+    fn page(title: &str, subtitle: &str, text: &str) -> TextPage {
+        TextPage {
+            title: title.to_string(),
+            subtitle: subtitle.to_string(),
+            text: text.to_string(),
+            width: 612.0,
+            height: 792.0,
+        }
+    }
+
+    #[test]
+    fn test_expand() {
+        let variables = [
+            ("source_name", "contract.pdf".to_string()),
+            ("page_count", "12".to_string()),
+        ];
+
+        assert_eq!(
+            expand("{source_name}, {page_count} pages, {date}", &variables),
+            "contract.pdf, 12 pages, {date}"
+        );
+    }
+
+    #[test]
+    fn test_wrap() {
+        let face = face();
+        let text = "The quick brown fox jumps over the lazy dog\n\nEnd";
+
+        let lines = wrap(&face, text, 12.0, 150.0);
+
+        assert!(lines.len() > 3);
+        assert!(lines
+            .iter()
+            .all(|line| text_width(&face, line, 12.0) <= 150.0));
+        assert_eq!(lines[lines.len() - 2], "");
+        assert_eq!(lines.join(" ").split_whitespace().count(), 10);
+
+        let lines = wrap(&face, &"W".repeat(40), 12.0, 150.0);
+        assert!(lines.len() > 1);
+        assert_eq!(lines.concat(), "W".repeat(40));
+    }
+
+    #[test]
+    fn test_layout() {
+        let face = face();
+        let page = page("Exhibit A", "", "Short description");
+
+        let lines = layout(&face, &page);
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].size, TITLE_SIZE);
+        let title_width = text_width(&face, "Exhibit A", TITLE_SIZE);
+        assert!((lines[0].x * 2.0 + title_width - page.width).abs() < 0.01);
+        assert_eq!(lines[1].x, MARGIN);
+        assert!(lines[0].y > page.height / 2.0 && lines[1].y < page.height / 2.0);
+    }
+
+    #[test]
+    fn test_layout_shrinks_long_text() {
+        let face = face();
+        let page = page("Exhibit B", "Subtitle", &"Line of text\n".repeat(60));
+
+        let lines = layout(&face, &page);
+
+        assert_eq!(lines.len(), 62);
+        assert!(lines[0].size < TITLE_SIZE);
+        assert!(lines.last().unwrap().y >= MARGIN);
+    }
+
+    #[test]
+    fn test_text_page_document() {
+        let document = text_page_document(&page("Exhibit Ω", "Sub", "")).unwrap();
+
+        assert_eq!(document.get_pages().len(), 1);
+        let text = document.extract_text(&[1]).unwrap();
+        assert!(text.contains("Exhibit Ω"));
+        assert!(text.contains("Sub"));
+
+        // Only the glyphs used are embedded.
+        let font_file = document
+            .objects
+            .values()
+            .filter_map(|object| object.as_stream().ok())
+            .find(|stream| stream.dict.has(b"Length1"))
+            .unwrap();
+        assert!(font_file.content.len() < FONT.len() / 10);
+    }
+
+    /// Number of segments of the outline of `glyph`.
+    fn outline_length(face: &Face, glyph: u16) -> usize {
+        struct Counter(usize);
+        impl ttf_parser::OutlineBuilder for Counter {
+            fn move_to(&mut self, _: f32, _: f32) {
+                self.0 += 1;
+            }
+            fn line_to(&mut self, _: f32, _: f32) {
+                self.0 += 1;
+            }
+            fn quad_to(&mut self, _: f32, _: f32, _: f32, _: f32) {
+                self.0 += 1;
+            }
+            fn curve_to(&mut self, _: f32, _: f32, _: f32, _: f32, _: f32, _: f32) {
+                self.0 += 1;
+            }
+            fn close(&mut self) {}
+        }
+
+        let mut counter = Counter(0);
+        face.outline_glyph(GlyphId(glyph), &mut counter);
+        counter.0
+    }
+
+    #[test]
+    fn test_subset_font() {
+        let face = face();
+        let [a, e_acute, z] = ['A', 'é', 'Z'].map(|character| glyph_index(&face, character).0);
+
+        let subset = subset_font(FONT, [a, e_acute]).unwrap();
+
+        assert!(subset.len() < FONT.len() / 10);
+        let subset_face = Face::parse(&subset, 0).unwrap();
+        assert_eq!(subset_face.number_of_glyphs(), face.number_of_glyphs());
+        // Composite glyphs keep the glyphs they are made of.
+        for glyph in [0, a, e_acute] {
+            assert!(outline_length(&face, glyph) > 0);
+            assert_eq!(
+                outline_length(&subset_face, glyph),
+                outline_length(&face, glyph)
+            );
+        }
+        assert_eq!(outline_length(&subset_face, z), 0);
+        assert!(subset_font(&FONT[..100], [a]).is_err());
+    }
+
+    #[test]
+    fn test_to_unicode_cmap() {
+        let glyphs = BTreeMap::from([(36, 'A'), (1000, '😀')]);
+
+        let cmap = to_unicode_cmap(&glyphs);
+
+        assert!(cmap.contains("2 beginbfchar\n<0024> <0041>\n<03E8> <D83DDE00>\nendbfchar"));
+    }
+
+    #[test]
+    fn test_insert_text_page() {
+        let mut project = Project {
+            source_files: vec![SourceFile::open(&PathBuf::from("test/basic.pdf"), None).unwrap()],
+        };
+        let ordering = vec![Selector::new(0, 0), Selector::new(0, 2)];
+        let template = TextPageTemplate {
+            title: "Exhibit A".to_string(),
+            subtitle: "{source_name}, {page_count} pages".to_string(),
+            text: String::new(),
+            size: PageSize::Custom {
+                width: 595.0,
+                height: 842.0,
+            },
+        };
+
+        let inserted = project.insert_text_page(&ordering, 0, &template).unwrap();
+
+        assert_eq!(inserted.len(), 3);
+        assert_eq!(inserted[0].source_file_index, 1);
+        let Source::Text(page) = &project.source_files[1].source else {
+            panic!("Not a text page");
+        };
+        assert_eq!(page.subtitle, "basic.pdf, 2 pages");
+        assert_eq!(project.source_files[1].name(), "Exhibit A");
+        assert_eq!(project.source_files[1].pages.len(), 1);
+    }
+}
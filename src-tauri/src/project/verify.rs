@@ -0,0 +1,341 @@
+use super::downsample::DownsampleReport;
+use super::sanitize::SanitizeReport;
+use super::{pdfium, Project, Selector, SigningOptions};
+use anyhow::{anyhow, Result};
+use image::imageops::{self, FilterType};
+use image::RgbImage;
+use lopdf::Document;
+use pdfium_render::prelude::*;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// Size of the renders being compared. Small enough to be quick, large enough to notice a missing
+/// image or a page turned the wrong way.
+const RENDER_SIZE: u32 = 200;
+
+/// Mean difference per channel, from 0 to 1, above which two renders are considered different.
+const TOLERANCE: f32 = 0.02;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportVerification {
+    expected_page_count: usize,
+    page_count: usize,
+    mismatches: Vec<PageMismatch>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PageMismatch {
+    /// Position of the page in the exported file.
+    index: usize,
+    source_file_index: usize,
+    page_index: usize,
+    /// `None` when the pages don't have the same proportions and couldn't be compared.
+    difference: Option<f32>,
+}
+
+/// Changes made on purpose to an exported file after its pages were put together, which
+/// verification doesn't report.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExpectedChanges {
+    /// Annotations were removed from any page.
+    annotations: bool,
+    /// A visible signature was added to this page.
+    signature_page: Option<usize>,
+    /// Images were resampled or recompressed.
+    images: bool,
+}
+
+impl ExpectedChanges {
+    /// The changes of an export sanitized, downsampled and signed with these results and options.
+    pub fn new(
+        sanitized: Option<&SanitizeReport>,
+        downsampled: Option<&DownsampleReport>,
+        signing: Option<&SigningOptions>,
+    ) -> Self {
+        Self {
+            annotations: sanitized.is_some_and(SanitizeReport::removed_annotations),
+            signature_page: signing.and_then(SigningOptions::appearance_page),
+            images: downsampled.is_some_and(|downsampled| !downsampled.is_empty()),
+        }
+    }
+
+    fn changes_annotations(&self, index: usize) -> bool {
+        self.annotations || self.signature_page == Some(index)
+    }
+}
+
+impl ExportVerification {
+    pub fn is_valid(&self) -> bool {
+        self.page_count == self.expected_page_count && self.mismatches.is_empty()
+    }
+}
+
+impl Display for ExportVerification {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.page_count != self.expected_page_count {
+            writeln!(
+                f,
+                "The exported file has {} pages, {} were expected.",
+                self.page_count, self.expected_page_count
+            )?;
+        }
+
+        for mismatch in &self.mismatches {
+            writeln!(
+                f,
+                "Page {} doesn't look like page {} of document {}.",
+                mismatch.index + 1,
+                mismatch.page_index + 1,
+                mismatch.source_file_index + 1
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Project {
+    /// Re-opens an exported file and checks that every page renders like the page it was
+    /// exported from. Annotations aren't compared when `changes` has some, and pages with images
+    /// aren't compared when they were downsampled.
+    pub fn verify_export(
+        &self,
+        path: &Path,
+        selectors: &[Selector],
+        changes: ExpectedChanges,
+    ) -> Result<ExportVerification> {
+        let written = Document::load(path)?;
+
+        let pdfium = pdfium()?;
+        let exported = pdfium.load_pdf_from_file(path, None)?;
+
+        let page_count = exported.pages().len() as usize;
+        if page_count != written.get_pages().len() {
+            return Err(anyhow!(
+                "The exported file is inconsistent: found {} pages with lopdf and {} with pdfium",
+                written.get_pages().len(),
+                page_count
+            ));
+        }
+
+        let source_bytes = self
+            .source_files
+            .iter()
+            .map(|source_file| source_file.to_bytes())
+            .collect::<Result<Vec<_>>>()?;
+        let sources = source_bytes
+            .iter()
+            .map(|bytes| pdfium.load_pdf_from_byte_slice(bytes, None))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let page_ids = written.get_pages().into_values().collect::<Vec<_>>();
+        let mut mismatches = Vec::new();
+
+        for (index, (selector, page)) in selectors.iter().zip(exported.pages().iter()).enumerate() {
+            let source_page = sources
+                .get(selector.source_file_index)
+                .and_then(|document| document.pages().iter().nth(selector.page_index))
+                .ok_or_else(|| anyhow!("Selector {} doesn't match a source page", index))?;
+
+            // Redacted and rasterized pages are expected to differ from their source.
+            let redacted =
+                self.source_files[selector.source_file_index].page_redactions(selector.page_index);
+            if !redacted.is_empty() || selector.rasterize.is_some() {
+                continue;
+            }
+
+            // So are pages whose images were downsampled.
+            let has_images = !written
+                .get_page_images(page_ids[index])
+                .unwrap_or_default()
+                .is_empty();
+            if changes.images && has_images {
+                continue;
+            }
+
+            // Exporting replaces the page's own rotation when the selector rotates it.
+            let rotation = match selector.rotation.as_rotation() {
+                Some(rotation) => {
+                    let intrinsic = source_page.rotation()?.as_degrees() as u32;
+                    (rotation + 360 - intrinsic) % 360
+                }
+                None => 0,
+            };
+
+            let annotations = !changes.changes_annotations(index);
+            let expected = render(&source_page, rotation, annotations)?;
+            let actual = render(&page, 0, annotations)?;
+
+            let difference = difference(&expected, &actual);
+            if !matches!(difference, Some(difference) if difference <= TOLERANCE) {
+                mismatches.push(PageMismatch {
+                    index,
+                    source_file_index: selector.source_file_index,
+                    page_index: selector.page_index,
+                    difference,
+                });
+            }
+        }
+
+        Ok(ExportVerification {
+            expected_page_count: selectors.len(),
+            page_count,
+            mismatches,
+        })
+    }
+}
+
+fn render(page: &PdfPage, rotation: u32, annotations: bool) -> Result<RgbImage> {
+    let render_config = PdfRenderConfig::new()
+        .set_target_width(RENDER_SIZE as Pixels)
+        .set_maximum_height(RENDER_SIZE as Pixels)
+        .render_annotations(annotations)
+        .render_form_data(annotations);
+
+    let img = page
+        .render_with_config(&render_config)?
+        .as_image()
+        .into_rgb8();
+
+    Ok(match rotation {
+        90 => imageops::rotate90(&img),
+        180 => imageops::rotate180(&img),
+        270 => imageops::rotate270(&img),
+        _ => img,
+    })
+}
+
+fn difference(expected: &RgbImage, actual: &RgbImage) -> Option<f32> {
+    let (width, height) = expected.dimensions();
+
+    // Renders are fitted in the same box, a pixel or two of rounding is expected.
+    if width.abs_diff(actual.width()) > 2 || height.abs_diff(actual.height()) > 2 {
+        return None;
+    }
+
+    let resized;
+    let actual = if actual.dimensions() != (width, height) {
+        resized = imageops::resize(actual, width, height, FilterType::Triangle);
+        &resized
+    } else {
+        actual
+    };
+
+    let total: u64 = expected
+        .as_raw()
+        .iter()
+        .zip(actual.as_raw())
+        .map(|(a, b)| a.abs_diff(*b) as u64)
+        .sum();
+
+    Some(total as f32 / (expected.as_raw().len() as f32 * 255.0))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::project::{Rotation, SourceFile};
+    use lopdf::{dictionary, Stream};
+    use std::path::PathBuf;
+
+    fn export_to_temp_file(project: &Project, selectors: &[Selector], name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(name);
+        project.export(selectors).unwrap().0.save(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_verify_export() {
+        let project = Project {
+            source_files: vec![
+                SourceFile::open(&PathBuf::from("test/basic.pdf"), None).unwrap(),
+                SourceFile::open(&PathBuf::from("test/paysage.pdf"), None).unwrap(),
+            ],
+        };
+        let selectors = vec![
+            Selector::new(0, 0),
+            Selector {
+                source_file_index: 1,
+                page_index: 1,
+                rotation: Rotation::R90,
+                rasterize: None,
+                group: None,
+            },
+        ];
+        let path = export_to_temp_file(&project, &selectors, "rancher-verify-export.pdf");
+
+        let verification = project
+            .verify_export(&path, &selectors, ExpectedChanges::default())
+            .unwrap();
+
+        assert!(verification.is_valid(), "{}", verification);
+    }
+
+    #[test]
+    fn test_verify_export_reports_mismatches() {
+        let project = Project {
+            source_files: vec![SourceFile::open(&PathBuf::from("test/basic.pdf"), None).unwrap()],
+        };
+        let selectors = vec![Selector::new(0, 0), Selector::new(0, 1)];
+        let path = export_to_temp_file(&project, &selectors, "rancher-verify-mismatch.pdf");
+
+        let swapped = vec![
+            Selector::new(0, 1),
+            Selector::new(0, 0),
+            Selector::new(0, 2),
+        ];
+        let verification = project
+            .verify_export(&path, &swapped, ExpectedChanges::default())
+            .unwrap();
+
+        assert!(!verification.is_valid());
+        assert_eq!(2, verification.page_count);
+        assert_eq!(3, verification.expected_page_count);
+        assert_eq!(2, verification.mismatches.len());
+    }
+
+    #[test]
+    fn test_verify_export_expected_changes() {
+        let project = Project {
+            source_files: vec![SourceFile::open(&PathBuf::from("test/basic.pdf"), None).unwrap()],
+        };
+        let selectors = vec![Selector::new(0, 0)];
+
+        // An annotation covering the page, like a visible signature would.
+        let (mut document, _) = project.export(&selectors).unwrap();
+        let appearance = document.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            },
+            b"0 g 0 0 612 792 re f".to_vec(),
+        ));
+        let annotation = document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Square",
+            "Rect" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            "AP" => dictionary! { "N" => appearance },
+        });
+        let page_id = document.page_iter().next().unwrap();
+        document
+            .get_dictionary_mut(page_id)
+            .unwrap()
+            .set("Annots", vec![annotation.into()]);
+        let path = std::env::temp_dir().join("rancher-verify-changes.pdf");
+        document.save(&path).unwrap();
+
+        let verification = project
+            .verify_export(&path, &selectors, ExpectedChanges::default())
+            .unwrap();
+        assert!(!verification.is_valid());
+
+        let changes = ExpectedChanges {
+            signature_page: Some(0),
+            ..ExpectedChanges::default()
+        };
+        let verification = project.verify_export(&path, &selectors, changes).unwrap();
+        assert!(verification.is_valid(), "{}", verification);
+    }
+}
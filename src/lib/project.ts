@@ -1,11 +1,82 @@
 export type Page = {
   preview_jpg: string
   dimensions: [number, number]
+  media_box: [number, number]
+  crop_box: [number, number]
+  rotation: number
+  label: string | null
+  annotation_count: number
+  form_field_count: number
+  has_text: boolean
+  image_only: boolean
+  estimated_size: number
+}
+
+export type Signature = {
+  field: string | null
+  signer: string | null
+  signed_at: string | null
+  covers_whole_document: boolean
+}
+
+export type Redaction = {
+  page_index: number
+  x: number
+  y: number
+  width: number
+  height: number
+  color: [number, number, number]
+}
+
+export type Separator = {
+  page_index: number
+  name: string
+}
+
+export type HitRect = {
+  x: number
+  y: number
+  width: number
+  height: number
+}
+
+export type SearchResult = {
+  source_file_index: number
+  page_index: number
+  snippet: string
+  highlight: { start: number, end: number }
+  hits: HitRect[]
+}
+
+export type OrderingOperation =
+  | { operation: "interleave", first: number, second: number, reverse_second?: boolean }
+  | { operation: "reverse", pages: { start: number, end: number } }
+  | { operation: "sort_sources", by: "name" | "date" | "title", descending?: boolean }
+  | { operation: "collate", copies: number }
+
+export type PageSize =
+  | { size: "custom", width: number, height: number }
+  | { size: "neighbor" }
+
+export type TextPageTemplate = {
+  title: string,
+  subtitle?: string,
+  text?: string,
+  size: PageSize,
+}
+
+export type PageRangeError = {
+  position: number
+  length: number
+  message: string
 }
 
 export type SourceFile = {
   pages: Page[]
   path: string,
+  signatures: Signature[],
+  redactions: Redaction[],
+  separators: Separator[],
 }
 
 export type Ordering = {
@@ -14,6 +85,42 @@ export type Ordering = {
   page_index: number,
   enabled: boolean,
   rotation: number,
+  rasterize?: RasterizeOptions | null,
+  group?: string | null,
+}
+
+export type RasterizeOptions = {
+  dpi: number,
+  color_mode: "color" | "grayscale" | "monochrome",
+}
+
+export type PageImageOptions = {
+  format?: "png" | "jpeg" | "tiff" | "webp",
+  dpi?: number,
+  color_mode?: "color" | "grayscale" | "monochrome",
+  quality?: number,
+}
+
+export type TextOptions = {
+  format?: "plain" | "markdown",
+  preserve_layout?: boolean,
+  mark_empty_pages?: boolean,
+}
+
+export type ArchiveOptions = {
+  format?: "tiff" | "cbz",
+  dpi?: number,
+  color_mode?: "color" | "grayscale" | "monochrome",
+  quality?: number,
+}
+
+export type DocumentMetadata = {
+  title: string | null
+  author: string | null
+  subject: string | null
+  keywords: string | null
+  creator: string | null
+  producer: string | null
 }
 
 export type Project = {